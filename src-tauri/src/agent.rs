@@ -0,0 +1,716 @@
+//! Agent mode: multi-step tasks run with the user's approval
+//!
+//! A run starts from a goal typed in a session. Each turn the model proposes
+//! one command (`ai_agent_next`); the user approves, edits or rejects it
+//! (`ai_agent_decide`), and approved commands run silently on an exec channel
+//! of the session (`ssh::exec_in_session`). The exit code, stdout and stderr
+//! are fed back to the model for the next proposal until it reports the goal
+//! done. Every step is stored with its risk assessment and result so a run can
+//! be reviewed later.
+//!
+//! Limits are enforced here, not by the model: a run proposes at most
+//! `max_steps` commands, and a command riskier than the run's `max_risk`
+//! (never above high, see `risk::assess`) is blocked instead of offered.
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::ai::{self, PromptLanguage};
+use crate::ai_provider::{ChatRole, ChatTurn, GenerateRequest};
+use crate::command_extract;
+use crate::redact::Redactor;
+use crate::risk::{self, Severity};
+use crate::ssh::ExecOutput;
+
+pub const DEFAULT_MAX_STEPS: u32 = 10;
+pub const MAX_STEPS_LIMIT: u32 = 30;
+pub const DEFAULT_MAX_RISK: Severity = Severity::Medium;
+
+/// Time limit of one step on the server
+pub const STEP_TIMEOUT_SECS: u64 = 120;
+
+/// Output of a step sent back to the model (the end is kept)
+const MAX_FEEDBACK_CHARS: usize = 3000;
+
+/// Output of a step kept in the database
+const MAX_STORED_OUTPUT: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Cancelled,
+    /// Stopped at `max_steps`
+    LimitReached,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Completed => "completed",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::LimitReached => "limit_reached",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "running" => Some(RunStatus::Running),
+            "completed" => Some(RunStatus::Completed),
+            "cancelled" => Some(RunStatus::Cancelled),
+            "limit_reached" => Some(RunStatus::LimitReached),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    /// Waiting for the user
+    Proposed,
+    /// Approved and being executed
+    Running,
+    Executed,
+    Rejected,
+    /// Over the run's risk limit; cannot be approved
+    Blocked,
+    /// Could not be run (session closed, timeout)
+    Failed,
+}
+
+impl StepStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepStatus::Proposed => "proposed",
+            StepStatus::Running => "running",
+            StepStatus::Executed => "executed",
+            StepStatus::Rejected => "rejected",
+            StepStatus::Blocked => "blocked",
+            StepStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "proposed" => Some(StepStatus::Proposed),
+            "running" => Some(StepStatus::Running),
+            "executed" => Some(StepStatus::Executed),
+            "rejected" => Some(StepStatus::Rejected),
+            "blocked" => Some(StepStatus::Blocked),
+            "failed" => Some(StepStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentRun {
+    pub id: String,
+    pub profile_id: Option<String>,
+    pub session_id: String,
+    pub goal: String,
+    /// Directory every step starts in (the terminal's cwd when the run started)
+    pub cwd: Option<String>,
+    pub os_info: Option<String>,
+    pub status: RunStatus,
+    /// The model's summary when it reported the goal done
+    pub summary: Option<String>,
+    pub max_steps: u32,
+    pub max_risk: Severity,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub step_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentStep {
+    pub id: i64,
+    pub run_id: String,
+    /// 1-based position in the run
+    pub idx: u32,
+    pub thought: String,
+    pub command: String,
+    pub risk: Option<Severity>,
+    pub risk_explanation: Option<String>,
+    pub status: StepStatus,
+    /// Rejection reason given by the user
+    pub note: Option<String>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub proposed_at: i64,
+    pub executed_at: Option<i64>,
+}
+
+/// A run together with its steps
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentRunReport {
+    pub run: AgentRun,
+    pub steps: Vec<AgentStep>,
+}
+
+/// Result of asking for the next step
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentTurn {
+    pub run: AgentRun,
+    /// The new step (None when the run finished or generation was cancelled)
+    pub step: Option<AgentStep>,
+    pub model: String,
+    pub cancelled: bool,
+}
+
+/// What the model wants to do next
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proposal {
+    Step { thought: String, command: String },
+    Done { summary: String },
+}
+
+/// Risk of a command under a run's limit
+#[derive(Debug, Clone)]
+pub struct StepRisk {
+    pub severity: Option<Severity>,
+    pub explanation: Option<String>,
+    pub blocked: bool,
+}
+
+/// Requested limits brought within the hard ones
+pub fn clamp_limits(max_steps: Option<u32>, max_risk: Option<Severity>) -> (u32, Severity) {
+    (
+        max_steps.unwrap_or(DEFAULT_MAX_STEPS).clamp(1, MAX_STEPS_LIMIT),
+        max_risk.unwrap_or(DEFAULT_MAX_RISK).min(Severity::High),
+    )
+}
+
+pub fn assess_step(run: &AgentRun, command: &str) -> StepRisk {
+    let report = risk::assess(command);
+    let explanation = Some(
+        report
+            .findings
+            .iter()
+            .map(|f| f.explanation.as_str())
+            .collect::<Vec<_>>()
+            .join(" / "),
+    )
+    .filter(|e| !e.is_empty());
+    StepRisk {
+        severity: report.severity,
+        explanation,
+        blocked: report.severity.is_some_and(|s| s > run.max_risk),
+    }
+}
+
+/// The command sent to the exec channel: each step starts in the run's directory
+pub fn exec_command(run: &AgentRun, command: &str) -> String {
+    match &run.cwd {
+        Some(cwd) => format!("cd '{}' || exit 1\n{}", cwd.replace('\'', r"'\''"), command),
+        None => command.to_string(),
+    }
+}
+
+// ============================================================================
+// Storage
+// ============================================================================
+
+const SELECT_RUN: &str = "SELECT r.id, r.profile_id, r.session_id, r.goal, r.cwd, r.os_info, r.status, r.summary,
+        r.max_steps, r.max_risk, r.created_at, r.updated_at,
+        (SELECT COUNT(*) FROM ai_agent_steps s WHERE s.run_id = r.id)
+     FROM ai_agent_runs r";
+
+fn row_to_run(row: &rusqlite::Row) -> Result<AgentRun> {
+    let status: String = row.get(6)?;
+    let max_risk: String = row.get(9)?;
+    Ok(AgentRun {
+        id: row.get(0)?,
+        profile_id: row.get(1)?,
+        session_id: row.get(2)?,
+        goal: row.get(3)?,
+        cwd: row.get(4)?,
+        os_info: row.get(5)?,
+        status: RunStatus::parse(&status).unwrap_or(RunStatus::Cancelled),
+        summary: row.get(7)?,
+        max_steps: row.get(8)?,
+        max_risk: Severity::parse(&max_risk).unwrap_or(DEFAULT_MAX_RISK),
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        step_count: row.get(12)?,
+    })
+}
+
+const SELECT_STEP: &str = "SELECT id, run_id, idx, thought, command, risk, risk_explanation, status, note,
+        stdout, stderr, exit_code, error, duration_ms, proposed_at, executed_at
+     FROM ai_agent_steps";
+
+fn row_to_step(row: &rusqlite::Row) -> Result<AgentStep> {
+    let risk: Option<String> = row.get(5)?;
+    let status: String = row.get(7)?;
+    Ok(AgentStep {
+        id: row.get(0)?,
+        run_id: row.get(1)?,
+        idx: row.get(2)?,
+        thought: row.get(3)?,
+        command: row.get(4)?,
+        risk: risk.as_deref().and_then(Severity::parse),
+        risk_explanation: row.get(6)?,
+        status: StepStatus::parse(&status).unwrap_or(StepStatus::Failed),
+        note: row.get(8)?,
+        stdout: row.get(9)?,
+        stderr: row.get(10)?,
+        exit_code: row.get(11)?,
+        error: row.get(12)?,
+        duration_ms: row.get(13)?,
+        proposed_at: row.get(14)?,
+        executed_at: row.get(15)?,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_run(
+    conn: &Connection,
+    profile_id: Option<&str>,
+    session_id: &str,
+    goal: &str,
+    cwd: Option<&str>,
+    os_info: Option<&str>,
+    max_steps: u32,
+    max_risk: Severity,
+    now: i64,
+) -> Result<AgentRun> {
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO ai_agent_runs (id, profile_id, session_id, goal, cwd, os_info, status, max_steps, max_risk, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+        params![
+            &id,
+            profile_id,
+            session_id,
+            goal.trim(),
+            cwd,
+            os_info,
+            RunStatus::Running.as_str(),
+            max_steps,
+            max_risk.as_str(),
+            now
+        ],
+    )?;
+    Ok(AgentRun {
+        id,
+        profile_id: profile_id.map(str::to_string),
+        session_id: session_id.to_string(),
+        goal: goal.trim().to_string(),
+        cwd: cwd.map(str::to_string),
+        os_info: os_info.map(str::to_string),
+        status: RunStatus::Running,
+        summary: None,
+        max_steps,
+        max_risk,
+        created_at: now,
+        updated_at: now,
+        step_count: 0,
+    })
+}
+
+pub fn get_run(conn: &Connection, id: &str) -> Result<Option<AgentRun>> {
+    let sql = format!("{} WHERE r.id = ?1", SELECT_RUN);
+    conn.query_row(&sql, params![id], row_to_run).optional()
+}
+
+/// Runs of a profile (None: runs without a profile), most recent first
+pub fn list_runs(conn: &Connection, profile_id: Option<&str>) -> Result<Vec<AgentRun>> {
+    let sql = format!("{} WHERE r.profile_id IS ?1 ORDER BY r.updated_at DESC, r.rowid DESC", SELECT_RUN);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![profile_id], row_to_run)?;
+    rows.collect()
+}
+
+/// Steps of a run in order
+pub fn steps(conn: &Connection, run_id: &str) -> Result<Vec<AgentStep>> {
+    let sql = format!("{} WHERE run_id = ?1 ORDER BY idx", SELECT_STEP);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![run_id], row_to_step)?;
+    rows.collect()
+}
+
+pub fn get_step(conn: &Connection, id: i64) -> Result<Option<AgentStep>> {
+    let sql = format!("{} WHERE id = ?1", SELECT_STEP);
+    conn.query_row(&sql, params![id], row_to_step).optional()
+}
+
+pub fn set_run_status(conn: &Connection, id: &str, status: RunStatus, summary: Option<&str>, now: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE ai_agent_runs SET status = ?2, summary = COALESCE(?3, summary), updated_at = ?4 WHERE id = ?1",
+        params![id, status.as_str(), summary, now],
+    )?;
+    Ok(())
+}
+
+/// Store a proposed step; it is blocked right away if it is over the run's risk limit
+pub fn add_step(conn: &Connection, run: &AgentRun, thought: &str, command: &str, now: i64) -> Result<AgentStep> {
+    let risk = assess_step(run, command);
+    let status = if risk.blocked { StepStatus::Blocked } else { StepStatus::Proposed };
+    let idx: u32 = conn.query_row(
+        "SELECT COALESCE(MAX(idx), 0) + 1 FROM ai_agent_steps WHERE run_id = ?1",
+        params![&run.id],
+        |row| row.get(0),
+    )?;
+    conn.execute(
+        "INSERT INTO ai_agent_steps (run_id, idx, thought, command, risk, risk_explanation, status, proposed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            &run.id,
+            idx,
+            thought.trim(),
+            command.trim(),
+            risk.severity.map(|s| s.as_str()),
+            risk.explanation,
+            status.as_str(),
+            now
+        ],
+    )?;
+    conn.execute("UPDATE ai_agent_runs SET updated_at = ?2 WHERE id = ?1", params![&run.id, now])?;
+    Ok(get_step(conn, conn.last_insert_rowid())?.expect("step just inserted"))
+}
+
+/// The user replaced the proposed command; re-assessed like a new proposal
+/// Returns false if the edited command is over the limit (the step is then blocked)
+pub fn edit_step(conn: &Connection, run: &AgentRun, step_id: i64, command: &str) -> Result<bool> {
+    let risk = assess_step(run, command);
+    let status = if risk.blocked { StepStatus::Blocked } else { StepStatus::Proposed };
+    conn.execute(
+        "UPDATE ai_agent_steps SET command = ?2, risk = ?3, risk_explanation = ?4, status = ?5 WHERE id = ?1",
+        params![step_id, command.trim(), risk.severity.map(|s| s.as_str()), risk.explanation, status.as_str()],
+    )?;
+    Ok(!risk.blocked)
+}
+
+pub fn reject_step(conn: &Connection, step_id: i64, note: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE ai_agent_steps SET status = ?2, note = ?3 WHERE id = ?1",
+        params![step_id, StepStatus::Rejected.as_str(), note.map(str::trim).filter(|n| !n.is_empty())],
+    )?;
+    Ok(())
+}
+
+/// Claim a proposed step for execution; false if it was already decided (double approval)
+pub fn start_step(conn: &Connection, step_id: i64) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE ai_agent_steps SET status = ?2 WHERE id = ?1 AND status = ?3",
+        params![step_id, StepStatus::Running.as_str(), StepStatus::Proposed.as_str()],
+    )?;
+    Ok(changed > 0)
+}
+
+fn stored(output: &str) -> String {
+    let skip = output.len().saturating_sub(MAX_STORED_OUTPUT);
+    let mut start = skip;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    output[start..].to_string()
+}
+
+pub fn record_execution(
+    conn: &Connection,
+    step_id: i64,
+    result: &std::result::Result<ExecOutput, String>,
+    duration_ms: i64,
+    now: i64,
+) -> Result<()> {
+    match result {
+        Ok(output) => conn.execute(
+            "UPDATE ai_agent_steps SET status = ?2, stdout = ?3, stderr = ?4, exit_code = ?5, duration_ms = ?6, executed_at = ?7
+             WHERE id = ?1",
+            params![
+                step_id,
+                StepStatus::Executed.as_str(),
+                stored(&output.stdout),
+                stored(&output.stderr),
+                output.exit_code,
+                duration_ms,
+                now
+            ],
+        )?,
+        Err(error) => conn.execute(
+            "UPDATE ai_agent_steps SET status = ?2, error = ?3, duration_ms = ?4, executed_at = ?5 WHERE id = ?1",
+            params![step_id, StepStatus::Failed.as_str(), error, duration_ms, now],
+        )?,
+    };
+    conn.execute(
+        "UPDATE ai_agent_runs SET updated_at = ?2 WHERE id = (SELECT run_id FROM ai_agent_steps WHERE id = ?1)",
+        params![step_id, now],
+    )?;
+    Ok(())
+}
+
+pub fn delete_run(conn: &Connection, id: &str) -> Result<bool> {
+    conn.execute("DELETE FROM ai_agent_steps WHERE run_id = ?1", params![id])?;
+    Ok(conn.execute("DELETE FROM ai_agent_runs WHERE id = ?1", params![id])? > 0)
+}
+
+// ============================================================================
+// Prompt
+// ============================================================================
+
+const SYSTEM_PROMPT: &str = r#"You are an autonomous Linux/Unix operator working on a remote server through SSH.
+You reach the goal below one shell command at a time. After each command you are shown its exit code, stdout and stderr.
+
+Reply with a single JSON object and nothing else, either the next step:
+{"thought": "why this step, in one or two sentences", "command": "the shell command"}
+or, when the goal is reached or cannot be reached:
+{"done": true, "summary": "what was done and the outcome"}
+
+Rules:
+- Every command runs in a new non-interactive shell without a terminal or input: never use editors, pagers, prompts or commands that wait for input (use -y, --no-pager, cat, tee, sed -i)
+- Each command starts in the same directory; `cd` does not carry over, so chain with && when needed
+- Prefer read-only inspection before changing anything, and the smallest change that reaches the goal
+- The user approves every command and may reject it; then propose something else or finish
+- Commands over the risk limit are blocked; do not try to get around the limit
+- At most {max_steps} commands in total
+
+## Goal
+{goal}
+
+{language}"#;
+
+fn proposal_json(step: &AgentStep) -> String {
+    serde_json::json!({ "thought": step.thought, "command": step.command }).to_string()
+}
+
+/// The result of a step as the model sees it
+fn observation(step: &AgentStep, max_risk: Severity, redactor: &Redactor) -> String {
+    let tail = |text: &str| {
+        let text = redactor.redact_text(text.trim());
+        let skip = text.chars().count().saturating_sub(MAX_FEEDBACK_CHARS);
+        let note = if skip > 0 { "(truncated, last part)\n" } else { "" };
+        format!("{}{}", note, text.chars().skip(skip).collect::<String>())
+    };
+
+    match step.status {
+        StepStatus::Executed => {
+            let mut text = format!("Step {} exit code: {}", step.idx, step.exit_code.unwrap_or(-1));
+            for (label, output) in [("stdout", &step.stdout), ("stderr", &step.stderr)] {
+                match output.as_deref().filter(|o| !o.trim().is_empty()) {
+                    Some(output) => text.push_str(&format!("\n## {}\n```\n{}\n```", label, tail(output))),
+                    None => text.push_str(&format!("\n## {}\n(empty)", label)),
+                }
+            }
+            text
+        }
+        StepStatus::Rejected => match &step.note {
+            Some(note) => format!("The user rejected step {}: {}\nPropose a different step or finish.", step.idx, note),
+            None => format!("The user rejected step {}. Propose a different step or finish.", step.idx),
+        },
+        StepStatus::Blocked => format!(
+            "Step {} was blocked: its risk ({}) is over the limit ({}): {}\nPropose a safer step or finish.",
+            step.idx,
+            step.risk.map_or("unknown", |r| r.as_str()),
+            max_risk.as_str(),
+            step.risk_explanation.as_deref().unwrap_or("")
+        ),
+        StepStatus::Failed => format!(
+            "Step {} could not be run: {}",
+            step.idx,
+            step.error.as_deref().unwrap_or("unknown error")
+        ),
+        StepStatus::Proposed | StepStatus::Running => format!("Step {} has not run yet.", step.idx),
+    }
+}
+
+/// Prompt for the next proposal: the goal in the system prompt, then each step and its result
+pub fn build_request(
+    run: &AgentRun,
+    steps: &[AgentStep],
+    language: PromptLanguage,
+    redactor: &Redactor,
+    context_size: Option<u32>,
+) -> GenerateRequest {
+    let system = SYSTEM_PROMPT
+        .replace("{max_steps}", &run.max_steps.to_string())
+        .replace("{goal}", &redactor.redact_text(&run.goal))
+        .replace("{language}", language.rule());
+
+    let mut start = vec!["## Environment".to_string()];
+    if let Some(os) = &run.os_info {
+        start.push(format!("Operating System: {}", os));
+    }
+    if let Some(cwd) = &run.cwd {
+        start.push(format!("Working Directory: {}", cwd));
+    }
+    start.push(format!("Risk limit: {}", run.max_risk.as_str()));
+    start.push(String::new());
+    start.push("Propose the first step.".to_string());
+
+    let mut turns = vec![ChatTurn {
+        role: ChatRole::User,
+        content: start.join("\n"),
+    }];
+    for step in steps {
+        turns.push(ChatTurn {
+            role: ChatRole::Assistant,
+            content: proposal_json(step),
+        });
+        let mut text = observation(step, run.max_risk, redactor);
+        let remaining = run.max_steps.saturating_sub(step.idx);
+        text.push_str(&format!("\n({} steps left)", remaining));
+        turns.push(ChatTurn {
+            role: ChatRole::User,
+            content: text,
+        });
+    }
+
+    // The last observation is the message; older turns go as history within the window
+    let user = turns.pop().map(|t| t.content).unwrap_or_default();
+    let window = context_size.map(|c| c as usize).unwrap_or(ai::DEFAULT_CONTEXT_TOKENS);
+    let budget = (window - window / 4).saturating_sub(ai::estimate_tokens(&system) + ai::estimate_tokens(&user));
+    let history = ai::truncate_history(&turns, budget).to_vec();
+
+    GenerateRequest { system, history, user }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawProposal {
+    #[serde(default)]
+    thought: String,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    summary: String,
+}
+
+/// Parse the model's answer; free text with a code block still yields a step
+pub fn parse_proposal(text: &str) -> Proposal {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => "",
+    };
+    if let Ok(raw) = serde_json::from_str::<RawProposal>(json) {
+        let command = raw.command.trim();
+        if raw.done || command.is_empty() {
+            let summary = [raw.summary.trim(), raw.thought.trim()]
+                .into_iter()
+                .find(|s| !s.is_empty())
+                .unwrap_or("")
+                .to_string();
+            return Proposal::Done { summary };
+        }
+        return Proposal::Step {
+            thought: raw.thought.trim().to_string(),
+            command: command.to_string(),
+        };
+    }
+
+    match command_extract::extract(text).into_iter().next() {
+        Some(first) => {
+            // The explanation before the code block
+            let before = [text.find("```"), text.find("~~~")].into_iter().flatten().min().map_or(text, |i| &text[..i]);
+            Proposal::Step {
+                thought: before.trim().to_string(),
+                command: first.command,
+            }
+        }
+        None => Proposal::Done {
+            summary: text.trim().to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn test_parse_proposal() {
+        assert_eq!(
+            parse_proposal("```json\n{\"thought\": \"Check disk usage\", \"command\": \"df -h /\"}\n```"),
+            Proposal::Step {
+                thought: "Check disk usage".into(),
+                command: "df -h /".into()
+            }
+        );
+        assert_eq!(
+            parse_proposal(r#"{"done": true, "summary": "nginx restarted"}"#),
+            Proposal::Done {
+                summary: "nginx restarted".into()
+            }
+        );
+        // Free text with a code block
+        assert_eq!(
+            parse_proposal("Let me look at the logs.\n\n```bash\njournalctl -u nginx -n 50 --no-pager\n```"),
+            Proposal::Step {
+                thought: "Let me look at the logs.".into(),
+                command: "journalctl -u nginx -n 50 --no-pager".into()
+            }
+        );
+        assert_eq!(
+            parse_proposal("Nothing else to do."),
+            Proposal::Done {
+                summary: "Nothing else to do.".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_steps_limits_and_prompt() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+        let redactor = Redactor::new(&[], false).unwrap();
+
+        let (max_steps, max_risk) = clamp_limits(Some(100), Some(Severity::Critical));
+        assert_eq!((max_steps, max_risk), (MAX_STEPS_LIMIT, Severity::High));
+        let (max_steps, max_risk) = clamp_limits(Some(5), None);
+        let run = create_run(&conn, None, "s1", "free disk space", Some("/srv/it's"), Some("Linux"), max_steps, max_risk, 100).unwrap();
+        assert_eq!(exec_command(&run, "du -sh *"), "cd '/srv/it'\\''s' || exit 1\ndu -sh *");
+
+        let step = add_step(&conn, &run, "Find big dirs", "du -sh /var/* | sort -h", 101).unwrap();
+        assert_eq!((step.idx, step.status), (1, StepStatus::Proposed));
+        assert!(start_step(&conn, step.id).unwrap());
+        assert!(!start_step(&conn, step.id).unwrap(), "a step runs only once");
+        let output = ExecOutput {
+            stdout: "4.0G /var/log\n".into(),
+            stderr: String::new(),
+            exit_code: 0,
+        };
+        record_execution(&conn, step.id, &Ok(output), 30, 102).unwrap();
+
+        // Over the limit: blocked, and cannot be started
+        let blocked = add_step(&conn, &run, "Open up the logs", "chmod -R 777 /var/log", 103).unwrap();
+        assert_eq!(blocked.status, StepStatus::Blocked);
+        assert_eq!(blocked.risk, Some(Severity::High));
+        assert!(!start_step(&conn, blocked.id).unwrap());
+
+        let third = add_step(&conn, &run, "Vacuum the journal", "journalctl --vacuum-size=1G", 104).unwrap();
+        reject_step(&conn, third.id, Some("keep a week instead")).unwrap();
+        // An edit over the limit blocks the step
+        let fourth = add_step(&conn, &run, "Remove old logs", "find /var/log -name '*.gz' -delete", 105).unwrap();
+        assert!(!edit_step(&conn, &run, fourth.id, "rm -rf /").unwrap());
+
+        let log = steps(&conn, &run.id).unwrap();
+        assert_eq!(log.len(), 4);
+        assert_eq!(get_run(&conn, &run.id).unwrap().unwrap().step_count, 4);
+
+        let request = build_request(&run, &log, PromptLanguage::En, &redactor, None);
+        assert!(request.system.contains("## Goal\nfree disk space"));
+        assert!(request.system.contains("At most 5 commands"));
+        assert!(request.history[0].content.contains("Working Directory: /srv/it's"));
+        assert!(request.history[2].content.contains("Step 1 exit code: 0\n## stdout\n```\n4.0G /var/log\n```\n## stderr\n(empty)"));
+        assert!(request.history[4].content.starts_with("Step 2 was blocked: its risk (high) is over the limit"));
+        assert!(request.history[6].content.contains("The user rejected step 3: keep a week instead"));
+        assert!(request.user.contains("Step 4 was blocked: its risk (critical)"));
+        assert!(request.user.ends_with("(1 steps left)"));
+
+        set_run_status(&conn, &run.id, RunStatus::Completed, Some("freed 3G"), 106).unwrap();
+        let done = get_run(&conn, &run.id).unwrap().unwrap();
+        assert_eq!((done.status, done.summary.as_deref()), (RunStatus::Completed, Some("freed 3G")));
+        assert!(delete_run(&conn, &run.id).unwrap());
+        assert!(steps(&conn, &run.id).unwrap().is_empty());
+    }
+}
@@ -1,36 +1,368 @@
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
 
-/// AI 설정 구조체
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::ai_provider::{AiProvider, ChatRole, ChatTurn, GenerateRequest, TokenSink};
+use crate::command_extract;
+use crate::profile;
+use crate::runbooks;
+use crate::settings;
+
+pub type AiError = Box<dyn Error + Send + Sync>;
+
+/// AI 백엔드 종류 (설정 키: ai_provider)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Ollama,
+    /// OpenAI 호환 /v1/chat/completions (llama.cpp server, vLLM, LM Studio 등)
+    OpenAi,
+    /// 테스트용 고정 응답
+    Mock,
+}
+
+impl ProviderKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "ollama" => Some(ProviderKind::Ollama),
+            "openai" => Some(ProviderKind::OpenAi),
+            "mock" => Some(ProviderKind::Mock),
+            _ => None,
+        }
+    }
+}
+
+/// 시스템 프롬프트가 요청하는 응답 언어 (설정 키: ai_language)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptLanguage {
+    #[default]
+    Ko,
+    En,
+}
+
+impl PromptLanguage {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "ko" | "korean" => Some(PromptLanguage::Ko),
+            "en" | "english" => Some(PromptLanguage::En),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            PromptLanguage::Ko => "ko",
+            PromptLanguage::En => "en",
+        }
+    }
+
+    /// 시스템 프롬프트에 넣는 응답 언어 지시
+    pub fn rule(&self) -> &'static str {
+        match self {
+            PromptLanguage::Ko => "**IMPORTANT: Always respond in Korean (한국어). All explanations must be in Korean.**",
+            PromptLanguage::En => "**IMPORTANT: Always respond in English.**",
+        }
+    }
+}
+
+const PROVIDER_SETTING: &str = "ai_provider";
+const SERVER_URL_SETTING: &str = "ai_server_url";
+const MODEL_SETTING: &str = "ai_model";
+/// 키체인을 쓸 수 없을 때만 사용 (키체인이 있으면 비어 있음)
+/// 일반 settings 명령으로는 읽거나 쓸 수 없다 (ai_config_set으로만 저장)
+pub const API_KEY_SETTING: &str = "ai_api_key";
+/// 키체인 항목 이름 (프로필 비밀번호와 같은 방식으로 저장)
+const API_KEY_KEYRING_ID: &str = "ai-api-key";
+const TEMPERATURE_SETTING: &str = "ai_temperature";
+const CONTEXT_SIZE_SETTING: &str = "ai_context_size";
+const TIMEOUT_SETTING: &str = "ai_timeout_secs";
+const LANGUAGE_SETTING: &str = "ai_language";
+const EMBEDDING_MODEL_SETTING: &str = "ai_embedding_model";
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 120;
+/// 런북 검색용 임베딩 모델 기본값 (Ollama)
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+const TEMPERATURE_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+const CONTEXT_SIZE_RANGE: std::ops::RangeInclusive<u32> = 256..=1_048_576;
+const TIMEOUT_RANGE: std::ops::RangeInclusive<u64> = 5..=3600;
+
+/// context size가 설정되지 않았을 때 가정하는 컨텍스트 윈도우 (토큰)
+pub const DEFAULT_CONTEXT_TOKENS: usize = 4096;
+
+/// AI 설정 구조체 (settings 테이블의 ai_* 키로 저장)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AIConfig {
+    #[serde(default)]
+    pub provider: ProviderKind,
     pub server_url: String,
+    /// 비어 있으면 질문 전에 설정해야 함 (ai_test_connection으로 목록 확인)
+    #[serde(default)]
     pub model: String,
+    /// 저장 시: None이면 기존 키 유지, 빈 문자열이면 삭제
+    /// 키 자체는 웹뷰로 보내지 않는다 (`api_key_set`만 전달)
+    #[serde(default, skip_serializing)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub api_key_set: bool,
+    /// None이면 서버 기본값
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// 컨텍스트 윈도우 (토큰), None이면 서버 기본값
+    #[serde(default)]
+    pub context_size: Option<u32>,
+    /// 응답이 이 시간 동안 멈추면 실패 처리 (초)
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub language: PromptLanguage,
+    /// 런북 검색(RAG)에 쓰는 임베딩 모델
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_embedding_model() -> String {
+    DEFAULT_EMBEDDING_MODEL.to_string()
+}
+
+impl ProviderKind {
+    /// 각 서버의 로컬 기본 주소
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            ProviderKind::Ollama => "http://localhost:11434",
+            ProviderKind::OpenAi => "http://localhost:8080/v1",
+            ProviderKind::Mock => "mock://",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::Mock => "mock",
+        }
+    }
 }
 
 impl Default for AIConfig {
     fn default() -> Self {
         Self {
-            server_url: "http://192.168.136.8:11434".to_string(),
-            model: "gpt-oss:20b".to_string(),
+            provider: ProviderKind::Ollama,
+            server_url: ProviderKind::Ollama.default_url().to_string(),
+            model: String::new(),
+            api_key: None,
+            api_key_set: false,
+            temperature: None,
+            context_size: None,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            language: PromptLanguage::Ko,
+            embedding_model: default_embedding_model(),
         }
     }
 }
 
-/// Ollama API 요청 구조체
-#[derive(Debug, Serialize)]
-struct OllamaRequest {
-    model: String,
-    prompt: String,
-    stream: bool,
+impl AIConfig {
+    /// 설정에서 로드 (없거나 범위를 벗어난 값은 기본값)
+    pub fn load(conn: &Connection) -> rusqlite::Result<Self> {
+        Self::load_with(conn, profile::keyring_available())
+    }
+
+    /// `keyring`: API 키를 OS 키체인에 두는지 (아니면 설정 테이블)
+    fn load_with(conn: &Connection, keyring: bool) -> rusqlite::Result<Self> {
+        let get = |key: &str| -> rusqlite::Result<Option<String>> {
+            Ok(settings::get_setting(conn, key)?
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()))
+        };
+
+        let provider = get(PROVIDER_SETTING)?
+            .and_then(|v| ProviderKind::parse(&v))
+            .unwrap_or_default();
+        Ok(AIConfig {
+            provider,
+            server_url: get(SERVER_URL_SETTING)?.unwrap_or_else(|| provider.default_url().to_string()),
+            model: get(MODEL_SETTING)?.unwrap_or_default(),
+            api_key: None,
+            api_key_set: false,
+            temperature: get(TEMPERATURE_SETTING)?
+                .and_then(|v| v.parse().ok())
+                .filter(|t| TEMPERATURE_RANGE.contains(t)),
+            context_size: get(CONTEXT_SIZE_SETTING)?
+                .and_then(|v| v.parse().ok())
+                .filter(|c| CONTEXT_SIZE_RANGE.contains(c)),
+            timeout_secs: get(TIMEOUT_SETTING)?
+                .and_then(|v| v.parse().ok())
+                .filter(|t| TIMEOUT_RANGE.contains(t))
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+            language: get(LANGUAGE_SETTING)?
+                .and_then(|v| PromptLanguage::parse(&v))
+                .unwrap_or_default(),
+            embedding_model: get(EMBEDDING_MODEL_SETTING)?.unwrap_or_else(default_embedding_model),
+        }
+        .with_api_key(load_api_key(conn, keyring)?))
+    }
+
+    fn with_api_key(self, api_key: Option<String>) -> Self {
+        AIConfig {
+            api_key_set: api_key.is_some(),
+            api_key,
+            ..self
+        }
+    }
+
+    /// 값 범위와 URL 형식 확인 (모델 미설정은 허용 — 연결 테스트 후 선택)
+    pub fn validate(&self) -> Result<(), String> {
+        if self.provider != ProviderKind::Mock {
+            let url = self.server_url.trim();
+            if !(url.starts_with("http://") || url.starts_with("https://")) || url.len() <= "https://".len() {
+                return Err(format!("서버 URL은 http:// 또는 https://로 시작해야 합니다: {}", url));
+            }
+            if url.contains(char::is_whitespace) {
+                return Err("서버 URL에 공백이 있습니다".to_string());
+            }
+        }
+        if let Some(t) = self.temperature {
+            if !TEMPERATURE_RANGE.contains(&t) {
+                return Err(format!("temperature는 0.0 ~ 2.0 사이여야 합니다: {}", t));
+            }
+        }
+        if let Some(c) = self.context_size {
+            if !CONTEXT_SIZE_RANGE.contains(&c) {
+                return Err(format!("context size는 256 ~ 1048576 사이여야 합니다: {}", c));
+            }
+        }
+        if !TIMEOUT_RANGE.contains(&self.timeout_secs) {
+            return Err(format!("timeout은 5 ~ 3600초 사이여야 합니다: {}", self.timeout_secs));
+        }
+        Ok(())
+    }
+
+    /// 검증 후 저장
+    pub fn save(&self, conn: &Connection) -> Result<(), String> {
+        self.save_with(conn, profile::keyring_available())
+    }
+
+    fn save_with(&self, conn: &Connection, keyring: bool) -> Result<(), String> {
+        self.validate()?;
+        save_api_key(conn, self.api_key.as_deref(), keyring)?;
+
+        let opt = |v: Option<String>| v.unwrap_or_default();
+        let values = [
+            (PROVIDER_SETTING, self.provider.name().to_string()),
+            (SERVER_URL_SETTING, self.server_url.trim().trim_end_matches('/').to_string()),
+            (MODEL_SETTING, self.model.trim().to_string()),
+            (TEMPERATURE_SETTING, opt(self.temperature.map(|t| t.to_string()))),
+            (CONTEXT_SIZE_SETTING, opt(self.context_size.map(|c| c.to_string()))),
+            (TIMEOUT_SETTING, self.timeout_secs.to_string()),
+            (LANGUAGE_SETTING, self.language.code().to_string()),
+            (EMBEDDING_MODEL_SETTING, self.embedding_model.trim().to_string()),
+        ];
+        for (key, value) in values {
+            settings::set_setting(conn, key, &value).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
 }
 
-/// Ollama API 응답 구조체
-#[derive(Debug, Deserialize)]
-struct OllamaResponse {
-    response: String,
-    #[serde(default)]
-    done: bool,
+/// 저장된 API 키 (키체인 또는 설정 테이블)
+fn load_api_key(conn: &Connection, keyring: bool) -> rusqlite::Result<Option<String>> {
+    let stored = settings::get_setting(conn, API_KEY_SETTING)?.filter(|k| !k.trim().is_empty());
+    if !keyring {
+        return Ok(stored);
+    }
+    if let Some(key) = stored {
+        // 이전 버전이 설정 테이블에 평문으로 저장한 키는 키체인으로 옮긴다
+        match profile::store_password(API_KEY_KEYRING_ID, &key) {
+            Ok(()) => settings::set_setting(conn, API_KEY_SETTING, "")?,
+            Err(e) => println!("[AI] Failed to move the API key to the keychain: {}", e),
+        }
+        return Ok(Some(key));
+    }
+    Ok(profile::get_password(API_KEY_KEYRING_ID).unwrap_or_else(|e| {
+        println!("[AI] Failed to read the API key from the keychain: {}", e);
+        None
+    }))
+}
+
+/// None이면 그대로 두고, 빈 문자열이면 삭제
+fn save_api_key(conn: &Connection, key: Option<&str>, keyring: bool) -> Result<(), String> {
+    let Some(key) = key.map(str::trim) else {
+        return Ok(());
+    };
+    if keyring {
+        if key.is_empty() {
+            profile::delete_password(API_KEY_KEYRING_ID)?;
+        } else {
+            profile::store_password(API_KEY_KEYRING_ID, key)?;
+        }
+        settings::set_setting(conn, API_KEY_SETTING, "").map_err(|e| e.to_string())
+    } else {
+        settings::set_setting(conn, API_KEY_SETTING, key).map_err(|e| e.to_string())
+    }
+}
+
+/// 연결 테스트 결과
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionTest {
+    pub ok: bool,
+    pub latency_ms: u64,
+    pub models: Vec<String>,
+    /// 설정된 모델이 서버 목록에 있는지
+    pub model_available: bool,
+    pub error: Option<String>,
+}
+
+/// 서버에 접속해 사용 가능한 모델 목록을 가져온다
+pub async fn test_connection(config: &AIConfig) -> ConnectionTest {
+    let started = std::time::Instant::now();
+    let result = match config.validate() {
+        Ok(()) => crate::ai_provider::from_config(config).list_models().await,
+        Err(e) => Err(e.into()),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(models) => ConnectionTest {
+            ok: true,
+            latency_ms,
+            model_available: models.iter().any(|m| m == &config.model),
+            models,
+            error: None,
+        },
+        Err(e) => ConnectionTest {
+            ok: false,
+            latency_ms,
+            models: Vec::new(),
+            model_available: false,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+lazy_static::lazy_static! {
+    // 진행 중인 요청 (request id -> 취소 신호)
+    static ref ACTIVE_REQUESTS: Mutex<HashMap<String, oneshot::Sender<()>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// 생성 타이밍 통계
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationStats {
+    pub total_ms: f64,
+    pub load_ms: f64,
+    pub prompt_tokens: u64,
+    pub prompt_eval_ms: f64,
+    pub response_tokens: u64,
+    pub eval_ms: f64,
+    pub tokens_per_second: f64,
 }
 
 /// AI 응답 결과
@@ -38,14 +370,68 @@ struct OllamaResponse {
 pub struct AIResponse {
     pub response: String,
     pub model: String,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// 사용자가 중간에 취소한 경우 (response는 그때까지 받은 내용)
+    #[serde(default)]
+    pub cancelled: bool,
+    #[serde(default)]
+    pub stats: Option<GenerationStats>,
+    /// 답변에 근거로 제공된 런북 구절 (본문에서 [n]으로 인용)
+    #[serde(default)]
+    pub sources: Vec<runbooks::RunbookSource>,
+}
+
+/// 진행 중인 요청 취소 (없으면 false)
+pub fn cancel_request(request_id: &str) -> bool {
+    match ACTIVE_REQUESTS.lock().unwrap().remove(request_id) {
+        Some(tx) => tx.send(()).is_ok(),
+        None => false,
+    }
+}
+
+/// 사용자 메시지 (컨텍스트가 있으면 앞에 붙임)
+/// 대화 기록을 다시 보낼 때도 같은 형식을 쓴다 (지난 질문은 컨텍스트 없이)
+pub fn user_message(prompt: &str, context: Option<&str>) -> String {
+    match context {
+        Some(ctx) => format!("## Current Context\n{}\n\n## User Question\n{}", ctx, prompt),
+        None => format!("## User Question\n{}", prompt),
+    }
 }
 
-/// Ollama API를 통해 AI 질문을 처리
-pub async fn ask_ollama(
-    config: &AIConfig,
+/// 대략적인 토큰 수 (ASCII는 4글자당 1토큰, 한글 등은 글자당 1토큰)
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.bytes().filter(|b| b.is_ascii()).count();
+    let other = text.chars().filter(|c| !c.is_ascii()).count();
+    ascii.div_ceil(4) + other + 4 // 메시지당 role 등 오버헤드
+}
+
+/// 예산 안에 들어가는 최근 대화만 남긴다 (오래된 턴부터 제거)
+/// 잘린 결과는 항상 사용자 메시지로 시작한다
+pub fn truncate_history(history: &[ChatTurn], budget: usize) -> &[ChatTurn] {
+    let mut used = 0;
+    let mut start = history.len();
+    for (i, turn) in history.iter().enumerate().rev() {
+        used += estimate_tokens(&turn.content);
+        if used > budget {
+            break;
+        }
+        start = i;
+    }
+    while start < history.len() && history[start].role != ChatRole::User {
+        start += 1;
+    }
+    &history[start..]
+}
+
+/// 시스템 프롬프트 + (예산 내) 대화 기록 + 컨텍스트 + 질문
+fn build_request(
     prompt: &str,
     context: Option<&str>,
-) -> Result<AIResponse, Box<dyn Error>> {
+    history: &[ChatTurn],
+    language: PromptLanguage,
+    context_size: Option<u32>,
+) -> GenerateRequest {
     // 시스템 프롬프트 구성 (한글 응답 요청)
     let system_prompt = r#"You are an expert Linux/Unix system administrator and terminal assistant.
 
@@ -61,79 +447,87 @@ Response format:
 2. Command(s) in ```bash blocks
 3. Important notes or warnings if needed
 
-**IMPORTANT: Always respond in Korean (한국어). All explanations must be in Korean.**
+{language}
 
 Keep responses focused and practical."#;
+    let system_prompt = system_prompt.replace("{language}", language.rule());
+    let user = user_message(prompt, context);
 
-    // 컨텍스트가 있으면 추가
-    let full_prompt = if let Some(ctx) = context {
-        format!(
-            "{}\n\n## Current Context\n{}\n\n## User Question\n{}",
-            system_prompt, ctx, prompt
-        )
-    } else {
-        format!("{}\n\n## User Question\n{}", system_prompt, prompt)
-    };
-
-    let client = reqwest::Client::new();
-    let request = OllamaRequest {
-        model: config.model.clone(),
-        prompt: full_prompt,
-        stream: false,
-    };
+    // 윈도우의 1/4은 응답용으로 남기고 나머지에서 시스템 프롬프트와 질문을 뺀 만큼만 기록에 사용
+    let window = context_size.map(|c| c as usize).unwrap_or(DEFAULT_CONTEXT_TOKENS);
+    let budget = (window - window / 4).saturating_sub(estimate_tokens(&system_prompt) + estimate_tokens(&user));
+    let kept = truncate_history(history, budget);
+    if kept.len() < history.len() {
+        println!("[AI] History truncated: {} of {} messages fit in {} tokens", kept.len(), history.len(), budget);
+    }
 
-    let url = format!("{}/api/generate", config.server_url);
+    GenerateRequest {
+        system: system_prompt,
+        history: kept.to_vec(),
+        user,
+    }
+}
 
-    let response = client
-        .post(&url)
-        .json(&request)
-        .timeout(std::time::Duration::from_secs(60))
-        .send()
-        .await?;
+/// 설정된 provider로 AI 질문을 처리 (스트리밍)
+/// `history`는 이전 대화 (단발 질문이면 빈 슬라이스), 컨텍스트 윈도우에 맞게 잘라서 보낸다
+/// 토큰이 도착할 때마다 `on_token`을 호출하고, `cancel_request(request_id)`로 중단할 수 있다
+pub async fn ask(
+    provider: &dyn AiProvider,
+    language: PromptLanguage,
+    prompt: &str,
+    context: Option<&str>,
+    history: &[ChatTurn],
+    request_id: &str,
+    on_token: TokenSink<'_>,
+) -> Result<AIResponse, AiError> {
+    let request = build_request(prompt, context, history, language, provider.context_size());
+    generate(provider, &request, request_id, on_token).await
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Ollama API error: {}", response.status()).into());
+/// 이미 구성된 요청을 보낸다 (취소 등록 포함, 다른 프롬프트를 쓰는 기능용)
+pub async fn generate(
+    provider: &dyn AiProvider,
+    request: &GenerateRequest,
+    request_id: &str,
+    on_token: TokenSink<'_>,
+) -> Result<AIResponse, AiError> {
+    if provider.model().trim().is_empty() {
+        return Err("AI 모델이 설정되지 않았습니다. AI 설정에서 모델을 선택하세요".into());
     }
 
-    let ollama_response: OllamaResponse = response.json().await?;
+    let (cancel_tx, mut cancel_rx) = oneshot::channel();
+    ACTIVE_REQUESTS
+        .lock()
+        .unwrap()
+        .insert(request_id.to_string(), cancel_tx);
 
-    Ok(AIResponse {
-        response: ollama_response.response,
-        model: config.model.clone(),
-    })
+    println!("[AI] Request {} via {} ({})", request_id, provider.name(), provider.model());
+    let result = provider.generate(request, on_token, &mut cancel_rx).await;
+    ACTIVE_REQUESTS.lock().unwrap().remove(request_id);
+
+    let mut response = result?;
+    if response.cancelled {
+        println!("[AI] Request {} cancelled", request_id);
+    }
+    response.request_id = Some(request_id.to_string());
+    Ok(response)
 }
 
-/// 응답에서 명령어 블록 추출 (```로 감싸진 부분)
+/// 응답에서 명령어 블록 추출: 코드 블록마다 하나씩 (명령어별 정보는 command_extract::extract)
 pub fn extract_commands(response: &str) -> Vec<String> {
-    let mut commands = Vec::new();
-    let mut in_code_block = false;
-    let mut current_command = String::new();
-
-    for line in response.lines() {
-        let trimmed = line.trim();
-
-        // 코드 블록 시작/종료 감지
-        if trimmed.starts_with("```") {
-            if in_code_block && !current_command.is_empty() {
-                commands.push(current_command.trim().to_string());
-                current_command.clear();
-            }
-            in_code_block = !in_code_block;
-            continue;
-        }
+    let mut commands: Vec<String> = Vec::new();
+    let mut last_block = None;
 
-        // 코드 블록 내부의 명령어 수집
-        if in_code_block {
-            if !trimmed.is_empty() && !trimmed.starts_with('#') {
-                current_command.push_str(line);
-                current_command.push('\n');
+    for extracted in command_extract::extract(response) {
+        // 같은 코드 블록의 명령어는 한 줄씩 이어 붙임
+        match commands.last_mut() {
+            Some(last) if extracted.block.is_some() && extracted.block == last_block => {
+                last.push('\n');
+                last.push_str(&extracted.command);
             }
+            _ => commands.push(extracted.command),
         }
-    }
-
-    // 마지막 명령어 추가
-    if !current_command.is_empty() {
-        commands.push(current_command.trim().to_string());
+        last_block = extracted.block;
     }
 
     commands
@@ -142,6 +536,7 @@ pub fn extract_commands(response: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai_provider::MockProvider;
 
     #[test]
     fn test_extract_commands() {
@@ -181,4 +576,112 @@ pwd
         assert_eq!(commands.len(), 1);
         assert_eq!(commands[0], "ls -la\npwd");
     }
+
+    #[tokio::test]
+    async fn test_ask_with_mock_provider() {
+        let provider = MockProvider::new("mock-model");
+        let ko = PromptLanguage::Ko;
+        let mut tokens = Vec::new();
+        let mut on_token = |t: &str| tokens.push(t.to_string());
+
+        let result = ask(&provider, ko, "디스크 사용량 확인", Some("cwd: /srv"), &[], "req-1", &mut on_token)
+            .await
+            .unwrap();
+
+        assert_eq!(result.response, MockProvider::answer("## User Question\n디스크 사용량 확인"));
+        assert_eq!(tokens.concat(), result.response);
+        assert_eq!(result.request_id.as_deref(), Some("req-1"));
+        assert_eq!(extract_commands(&result.response), vec!["echo mock"]);
+        assert!(!cancel_request("req-1"));
+
+        // Cancelling mid-stream keeps what was generated so far
+        let mut cancel_after_first = |_: &str| {
+            cancel_request("req-2");
+        };
+        let result = ask(&provider, ko, "질문", None, &[], "req-2", &mut cancel_after_first).await.unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.response, "Mock ");
+    }
+
+    #[test]
+    fn test_config_validation_and_persistence() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_db(&conn).unwrap();
+
+        // Nothing stored: local defaults, no model, no LAN address
+        let config = AIConfig::load_with(&conn, false).unwrap();
+        assert_eq!(config, AIConfig::default());
+        assert_eq!(config.server_url, "http://localhost:11434");
+
+        let invalid = [
+            AIConfig { server_url: "localhost:11434".into(), ..Default::default() },
+            AIConfig { temperature: Some(3.5), ..Default::default() },
+            AIConfig { context_size: Some(10), ..Default::default() },
+            AIConfig { timeout_secs: 0, ..Default::default() },
+        ];
+        for config in invalid {
+            assert!(config.save_with(&conn, false).is_err(), "{:?}", config);
+        }
+
+        let config = AIConfig {
+            provider: ProviderKind::OpenAi,
+            server_url: "http://gpu-box:8000/v1/".into(),
+            model: "qwen2.5-coder".into(),
+            api_key: Some("sk-local".into()),
+            api_key_set: false,
+            temperature: Some(0.2),
+            context_size: Some(8192),
+            timeout_secs: 300,
+            language: PromptLanguage::En,
+            embedding_model: "bge-m3".into(),
+        };
+        config.save_with(&conn, false).unwrap();
+        let loaded = AIConfig::load_with(&conn, false).unwrap();
+        assert_eq!(loaded.server_url, "http://gpu-box:8000/v1");
+        assert_eq!(
+            loaded,
+            AIConfig { server_url: loaded.server_url.clone(), api_key_set: true, ..config.clone() }
+        );
+        // The key never goes back to the webview, and saving without one keeps it
+        let json = serde_json::to_value(&loaded).unwrap();
+        assert!(json.get("api_key").is_none());
+        assert_eq!(json["api_key_set"], true);
+        AIConfig { api_key: None, ..config.clone() }.save_with(&conn, false).unwrap();
+        assert_eq!(AIConfig::load_with(&conn, false).unwrap().api_key.as_deref(), Some("sk-local"));
+        AIConfig { api_key: Some(String::new()), ..config }.save_with(&conn, false).unwrap();
+        assert!(!AIConfig::load_with(&conn, false).unwrap().api_key_set);
+
+        // Out-of-range values written directly fall back to defaults
+        settings::set_setting(&conn, "ai_timeout_secs", "99999").unwrap();
+        assert_eq!(AIConfig::load_with(&conn, false).unwrap().timeout_secs, DEFAULT_TIMEOUT_SECS);
+
+        let en = build_request("q", None, &[], PromptLanguage::En, None);
+        assert!(en.system.contains("respond in English"));
+        assert!(!en.system.contains("{language}"));
+    }
+
+    #[test]
+    fn test_history_truncation() {
+        let turn = |role, content: &str| ChatTurn { role, content: content.to_string() };
+        let history = vec![
+            turn(ChatRole::User, &"a".repeat(400)),
+            turn(ChatRole::Assistant, &"b".repeat(400)),
+            turn(ChatRole::User, "짧은 질문"),
+            turn(ChatRole::Assistant, "짧은 답"),
+        ];
+
+        assert_eq!(truncate_history(&history, 10_000).len(), 4);
+        // The old long pair does not fit; the recent pair does
+        assert_eq!(truncate_history(&history, 50), &history[2..]);
+        // Never starts with an assistant reply
+        let everything: usize = history[1..].iter().map(|t| estimate_tokens(&t.content)).sum();
+        assert_eq!(truncate_history(&history[1..], everything), &history[2..]);
+        assert!(truncate_history(&history, 0).is_empty());
+
+        // A small context window drops the older turns from the request
+        let small = build_request("q", None, &history, PromptLanguage::Ko, Some(512));
+        assert_eq!(small.history, history[2..]);
+        let large = build_request("q", None, &history, PromptLanguage::Ko, Some(8192));
+        assert_eq!(large.history.len(), 4);
+    }
 }
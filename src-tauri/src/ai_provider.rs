@@ -0,0 +1,819 @@
+//! AI backends behind a common trait
+//!
+//! `ai::ask` builds the prompt and handles cancellation; a provider only turns
+//! a system prompt, earlier conversation turns and a user message into
+//! streamed tokens. Implementations: Ollama (`/api/chat`), any OpenAI-compatible server
+//! (`/v1/chat/completions`: llama.cpp server, vLLM, LM Studio...) and a
+//! deterministic mock for tests. The provider and its options (temperature,
+//! context size, timeout) come from the typed AI settings (see `ai::AIConfig`).
+//! Providers also compute embeddings for runbook search (see `runbooks`).
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::ai::{AIConfig, AIResponse, AiError, GenerationStats, ProviderKind};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "user" => Some(ChatRole::User),
+            "assistant" => Some(ChatRole::Assistant),
+            _ => None,
+        }
+    }
+}
+
+/// One earlier message of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+/// What to generate from
+#[derive(Debug, Clone)]
+pub struct GenerateRequest {
+    pub system: String,
+    /// Earlier turns, oldest first (already truncated to fit the context)
+    pub history: Vec<ChatTurn>,
+    pub user: String,
+}
+
+impl GenerateRequest {
+    /// System prompt, history and the new user message as role/content pairs
+    fn messages(&self) -> Vec<ChatMessage<'_>> {
+        let mut messages = vec![ChatMessage { role: "system", content: &self.system }];
+        messages.extend(self.history.iter().map(|turn| ChatMessage {
+            role: turn.role.as_str(),
+            content: &turn.content,
+        }));
+        messages.push(ChatMessage { role: "user", content: &self.user });
+        messages
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+/// Sampling and transport options shared by the HTTP providers
+#[derive(Debug, Clone)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub context_size: Option<u32>,
+    /// No data for this long fails the request
+    pub idle_timeout: Duration,
+}
+
+impl GenerationOptions {
+    fn from_config(config: &AIConfig) -> Self {
+        GenerationOptions {
+            temperature: config.temperature,
+            context_size: config.context_size,
+            idle_timeout: Duration::from_secs(config.timeout_secs),
+        }
+    }
+}
+
+/// Callback receiving each generated token
+pub type TokenSink<'a> = &'a mut (dyn FnMut(&str) + Send);
+
+#[async_trait]
+pub trait AiProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn model(&self) -> &str;
+
+    /// Context window in tokens, if configured (used to budget conversation history)
+    fn context_size(&self) -> Option<u32> {
+        None
+    }
+
+    /// Models available at the server (also serves as a connectivity check)
+    async fn list_models(&self) -> Result<Vec<String>, AiError>;
+
+    /// Generate a response, calling `on_token` as text arrives
+    /// Resolving `cancel` stops generation and returns the text so far with `cancelled` set
+    async fn generate(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+        cancel: &mut oneshot::Receiver<()>,
+    ) -> Result<AIResponse, AiError>;
+
+    /// One embedding vector per input, computed with `model` (an embedding model, not the chat model)
+    async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, AiError>;
+}
+
+/// Fail unless there is one vector per input
+fn check_embeddings(embeddings: Vec<Vec<f32>>, inputs: &[String]) -> Result<Vec<Vec<f32>>, AiError> {
+    if embeddings.len() != inputs.len() || embeddings.iter().any(|e| e.is_empty()) {
+        return Err(format!("AI API error: expected {} embeddings, got {}", inputs.len(), embeddings.len()).into());
+    }
+    Ok(embeddings)
+}
+
+/// Provider for the configured backend
+pub fn from_config(config: &AIConfig) -> Box<dyn AiProvider> {
+    let options = GenerationOptions::from_config(config);
+    match config.provider {
+        ProviderKind::Ollama => Box::new(OllamaProvider::new(&config.server_url, &config.model, options)),
+        ProviderKind::OpenAi => Box::new(OpenAiProvider::new(
+            &config.server_url,
+            &config.model,
+            config.api_key.clone(),
+            options,
+        )),
+        ProviderKind::Mock => Box::new(MockProvider::new(Some(config.model.as_str()).filter(|m| !m.is_empty()).unwrap_or("mock"))),
+    }
+}
+
+fn empty_response(model: &str) -> AIResponse {
+    AIResponse {
+        response: String::new(),
+        model: model.to_string(),
+        request_id: None,
+        cancelled: false,
+        stats: None,
+        sources: Vec::new(),
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, AiError> {
+    Ok(reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()?)
+}
+
+/// Joins lines (and UTF-8 characters) split across chunk boundaries
+#[derive(Default)]
+struct LineDecoder {
+    buf: Vec<u8>,
+}
+
+impl LineDecoder {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Last line without a trailing newline
+    fn finish(&mut self) -> Option<String> {
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.buf)).trim().to_string();
+        Some(line).filter(|l| !l.is_empty())
+    }
+}
+
+enum StreamEvent {
+    Lines(Vec<String>),
+    /// The body ended; remaining partial line, if any
+    End(Vec<String>),
+    Cancelled,
+}
+
+/// Wait for the next lines of a streaming body, the end of it, or cancellation
+async fn next_lines(
+    response: &mut reqwest::Response,
+    decoder: &mut LineDecoder,
+    idle_timeout: Duration,
+    cancel: &mut oneshot::Receiver<()>,
+) -> Result<StreamEvent, AiError> {
+    tokio::select! {
+        chunk = tokio::time::timeout(idle_timeout, response.chunk()) => {
+            match chunk.map_err(|_| "AI server stopped responding")?? {
+                Some(bytes) => Ok(StreamEvent::Lines(decoder.push(&bytes))),
+                None => Ok(StreamEvent::End(decoder.finish().into_iter().collect())),
+            }
+        }
+        _ = cancel => Ok(StreamEvent::Cancelled),
+    }
+}
+
+/// Send a request and decode its JSON body (model lists, embeddings)
+async fn request_json<T: serde::de::DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    timeout: Duration,
+) -> Result<T, AiError> {
+    let response = request.timeout(timeout).send().await?;
+    if !response.status().is_success() {
+        return Err(format!("AI API error: {}", response.status()).into());
+    }
+    Ok(response.json().await?)
+}
+
+/// Send a request unless cancelled first; None if cancelled
+async fn send(
+    request: reqwest::RequestBuilder,
+    cancel: &mut oneshot::Receiver<()>,
+) -> Result<Option<reqwest::Response>, AiError> {
+    let response = tokio::select! {
+        response = request.send() => response?,
+        _ = cancel => return Ok(None),
+    };
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("AI API error: {} {}", status, body.trim()).into());
+    }
+    Ok(Some(response))
+}
+
+// ============================================================================
+// Ollama
+// ============================================================================
+
+pub struct OllamaProvider {
+    server_url: String,
+    model: String,
+    options: GenerationOptions,
+}
+
+impl OllamaProvider {
+    pub fn new(server_url: &str, model: &str, options: GenerationOptions) -> Self {
+        OllamaProvider {
+            server_url: server_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            options,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddings {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaTags {
+    #[serde(default)]
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+/// One NDJSON line of a streaming /api/chat response
+/// The final line (done: true) carries timing stats in nanoseconds
+#[derive(Debug, Default, Deserialize)]
+struct OllamaChunk {
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    total_duration: Option<u64>,
+    #[serde(default)]
+    load_duration: Option<u64>,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    prompt_eval_duration: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+    #[serde(default)]
+    eval_duration: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+}
+
+impl OllamaChunk {
+    fn text(&self) -> &str {
+        self.message.as_ref().map(|m| m.content.as_str()).unwrap_or("")
+    }
+
+    fn stats(&self) -> GenerationStats {
+        let ms = |ns: Option<u64>| ns.unwrap_or(0) as f64 / 1_000_000.0;
+        let eval_ms = ms(self.eval_duration);
+        let response_tokens = self.eval_count.unwrap_or(0);
+        GenerationStats {
+            total_ms: ms(self.total_duration),
+            load_ms: ms(self.load_duration),
+            prompt_tokens: self.prompt_eval_count.unwrap_or(0),
+            prompt_eval_ms: ms(self.prompt_eval_duration),
+            response_tokens,
+            eval_ms,
+            tokens_per_second: if eval_ms > 0.0 {
+                response_tokens as f64 * 1000.0 / eval_ms
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl AiProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn context_size(&self) -> Option<u32> {
+        self.options.context_size
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, AiError> {
+        let request = http_client()?.get(format!("{}/api/tags", self.server_url));
+        let tags: OllamaTags = request_json(request, self.options.idle_timeout).await?;
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    async fn generate(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+        cancel: &mut oneshot::Receiver<()>,
+    ) -> Result<AIResponse, AiError> {
+        let mut result = empty_response(&self.model);
+        let body = OllamaRequest {
+            model: &self.model,
+            messages: request.messages(),
+            stream: true,
+            options: OllamaOptions {
+                temperature: self.options.temperature,
+                num_ctx: self.options.context_size,
+            },
+        };
+        let http = http_client()?
+            .post(format!("{}/api/chat", self.server_url))
+            .json(&body);
+        let Some(mut response) = send(http, cancel).await? else {
+            result.cancelled = true;
+            return Ok(result);
+        };
+
+        let mut decoder = LineDecoder::default();
+        loop {
+            let (lines, ended) = match next_lines(&mut response, &mut decoder, self.options.idle_timeout, cancel).await? {
+                StreamEvent::Lines(lines) => (lines, false),
+                StreamEvent::End(lines) => (lines, true),
+                StreamEvent::Cancelled => {
+                    result.cancelled = true;
+                    return Ok(result);
+                }
+            };
+
+            for line in lines {
+                let chunk: OllamaChunk = serde_json::from_str(&line)?;
+                if let Some(error) = chunk.error {
+                    return Err(format!("Ollama API error: {}", error).into());
+                }
+                let text = chunk.text();
+                if !text.is_empty() {
+                    on_token(text);
+                    result.response.push_str(text);
+                }
+                if chunk.done {
+                    result.stats = Some(chunk.stats());
+                    return Ok(result);
+                }
+            }
+
+            if ended {
+                return Ok(result);
+            }
+        }
+    }
+
+    async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, AiError> {
+        let request = http_client()?
+            .post(format!("{}/api/embed", self.server_url))
+            .json(&EmbedRequest { model, input: inputs });
+        let body: OllamaEmbeddings = request_json(request, self.options.idle_timeout).await?;
+        check_embeddings(body.embeddings, inputs)
+    }
+}
+
+// ============================================================================
+// OpenAI-compatible (/v1/chat/completions)
+// ============================================================================
+
+pub struct OpenAiProvider {
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    options: GenerationOptions,
+}
+
+impl OpenAiProvider {
+    /// `server_url` may or may not include the `/v1` suffix
+    pub fn new(server_url: &str, model: &str, api_key: Option<String>, options: GenerationOptions) -> Self {
+        let url = server_url.trim_end_matches('/');
+        let base_url = if url.ends_with("/v1") {
+            url.to_string()
+        } else {
+            format!("{}/v1", url)
+        };
+        OpenAiProvider {
+            base_url,
+            model: model.to_string(),
+            api_key: api_key.filter(|k| !k.trim().is_empty()),
+            options,
+        }
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    stream_options: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingList {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChatChoice {
+    #[serde(default)]
+    delta: ChatDelta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChatDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+/// Payload of a server-sent event line; None for comments and other fields
+fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim)
+}
+
+/// Stats measured on our side (servers only report token counts)
+fn measured_stats(started: Instant, first_token: Option<Instant>, usage: Option<&ChatUsage>) -> GenerationStats {
+    let total_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let eval_ms = first_token.map(|t| t.elapsed().as_secs_f64() * 1000.0).unwrap_or(0.0);
+    let response_tokens = usage.map(|u| u.completion_tokens).unwrap_or(0);
+    GenerationStats {
+        total_ms,
+        load_ms: 0.0,
+        prompt_tokens: usage.map(|u| u.prompt_tokens).unwrap_or(0),
+        prompt_eval_ms: total_ms - eval_ms,
+        response_tokens,
+        eval_ms,
+        tokens_per_second: if eval_ms > 0.0 {
+            response_tokens as f64 * 1000.0 / eval_ms
+        } else {
+            0.0
+        },
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn context_size(&self) -> Option<u32> {
+        self.options.context_size
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, AiError> {
+        let request = self.authorized(http_client()?.get(format!("{}/models", self.base_url)));
+        let list: ModelList = request_json(request, self.options.idle_timeout).await?;
+        Ok(list.data.into_iter().map(|m| m.id).collect())
+    }
+
+    async fn generate(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+        cancel: &mut oneshot::Receiver<()>,
+    ) -> Result<AIResponse, AiError> {
+        let started = Instant::now();
+        let mut first_token = None;
+        let mut usage = None;
+        let mut result = empty_response(&self.model);
+
+        let body = ChatRequest {
+            model: &self.model,
+            messages: request.messages(),
+            stream: true,
+            stream_options: serde_json::json!({ "include_usage": true }),
+            temperature: self.options.temperature,
+        };
+        let http = self.authorized(
+            http_client()?
+                .post(format!("{}/chat/completions", self.base_url))
+                .json(&body),
+        );
+        let Some(mut response) = send(http, cancel).await? else {
+            result.cancelled = true;
+            return Ok(result);
+        };
+
+        let mut decoder = LineDecoder::default();
+        loop {
+            let (lines, ended) = match next_lines(&mut response, &mut decoder, self.options.idle_timeout, cancel).await? {
+                StreamEvent::Lines(lines) => (lines, false),
+                StreamEvent::End(lines) => (lines, true),
+                StreamEvent::Cancelled => {
+                    result.cancelled = true;
+                    return Ok(result);
+                }
+            };
+
+            for line in lines {
+                let Some(data) = sse_data(&line) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    result.stats = Some(measured_stats(started, first_token, usage.as_ref()));
+                    return Ok(result);
+                }
+
+                let chunk: ChatChunk = serde_json::from_str(data)?;
+                if let Some(error) = chunk.error {
+                    return Err(format!("AI API error: {}", error).into());
+                }
+                for content in chunk.choices.into_iter().filter_map(|c| c.delta.content) {
+                    if content.is_empty() {
+                        continue;
+                    }
+                    first_token.get_or_insert_with(Instant::now);
+                    on_token(&content);
+                    result.response.push_str(&content);
+                }
+                if chunk.usage.is_some() {
+                    usage = chunk.usage;
+                }
+            }
+
+            if ended {
+                result.stats = Some(measured_stats(started, first_token, usage.as_ref()));
+                return Ok(result);
+            }
+        }
+    }
+
+    async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, AiError> {
+        let request = self.authorized(
+            http_client()?
+                .post(format!("{}/embeddings", self.base_url))
+                .json(&EmbedRequest { model, input: inputs }),
+        );
+        let mut body: EmbeddingList = request_json(request, self.options.idle_timeout).await?;
+        body.data.sort_by_key(|d| d.index);
+        check_embeddings(body.data.into_iter().map(|d| d.embedding).collect(), inputs)
+    }
+}
+
+// ============================================================================
+// Mock
+// ============================================================================
+
+/// Deterministic provider: echoes the question in a fixed answer, one word per token
+pub struct MockProvider {
+    model: String,
+}
+
+impl MockProvider {
+    pub fn new(model: &str) -> Self {
+        MockProvider {
+            model: model.to_string(),
+        }
+    }
+
+    pub fn answer(user: &str) -> String {
+        let question = user.lines().last().unwrap_or("").trim();
+        format!("Mock answer for: {}\n\n```bash\necho mock\n```\n", question)
+    }
+
+    /// Hashed bag of words, normalized, so texts sharing words score as similar
+    pub fn embedding(text: &str) -> Vec<f32> {
+        let mut vector = vec![0f32; 64];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            let hash = word
+                .to_lowercase()
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+            vector[(hash % 64) as usize] += 1.0;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl AiProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, AiError> {
+        Ok(vec!["mock".to_string()])
+    }
+
+    async fn generate(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+        cancel: &mut oneshot::Receiver<()>,
+    ) -> Result<AIResponse, AiError> {
+        let mut result = empty_response(&self.model);
+        let answer = Self::answer(&request.user);
+
+        for token in answer.split_inclusive(' ') {
+            if cancel.try_recv().is_ok() {
+                result.cancelled = true;
+                return Ok(result);
+            }
+            on_token(token);
+            result.response.push_str(token);
+            tokio::task::yield_now().await;
+        }
+
+        result.stats = Some(GenerationStats {
+            response_tokens: answer.split_inclusive(' ').count() as u64,
+            ..Default::default()
+        });
+        Ok(result)
+    }
+
+    async fn embed(&self, _model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, AiError> {
+        Ok(inputs.iter().map(|text| Self::embedding(text)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_decoding_and_stats() {
+        let stream = "{\"message\":{\"role\":\"assistant\",\"content\":\"안녕\"},\"done\":false}\n\
+                      {\"message\":{\"role\":\"assistant\",\"content\":\" ls\"},\"done\":false}\n\
+                      {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"total_duration\":2000000000,\"eval_count\":50,\"eval_duration\":1000000000}";
+
+        // Lines and multi-byte characters split across chunks are restored
+        let mut decoder = LineDecoder::default();
+        let mut lines = Vec::new();
+        for chunk in stream.as_bytes().chunks(7) {
+            lines.extend(decoder.push(chunk));
+        }
+        lines.extend(decoder.finish());
+        assert_eq!(lines.len(), 3);
+
+        let chunks: Vec<OllamaChunk> = lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let text: String = chunks.iter().map(|c| c.text()).collect();
+        assert_eq!(text, "안녕 ls");
+        assert!(chunks[2].done);
+
+        let stats = chunks[2].stats();
+        assert_eq!(stats.total_ms, 2000.0);
+        assert_eq!(stats.response_tokens, 50);
+        assert_eq!(stats.tokens_per_second, 50.0);
+    }
+
+    #[test]
+    fn test_openai_chunks() {
+        let options = GenerationOptions::from_config(&AIConfig::default());
+        let base_url = |url: &str| OpenAiProvider::new(url, "m", None, options.clone()).base_url;
+        assert_eq!(base_url("http://localhost:8080/"), "http://localhost:8080/v1");
+        assert_eq!(base_url("https://api.example.com/v1"), "https://api.example.com/v1");
+
+        let line = r#"data: {"choices":[{"index":0,"delta":{"content":"ls -la"}}]}"#;
+        let chunk: ChatChunk = serde_json::from_str(sse_data(line).unwrap()).unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("ls -la"));
+
+        let usage = r#"data: {"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":34,"total_tokens":46}}"#;
+        let chunk: ChatChunk = serde_json::from_str(sse_data(usage).unwrap()).unwrap();
+        assert_eq!(chunk.usage.unwrap().completion_tokens, 34);
+
+        assert_eq!(sse_data("data: [DONE]"), Some("[DONE]"));
+        assert_eq!(sse_data(": keep-alive"), None);
+
+        let request = GenerateRequest {
+            system: "sys".into(),
+            history: vec![
+                ChatTurn { role: ChatRole::User, content: "q1".into() },
+                ChatTurn { role: ChatRole::Assistant, content: "a1".into() },
+            ],
+            user: "q2".into(),
+        };
+        let roles: Vec<&str> = request.messages().iter().map(|m| m.role).collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+    }
+}
@@ -0,0 +1,271 @@
+//! Command usage analytics over the history table
+//!
+//! Aggregates for the usage dashboard: most used commands, failure rate by
+//! command, slowest commands and an hour-by-weekday activity heatmap. All
+//! queries take the same filter (profile, time range). Compacted history
+//! (see `retention`) has no per-run rows and is not included.
+
+use rusqlite::{Connection, Result, Row, ToSql};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: usize = 20;
+
+/// Filter shared by all analytics queries; None means no restriction
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AnalyticsQuery {
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    /// Inclusive start (Unix seconds)
+    #[serde(default)]
+    pub since: Option<i64>,
+    /// Exclusive end (Unix seconds)
+    #[serde(default)]
+    pub until: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl AnalyticsQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT) as i64
+    }
+}
+
+// ?1 profile, ?2 since, ?3 until (see `collect`)
+const FILTER: &str = "(?1 IS NULL OR profile_id = ?1) AND (?2 IS NULL OR ts >= ?2) AND (?3 IS NULL OR ts < ?3)";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandUsage {
+    pub cmd: String,
+    pub count: i64,
+    pub last_used: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureRate {
+    pub cmd: String,
+    /// Runs with a known exit code
+    pub runs: i64,
+    pub failures: i64,
+    /// failures / runs
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DurationStats {
+    pub cmd: String,
+    /// Runs with a known duration
+    pub runs: i64,
+    pub avg_ms: f64,
+    pub max_ms: i64,
+    pub total_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatmapCell {
+    /// 0 = Sunday
+    pub weekday: u8,
+    pub hour: u8,
+    pub count: i64,
+}
+
+/// Run `sql` with the filter as ?1-?3 followed by `extra` parameters
+fn collect<T>(
+    conn: &Connection,
+    sql: &str,
+    query: &AnalyticsQuery,
+    extra: &[&dyn ToSql],
+    map: impl FnMut(&Row) -> Result<T>,
+) -> Result<Vec<T>> {
+    let mut all: Vec<&dyn ToSql> = vec![&query.profile_id, &query.since, &query.until];
+    all.extend_from_slice(extra);
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(all.as_slice(), map)?;
+    rows.collect()
+}
+
+/// Most frequently run commands
+pub fn top_commands(conn: &Connection, query: &AnalyticsQuery) -> Result<Vec<CommandUsage>> {
+    let sql = format!(
+        "SELECT cmd, COUNT(*) AS count, MAX(ts) FROM history
+         WHERE {}
+         GROUP BY cmd
+         ORDER BY count DESC, MAX(ts) DESC
+         LIMIT ?4",
+        FILTER
+    );
+    collect(conn, &sql, query, &[&query.limit()], |row| {
+        Ok(CommandUsage {
+            cmd: row.get(0)?,
+            count: row.get(1)?,
+            last_used: row.get(2)?,
+        })
+    })
+}
+
+/// Commands that fail most often (non-zero exit code), among those run at least `min_runs` times
+pub fn failure_rates(conn: &Connection, query: &AnalyticsQuery, min_runs: i64) -> Result<Vec<FailureRate>> {
+    let sql = format!(
+        "SELECT cmd, COUNT(*) AS runs, SUM(exit_code != 0) AS failures FROM history
+         WHERE {} AND exit_code IS NOT NULL
+         GROUP BY cmd
+         HAVING runs >= ?5 AND failures > 0
+         ORDER BY CAST(failures AS REAL) / runs DESC, failures DESC
+         LIMIT ?4",
+        FILTER
+    );
+    collect(conn, &sql, query, &[&query.limit(), &min_runs.max(1)], |row| {
+        let runs: i64 = row.get(1)?;
+        let failures: i64 = row.get(2)?;
+        Ok(FailureRate {
+            cmd: row.get(0)?,
+            runs,
+            failures,
+            rate: failures as f64 / runs as f64,
+        })
+    })
+}
+
+/// Commands with the highest average duration
+pub fn slowest_commands(conn: &Connection, query: &AnalyticsQuery) -> Result<Vec<DurationStats>> {
+    let sql = format!(
+        "SELECT cmd, COUNT(*), AVG(duration_ms) AS avg_ms, MAX(duration_ms), SUM(duration_ms) FROM history
+         WHERE {} AND duration_ms IS NOT NULL
+         GROUP BY cmd
+         ORDER BY avg_ms DESC
+         LIMIT ?4",
+        FILTER
+    );
+    collect(conn, &sql, query, &[&query.limit()], |row| {
+        Ok(DurationStats {
+            cmd: row.get(0)?,
+            runs: row.get(1)?,
+            avg_ms: row.get(2)?,
+            max_ms: row.get(3)?,
+            total_ms: row.get(4)?,
+        })
+    })
+}
+
+/// Command counts by weekday and hour, in the caller's time zone
+/// Only non-empty cells are returned
+pub fn activity_heatmap(
+    conn: &Connection,
+    query: &AnalyticsQuery,
+    tz_offset_minutes: i32,
+) -> Result<Vec<HeatmapCell>> {
+    let sql = format!(
+        "SELECT CAST(strftime('%w', ts + ?4, 'unixepoch') AS INTEGER) AS weekday,
+                CAST(strftime('%H', ts + ?4, 'unixepoch') AS INTEGER) AS hour,
+                COUNT(*)
+         FROM history
+         WHERE {}
+         GROUP BY weekday, hour
+         ORDER BY weekday, hour",
+        FILTER
+    );
+    let offset_secs = tz_offset_minutes as i64 * 60;
+    collect(conn, &sql, query, &[&offset_secs], |row| {
+        Ok(HeatmapCell {
+            weekday: row.get(0)?,
+            hour: row.get(1)?,
+            count: row.get(2)?,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+    use rusqlite::params;
+
+    // Sunday 2023-11-12 00:00:00 UTC
+    const SUNDAY: i64 = 1_699_747_200;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        for id in ["p1", "p2"] {
+            conn.execute(
+                "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+                 VALUES (?1, 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+                [id],
+            )
+            .unwrap();
+        }
+
+        // (profile, cmd, seconds after SUNDAY, exit code, duration)
+        let rows = [
+            ("p1", "git status", 9 * 3600, Some(0), Some(20)),
+            ("p1", "git status", 9 * 3600 + 60, Some(0), Some(40)),
+            ("p1", "git status", 86_400 + 10 * 3600, Some(0), None),
+            ("p1", "make test", 86_400 + 10 * 3600, Some(2), Some(90_000)),
+            ("p1", "make test", 86_400 + 11 * 3600, Some(0), Some(60_000)),
+            ("p1", "cargo build", 86_400 + 11 * 3600, Some(101), Some(30_000)),
+            ("p1", "cargo build", 86_400 + 12 * 3600, None, None),
+            ("p2", "uptime", 3600, Some(0), Some(5)),
+        ];
+        for (i, (profile, cmd, offset, exit_code, duration)) in rows.into_iter().enumerate() {
+            conn.execute(
+                "INSERT INTO history (id, profile_id, cmd, ts, exit_code, duration_ms) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![format!("h{}", i), profile, cmd, SUNDAY + offset, exit_code, duration],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn p1() -> AnalyticsQuery {
+        AnalyticsQuery {
+            profile_id: Some("p1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_usage_failures_and_durations() {
+        let conn = setup();
+
+        let top = top_commands(&conn, &p1()).unwrap();
+        assert_eq!(top[0].cmd, "git status");
+        assert_eq!(top[0].count, 3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top_commands(&conn, &AnalyticsQuery::default()).unwrap().len(), 4);
+
+        // cargo build: 1 known run, 1 failure; make test: 2 runs, 1 failure
+        let failures = failure_rates(&conn, &p1(), 1).unwrap();
+        let summary: Vec<(&str, i64, f64)> = failures.iter().map(|f| (f.cmd.as_str(), f.runs, f.rate)).collect();
+        assert_eq!(summary, vec![("cargo build", 1, 1.0), ("make test", 2, 0.5)]);
+        assert_eq!(failure_rates(&conn, &p1(), 2).unwrap().len(), 1);
+
+        let slow = slowest_commands(&conn, &p1()).unwrap();
+        assert_eq!(slow[0].cmd, "make test");
+        assert_eq!(slow[0].avg_ms, 75_000.0);
+        assert_eq!(slow[0].total_ms, 150_000);
+        assert_eq!(slow.last().unwrap().cmd, "git status");
+
+        let window = AnalyticsQuery {
+            since: Some(SUNDAY + 86_400),
+            limit: Some(1),
+            ..p1()
+        };
+        let top = top_commands(&conn, &window).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!((top[0].cmd.as_str(), top[0].count), ("cargo build", 2));
+    }
+
+    #[test]
+    fn test_activity_heatmap_time_zone() {
+        let conn = setup();
+
+        let cells = activity_heatmap(&conn, &p1(), 0).unwrap();
+        assert_eq!(cells[0], HeatmapCell { weekday: 0, hour: 9, count: 2 });
+        assert_eq!(cells.iter().map(|c| c.count).sum::<i64>(), 7);
+
+        // UTC-10: Sunday 09:00 is Saturday 23:00, Monday 10:00 is Monday 00:00
+        let cells = activity_heatmap(&conn, &p1(), -600).unwrap();
+        assert_eq!(cells[0], HeatmapCell { weekday: 1, hour: 0, count: 2 });
+        assert!(cells.contains(&HeatmapCell { weekday: 6, hour: 23, count: 2 }));
+    }
+}
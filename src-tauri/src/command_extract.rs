@@ -0,0 +1,441 @@
+//! Shell commands in AI responses
+//!
+//! Parses the markdown of a response into fenced code blocks (``` or ~~~,
+//! indented inside list items too) and splits shell blocks into commands:
+//! prompt markers (`$ `, `user@host:~$ `, a root `# ` before a known command)
+//! are stripped, the output lines of a pasted session are dropped, and line
+//! continuations and heredoc bodies stay with the command that opened them.
+//! Blocks in other languages (json, yaml, python, plain output) are skipped.
+//! When a response has no code block at all, inline `code` spans that look
+//! like commands are used instead.
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::commands_dict;
+
+/// Fence languages treated as shell (an untagged block too)
+const SHELL_LANGUAGES: &[&str] = &["bash", "sh", "shell", "zsh", "fish", "ksh", "console", "terminal", "shell-session", "shellsession", "sh-session"];
+
+/// Languages whose blocks are copies of a terminal session (lines without a prompt are output)
+const SESSION_LANGUAGES: &[&str] = &["console", "terminal", "shell-session", "shellsession", "sh-session"];
+
+/// Commands recognized after a root `# ` prompt in addition to the completion specs
+const COMMON_COMMANDS: &[&str] = &[
+    "apt-get", "apk", "pacman", "zypper", "snap", "pip", "pip3", "npm", "make", "echo", "printf", "export", "source",
+    "service", "mount", "umount", "lsblk", "useradd", "usermod", "userdel", "passwd", "crontab", "visudo", "nginx",
+    "mysql", "psql", "firewall-cmd", "ufw", "iptables", "setenforce", "hostnamectl", "timedatectl", "reboot",
+    "shutdown", "tee", "dd", "mkfs", "fdisk", "sysctl", "modprobe", "update-alternatives", "certbot", "openssl",
+];
+
+/// Wrappers skipped when looking for the command name
+const WRAPPERS: &[&str] = &["sudo", "doas", "env", "time", "nohup"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandSource {
+    /// A fenced code block
+    Block,
+    /// An inline `code` span (only when the response has no blocks)
+    Inline,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExtractedCommand {
+    pub command: String,
+    /// Fence language, lowercased (None for untagged blocks and inline code)
+    pub language: Option<String>,
+    pub source: CommandSource,
+    /// Index of the code block among the response's shell blocks
+    pub block: Option<usize>,
+    /// 1-based line of the response where the command starts
+    pub line: usize,
+    /// A prompt marker was stripped
+    pub had_prompt: bool,
+    /// Spans several lines (continuation, heredoc or script)
+    pub multiline: bool,
+}
+
+lazy_static::lazy_static! {
+    // user@host:~/dir$ , [user@host dir]# , (venv) user@host %
+    static ref HOST_PROMPT: Regex =
+        Regex::new(r"^(?:\([\w.-]+\)\s*)?(?:\[[^\]\s]+@[^\]]+\]|[\w.-]+@[\w.-]+(?::\S*?)?)\s*[$#%]\s*(.*)$").unwrap();
+    static ref USER_PROMPT: Regex = Regex::new(r"^(?:\([\w.-]+\)\s*)?[$%]\s+(\S.*)$").unwrap();
+    static ref ROOT_PROMPT: Regex = Regex::new(r"^#\s+(\S.*)$").unwrap();
+    static ref HEREDOC: Regex = Regex::new(r#"(?:^|[^<])<<-?\s*['"]?([A-Za-z_][\w.-]*)['"]?"#).unwrap();
+}
+
+/// All commands in a response, in order
+pub fn extract(response: &str) -> Vec<ExtractedCommand> {
+    let blocks = code_blocks(response);
+    if blocks.is_empty() {
+        return inline_commands(response);
+    }
+
+    let mut commands = Vec::new();
+    let shell_blocks = blocks.iter().filter(|b| is_shell(b.language.as_deref()));
+    for (index, block) in shell_blocks.enumerate() {
+        parse_block(block, index, &mut commands);
+    }
+    commands
+}
+
+struct CodeBlock<'a> {
+    language: Option<String>,
+    /// (1-based line number, text with the fence indentation removed)
+    lines: Vec<(usize, &'a str)>,
+}
+
+fn is_shell(language: Option<&str>) -> bool {
+    language.is_none_or(|l| SHELL_LANGUAGES.contains(&l))
+}
+
+/// "```bash", "~~~ {.sh}", "```shell title=x" -> the language
+fn fence_language(info: &str) -> Option<String> {
+    let word = info.split_whitespace().next()?;
+    let word = word.trim_start_matches('{').trim_start_matches('.').trim_end_matches('}');
+    Some(word.to_lowercase()).filter(|w| !w.is_empty())
+}
+
+fn code_blocks(response: &str) -> Vec<CodeBlock<'_>> {
+    let mut blocks = Vec::new();
+    // (fence char, fence length, indentation, block)
+    let mut open: Option<(char, usize, usize, CodeBlock)> = None;
+
+    for (number, line) in response.lines().enumerate() {
+        // Only ASCII indentation is stripped: other whitespace (U+3000, NBSP) is multi-byte
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let trimmed = line.trim();
+        let fence = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let fence_len = fence.map_or(0, |c| trimmed.chars().take_while(|x| *x == c).count());
+
+        match &mut open {
+            Some((c, len, _, _)) if fence == Some(*c) && fence_len >= *len && trimmed.len() == fence_len => {
+                let (_, _, _, block) = open.take().unwrap();
+                blocks.push(block);
+            }
+            Some((_, _, block_indent, block)) => {
+                // Content is indented like the fence when the block is inside a list item
+                let strip = indent.min(*block_indent);
+                block.lines.push((number + 1, &line[strip..]));
+            }
+            None if fence_len >= 3 => {
+                let info = &trimmed[fence_len..];
+                // An inline span such as ```code``` on one line is not a fence
+                if fence == Some('`') && info.contains('`') {
+                    continue;
+                }
+                let block = CodeBlock {
+                    language: fence_language(info),
+                    lines: Vec::new(),
+                };
+                open = Some((fence.unwrap(), fence_len, indent, block));
+            }
+            None => {}
+        }
+    }
+    // An unterminated block runs to the end of the response (truncated or cancelled answers)
+    if let Some((_, _, _, block)) = open {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Whether `word` names a command (completion specs plus a few common ones)
+fn is_known_command(word: &str) -> bool {
+    let name = word.rsplit('/').next().unwrap_or(word);
+    COMMON_COMMANDS.contains(&name) || commands_dict::with_specs(|specs| specs.iter().any(|s| s.name == name))
+}
+
+/// The command name of a line, skipping `sudo` and variable assignments
+fn command_name(line: &str) -> Option<&str> {
+    line.split_whitespace()
+        .find(|w| !WRAPPERS.contains(w) && !w.starts_with('-') && w.find('=').is_none_or(|i| i == 0))
+}
+
+/// Strip a prompt marker; a bare `# ` only counts when `allow_root` and a command follows
+fn strip_prompt(line: &str, allow_root: bool) -> Option<&str> {
+    let line = line.trim();
+    if let Some(caps) = HOST_PROMPT.captures(line).or_else(|| USER_PROMPT.captures(line)) {
+        return caps.get(1).map(|m| m.as_str().trim_end());
+    }
+    if allow_root {
+        if let Some(rest) = ROOT_PROMPT.captures(line).and_then(|c| c.get(1)) {
+            if command_name(rest.as_str()).is_some_and(is_known_command) {
+                return Some(rest.as_str().trim_end());
+            }
+        }
+    }
+    None
+}
+
+/// The line ends with `\`, `|`, `&&` or `||`: the command goes on
+fn continues(line: &str) -> bool {
+    let line = line.trim_end();
+    let backslashes = line.chars().rev().take_while(|c| *c == '\\').count();
+    backslashes % 2 == 1 || line.ends_with('|') || line.ends_with("&&")
+}
+
+fn heredoc_delimiters(line: &str) -> Vec<String> {
+    HEREDOC.captures_iter(line).map(|c| c[1].to_string()).collect()
+}
+
+struct Pending {
+    text: String,
+    line: usize,
+    had_prompt: bool,
+    heredocs: Vec<String>,
+    continues: bool,
+}
+
+impl Pending {
+    fn open(&self) -> bool {
+        !self.heredocs.is_empty() || self.continues
+    }
+}
+
+fn parse_block(block: &CodeBlock, index: usize, out: &mut Vec<ExtractedCommand>) {
+    let mut push = |pending: Pending| {
+        let command = pending.text.trim_end().to_string();
+        if command.trim().is_empty() {
+            return;
+        }
+        out.push(ExtractedCommand {
+            multiline: command.contains('\n'),
+            command,
+            language: block.language.clone(),
+            source: CommandSource::Block,
+            block: Some(index),
+            line: pending.line,
+            had_prompt: pending.had_prompt,
+        });
+    };
+
+    let first = block.lines.iter().find(|(_, l)| !l.trim().is_empty());
+    // A script: keep it whole, comments included
+    if let Some(&(line, _)) = first.filter(|(_, l)| l.trim_start().starts_with("#!")) {
+        let start = block.lines.iter().position(|(n, _)| *n == line).unwrap_or(0);
+        let body: Vec<&str> = block.lines[start..].iter().map(|(_, l)| *l).collect();
+        push(Pending {
+            text: body.join("\n"),
+            line,
+            had_prompt: false,
+            heredocs: Vec::new(),
+            continues: false,
+        });
+        return;
+    }
+
+    // A copied session starts with a prompt
+    let session = block.language.as_deref().is_some_and(|l| SESSION_LANGUAGES.contains(&l))
+        || first.is_some_and(|(_, l)| strip_prompt(l, false).is_some());
+    // A root `# cmd` is a prompt in a session, or when the block has nothing but `#` lines;
+    // otherwise it is a comment
+    let allow_root = session
+        || block
+            .lines
+            .iter()
+            .all(|(_, l)| l.trim().is_empty() || l.trim_start().starts_with('#'));
+
+    let mut pending: Option<Pending> = None;
+    for &(number, line) in &block.lines {
+        if let Some(p) = pending.as_mut() {
+            // Secondary prompt of a copied session
+            let text = if session { line.strip_prefix("> ").unwrap_or(line) } else { line };
+            p.text.push('\n');
+            p.text.push_str(text);
+            if let Some(delimiter) = p.heredocs.first() {
+                if text.trim() == delimiter {
+                    p.heredocs.remove(0);
+                }
+            } else {
+                p.continues = continues(text);
+                p.heredocs = heredoc_delimiters(text);
+            }
+            if !p.open() {
+                push(pending.take().unwrap());
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let (text, had_prompt) = match strip_prompt(trimmed, allow_root) {
+            Some(rest) => (rest, true),
+            // Output of the previous command
+            None if session => continue,
+            None if trimmed.starts_with('#') => continue,
+            // Full-width or no-break spaces in front are not shell whitespace
+            None => (line.trim_end().trim_start_matches(|c: char| c.is_whitespace() && !c.is_ascii()), false),
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        let p = Pending {
+            text: text.to_string(),
+            line: number,
+            had_prompt,
+            heredocs: heredoc_delimiters(text),
+            continues: continues(text),
+        };
+        if p.open() {
+            pending = Some(p);
+        } else {
+            push(p);
+        }
+    }
+    // Unterminated heredoc or continuation at the end of the block
+    if let Some(p) = pending {
+        push(p);
+    }
+}
+
+/// Inline code spans of a line: a run of backticks closed by a run of the same length
+fn code_spans(line: &str) -> Vec<&str> {
+    let bytes = line.as_bytes();
+    let run_at = |i: usize| bytes[i..].iter().take_while(|b| **b == b'`').count();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let open = run_at(i);
+        let start = i + open;
+        let mut j = start;
+        let mut close = None;
+        while j < bytes.len() {
+            if bytes[j] == b'`' {
+                let run = run_at(j);
+                if run == open {
+                    close = Some(j);
+                    break;
+                }
+                j += run;
+            } else {
+                j += 1;
+            }
+        }
+        match close {
+            Some(end) => {
+                spans.push(&line[start..end]);
+                i = end + open;
+            }
+            // No closing run: the backticks are literal
+            None => i = start,
+        }
+    }
+    spans
+}
+
+/// Inline `code` spans that start with a known command and have arguments (or a prompt)
+fn inline_commands(response: &str) -> Vec<ExtractedCommand> {
+    let mut commands = Vec::new();
+    for (number, line) in response.lines().enumerate() {
+        for code in code_spans(line) {
+            let code = code.trim();
+            let (text, had_prompt) = match strip_prompt(code, false) {
+                Some(rest) => (rest, true),
+                None => (code, false),
+            };
+            if !(had_prompt || text.contains(char::is_whitespace)) {
+                continue;
+            }
+            if command_name(text).is_some_and(is_known_command) {
+                commands.push(ExtractedCommand {
+                    command: text.to_string(),
+                    language: None,
+                    source: CommandSource::Inline,
+                    block: None,
+                    line: number + 1,
+                    had_prompt,
+                    multiline: false,
+                });
+            }
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(response: &str) -> Vec<String> {
+        extract(response).into_iter().map(|c| c.command).collect()
+    }
+
+    #[test]
+    fn test_extract_corpus() {
+        let cases: &[(&str, &[&str])] = &[
+            // Plain blocks, one command per line, comments dropped
+            ("```bash\n# update the index\napt update\napt install -y jq # json tool\n```", &["apt update", "apt install -y jq # json tool"]),
+            // Prompts stripped, output lines of a session dropped
+            (
+                "```console\n$ df -h /\nFilesystem  Size  Used\n/dev/sda1   40G   12G\nuser@web-1:~/app$ du -sh .\n12G .\n```",
+                &["df -h /", "du -sh ."],
+            ),
+            ("```\n[root@db01 ~]# systemctl restart postgresql\n```", &["systemctl restart postgresql"]),
+            // Root prompts are kept when the block is nothing but prompts
+            ("```\n# apt update\n# systemctl restart nginx\n```", &["apt update", "systemctl restart nginx"]),
+            ("```sh\n# ls shows the files\nls -la\n```", &["ls -la"]),
+            // Line continuations and pipes across lines
+            (
+                "```bash\ndocker run -d \\\n  --name web \\\n  nginx\ncat access.log |\n  sort | uniq -c\n```",
+                &["docker run -d \\\n  --name web \\\n  nginx", "cat access.log |\n  sort | uniq -c"],
+            ),
+            // Heredoc bodies are preserved verbatim, comments and prompts included
+            (
+                "```bash\ncat <<'EOF' > /etc/motd\n# managed by ops\n$ not a prompt\nEOF\nchmod 644 /etc/motd\n```",
+                &["cat <<'EOF' > /etc/motd\n# managed by ops\n$ not a prompt\nEOF", "chmod 644 /etc/motd"],
+            ),
+            (
+                "```console\n$ cat <<EOF | sudo tee /etc/sysctl.d/99.conf\n> vm.swappiness=10\n> EOF\nvm.swappiness=10\n```",
+                &["cat <<EOF | sudo tee /etc/sysctl.d/99.conf\nvm.swappiness=10\nEOF"],
+            ),
+            // Scripts stay whole
+            ("```bash\n#!/bin/bash\n# backup\nset -e\ntar czf /tmp/b.tgz /etc\n```", &["#!/bin/bash\n# backup\nset -e\ntar czf /tmp/b.tgz /etc"]),
+            // Other languages are not commands
+            ("```json\n{\"a\": 1}\n```\n```yaml\nkey: value\n```\n```text\nerror: x\n```", &[]),
+            // Tildes, list indentation and an unterminated block
+            ("1. Install:\n\n   ~~~sh\n   apt install nginx\n   ~~~\n2. Start:\n   ```\n   systemctl start nginx", &["apt install nginx", "systemctl start nginx"]),
+            // Full-width and no-break spaces inside an indented block
+            ("1. Check:\n   ```bash\n\u{3000}df -h\n\u{a0}\u{a0}free -m\n   ```", &["df -h", "free -m"]),
+            // Inline code only without blocks, and only when it looks like a command
+            ("Run `sudo apt update` then check `/etc/hosts` and `$ uptime`.", &["sudo apt update", "uptime"]),
+            ("See ```bash``` docs, then `git status`", &["git status"]),
+            ("Use `ls` here\n```\npwd\n```", &["pwd"]),
+        ];
+        for (response, expected) in cases {
+            assert_eq!(commands(response), *expected, "{}", response);
+        }
+    }
+
+    #[test]
+    fn test_extract_metadata() {
+        let response = "Check it:\n\n```console\n$ systemctl status nginx\n● nginx.service\n```\n\n```BASH\ntail -f \\\n  /var/log/syslog\n```";
+        let extracted = extract(response);
+        assert_eq!(extracted.len(), 2);
+
+        assert_eq!(extracted[0].language.as_deref(), Some("console"));
+        assert_eq!(extracted[0].block, Some(0));
+        assert_eq!(extracted[0].line, 4);
+        assert!(extracted[0].had_prompt);
+        assert!(!extracted[0].multiline);
+
+        assert_eq!(extracted[1].language.as_deref(), Some("bash"));
+        assert_eq!(extracted[1].block, Some(1));
+        assert_eq!(extracted[1].line, 9);
+        assert_eq!(extracted[1].source, CommandSource::Block);
+        assert!(extracted[1].multiline && !extracted[1].had_prompt);
+
+        let inline = extract("Try `$ free -m`");
+        assert_eq!(inline[0].source, CommandSource::Inline);
+        assert_eq!(inline[0].block, None);
+        assert!(inline[0].had_prompt);
+    }
+}
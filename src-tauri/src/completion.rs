@@ -0,0 +1,352 @@
+//! Argument-aware command line completion
+//!
+//! Parses the partial command line against the command specs (see `commands_dict`)
+//! to find where the cursor is: a command name, a subcommand, an option, an
+//! option's value or a positional argument. Static candidates come from the spec;
+//! for dynamic argument types (files, hosts, branches...) the result reports what
+//! kind of value is expected so the caller can fill it in.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::commands_dict::{self, ArgKind, ArgSpec, CommandSpec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionKind {
+    Command,
+    Subcommand,
+    Option,
+    Argument,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completion {
+    /// Token to insert in place of the partial token
+    pub value: String,
+    /// Whole command line after accepting this completion
+    pub line: String,
+    pub description: Option<String>,
+    pub kind: CompletionKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResult {
+    /// The partial token being completed (unquoted)
+    pub partial: String,
+    /// Character index in the line where the partial token starts
+    pub replace_from: usize,
+    /// Argument type expected at the cursor, if any
+    pub expecting: Option<ArgKind>,
+    pub completions: Vec<Completion>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    /// Byte offset in the line
+    start: usize,
+}
+
+/// Split the current (last) command of a line into shell words
+/// Returns the complete words and the word under the cursor (None after whitespace)
+fn tokenize(line: &str) -> (Vec<Token>, Option<Token>) {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            current.get_or_insert(Token { text: String::new(), start: i }).text.push(c);
+            continue;
+        }
+
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert(Token { text: String::new(), start: i }).text.push(c),
+            (None, '\\') => {
+                escaped = true;
+                current.get_or_insert(Token { text: String::new(), start: i });
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert(Token { text: String::new(), start: i });
+            }
+            (None, '|' | ';' | '&') => {
+                // A new command starts after a pipe or separator
+                current = None;
+                tokens.clear();
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(token) = current.take() {
+                    tokens.push(token);
+                }
+            }
+            (None, c) => current.get_or_insert(Token { text: String::new(), start: i }).text.push(c),
+        }
+    }
+
+    (tokens, current)
+}
+
+fn is_option(token: &str) -> bool {
+    token.len() > 1 && token.starts_with('-')
+}
+
+/// Where the cursor is after walking the complete tokens
+#[derive(Default)]
+struct Position<'a> {
+    /// None while a command name is expected
+    spec: Option<&'a CommandSpec>,
+    /// Option waiting for its value
+    pending: Option<&'a ArgSpec>,
+    positional: usize,
+    used: HashSet<&'a str>,
+    options_ended: bool,
+}
+
+impl<'a> Position<'a> {
+    fn command(spec: &'a CommandSpec) -> Self {
+        Position {
+            spec: Some(spec),
+            ..Default::default()
+        }
+    }
+}
+
+fn find_command<'a>(specs: &'a [CommandSpec], name: &str) -> Option<&'a CommandSpec> {
+    // Allow paths like /usr/bin/git
+    let name = name.rsplit('/').next().unwrap_or(name);
+    specs.iter().find(|s| s.name == name)
+}
+
+/// Walk the complete tokens; None if the command is unknown
+fn locate<'a>(specs: &'a [CommandSpec], tokens: &[Token]) -> Option<Position<'a>> {
+    let mut pos = Position::default();
+
+    for token in tokens {
+        let tok = token.text.as_str();
+        let Some(spec) = pos.spec else {
+            pos = Position::command(find_command(specs, tok)?);
+            continue;
+        };
+
+        if pos.pending.take().is_some() {
+            continue;
+        }
+
+        if !pos.options_ended && tok == "--" {
+            pos.options_ended = true;
+            continue;
+        }
+
+        if !pos.options_ended && is_option(tok) {
+            let name = tok.split('=').next().unwrap_or(tok);
+            if let Some(opt) = spec.option(name) {
+                pos.used.extend(opt.names.iter().map(|n| n.as_str()));
+                if !tok.contains('=') {
+                    pos.pending = opt.arg.as_ref();
+                }
+            }
+            continue;
+        }
+
+        if pos.positional == 0 {
+            if let Some(sub) = spec.subcommand(tok) {
+                pos = Position::command(sub);
+                continue;
+            }
+        }
+
+        if spec.arg_at(pos.positional).is_some_and(|a| a.kind == ArgKind::Command) {
+            // sudo, watch: the rest of the line is its own command
+            pos = Position::command(find_command(specs, tok)?);
+            continue;
+        }
+
+        pos.positional += 1;
+    }
+
+    Some(pos)
+}
+
+/// Build a completion that replaces the partial token after `head`
+pub fn make_completion(
+    head: &str,
+    value: String,
+    description: Option<String>,
+    kind: CompletionKind,
+) -> Completion {
+    // Leave the cursor on the same word after "--opt=" or a directory
+    let suffix = if value.ends_with('=') || value.ends_with('/') { "" } else { " " };
+    Completion {
+        line: format!("{}{}{}", head, value, suffix),
+        value,
+        description,
+        kind,
+    }
+}
+
+/// Complete the last token of `line` using the loaded command specs
+pub fn complete(line: &str, limit: usize) -> CompletionResult {
+    commands_dict::with_specs(|specs| complete_with(specs, line, limit))
+}
+
+pub fn complete_with(specs: &[CommandSpec], line: &str, limit: usize) -> CompletionResult {
+    let (tokens, partial) = tokenize(line);
+    let (partial, replace_from) = match partial {
+        Some(t) => (t.text, t.start),
+        None => (String::new(), line.len()),
+    };
+    let head = &line[..replace_from];
+
+    let mut result = CompletionResult {
+        partial: partial.clone(),
+        replace_from: head.chars().count(),
+        expecting: None,
+        completions: Vec::new(),
+    };
+
+    let Some(pos) = locate(specs, &tokens) else {
+        return result;
+    };
+
+    let mut push = |value: String, description: Option<&String>, kind: CompletionKind| {
+        result
+            .completions
+            .push(make_completion(head, value, description.cloned(), kind));
+    };
+
+    let command_names = |push: &mut dyn FnMut(String, Option<&String>, CompletionKind)| {
+        for spec in specs.iter().filter(|s| s.name.starts_with(&partial)) {
+            push(spec.name.clone(), spec.description.as_ref(), CompletionKind::Command);
+        }
+    };
+
+    let Some(spec) = pos.spec else {
+        command_names(&mut push);
+        result.completions.truncate(limit);
+        return result;
+    };
+
+    let options = |push: &mut dyn FnMut(String, Option<&String>, CompletionKind)| {
+        for opt in spec.options.iter().filter(|o| !o.names.iter().any(|n| pos.used.contains(n.as_str()))) {
+            if let Some(name) = opt.names.iter().find(|n| n.starts_with(&partial)) {
+                push(name.clone(), opt.description.as_ref(), CompletionKind::Option);
+            }
+        }
+    };
+
+    let mut expecting = None;
+
+    if let Some(arg) = pos.pending {
+        expecting = Some(arg.kind);
+        for s in arg.suggestions.iter().filter(|s| s.starts_with(&partial)) {
+            push(s.clone(), None, CompletionKind::Argument);
+        }
+    } else if !pos.options_ended && partial.starts_with('-') {
+        match partial.split_once('=') {
+            Some((name, value)) => {
+                if let Some(arg) = spec.option(name).and_then(|o| o.arg.as_ref()) {
+                    expecting = Some(arg.kind);
+                    for s in arg.suggestions.iter().filter(|s| s.starts_with(value)) {
+                        push(format!("{}={}", name, s), None, CompletionKind::Argument);
+                    }
+                }
+            }
+            None => options(&mut push),
+        }
+    } else {
+        if pos.positional == 0 {
+            for sub in spec.subcommands.iter().filter(|s| s.name.starts_with(&partial)) {
+                push(sub.name.clone(), sub.description.as_ref(), CompletionKind::Subcommand);
+            }
+        }
+
+        match spec.arg_at(pos.positional) {
+            Some(arg) if arg.kind == ArgKind::Command => command_names(&mut push),
+            Some(arg) => {
+                expecting = Some(arg.kind);
+                for s in arg.suggestions.iter().filter(|s| s.starts_with(&partial)) {
+                    push(s.clone(), None, CompletionKind::Argument);
+                }
+            }
+            None => {}
+        }
+
+        // Nothing static to offer at an empty word: show the options instead
+        if partial.is_empty() && !pos.options_ended {
+            options(&mut push);
+        }
+    }
+
+    result.expecting = expecting;
+    result.completions.truncate(limit);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs() -> Vec<CommandSpec> {
+        commands_dict::parse_toml(include_str!("../specs/commands.toml")).unwrap()
+    }
+
+    fn values(result: &CompletionResult) -> Vec<&str> {
+        result.completions.iter().map(|c| c.value.as_str()).collect()
+    }
+
+    #[test]
+    fn test_completes_subcommands_options_and_arg_kinds() {
+        let specs = specs();
+
+        let r = complete_with(&specs, "git ch", 10);
+        assert_eq!(values(&r), vec!["checkout"]);
+        assert_eq!(r.completions[0].line, "git checkout ");
+        assert_eq!(r.replace_from, 4);
+
+        let r = complete_with(&specs, "git commit --am", 10);
+        assert_eq!(values(&r), vec!["--amend"]);
+        assert_eq!(r.completions[0].kind, CompletionKind::Option);
+
+        // Option values and typed positionals
+        let r = complete_with(&specs, "journalctl -u ", 10);
+        assert_eq!(r.expecting, Some(ArgKind::Service));
+        let r = complete_with(&specs, "git checkout ", 10);
+        assert_eq!(r.expecting, Some(ArgKind::Branch));
+        let r = complete_with(&specs, "git push -u origin ", 10);
+        assert_eq!(r.expecting, Some(ArgKind::Branch));
+        let r = complete_with(&specs, "curl -X P", 10);
+        assert_eq!(values(&r), vec!["POST", "PUT"]);
+        let r = complete_with(&specs, "grep --color=al", 10);
+        assert_eq!(values(&r), vec!["--color=always"]);
+
+        // Used options are not offered again
+        let r = complete_with(&specs, "tail -f -", 20);
+        assert!(!values(&r).contains(&"-f"));
+        assert!(values(&r).contains(&"-n"));
+
+        assert!(complete_with(&specs, "frobnicate --", 10).completions.is_empty());
+    }
+
+    #[test]
+    fn test_nested_commands_pipes_and_quotes() {
+        let specs = specs();
+
+        let r = complete_with(&specs, "sudo systemctl rest", 10);
+        assert_eq!(values(&r), vec!["restart"]);
+        let r = complete_with(&specs, "sudo systemctl restart ", 10);
+        assert_eq!(r.expecting, Some(ArgKind::Service));
+
+        let r = complete_with(&specs, "ps aux | gr", 10);
+        assert_eq!(values(&r), vec!["grep"]);
+        assert_eq!(r.completions[0].line, "ps aux | grep ");
+
+        // A quoted message is one word, so the next word is an option again
+        let r = complete_with(&specs, "git commit -m \"fix: a | b\" --a", 10);
+        assert_eq!(values(&r), vec!["--all", "--amend"]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Persisted multi-turn AI conversations
+//!
+//! A conversation belongs to a profile (or to no profile for local use) and
+//! remembers the session it was started from. Each exchange stores the user's
+//! question with the terminal context it was asked in, and the answer, so a
+//! resumed conversation can be replayed to the model as chat history (see
+//! `ai::ask`, which trims it to the context window). Only the questions and
+//! answers are replayed; terminal context goes with the current question alone.
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::ai;
+use crate::ai_provider::{ChatRole, ChatTurn};
+
+/// Titles taken from the first question are cut to this many characters
+const TITLE_MAX_CHARS: usize = 60;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Conversation {
+    pub id: String,
+    pub profile_id: Option<String>,
+    pub session_id: Option<String>,
+    /// Empty until the first question is asked
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub message_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationMessage {
+    pub id: i64,
+    pub role: ChatRole,
+    pub content: String,
+    /// Terminal context sent with a user message
+    pub context: Option<String>,
+    pub ts: i64,
+}
+
+const SELECT_CONVERSATION: &str = "SELECT c.id, c.profile_id, c.session_id, c.title, c.created_at, c.updated_at,
+        (SELECT COUNT(*) FROM ai_messages m WHERE m.conversation_id = c.id)
+     FROM ai_conversations c";
+
+fn row_to_conversation(row: &rusqlite::Row) -> Result<Conversation> {
+    Ok(Conversation {
+        id: row.get(0)?,
+        profile_id: row.get(1)?,
+        session_id: row.get(2)?,
+        title: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        message_count: row.get(6)?,
+    })
+}
+
+/// Start an empty conversation
+pub fn create(
+    conn: &Connection,
+    profile_id: Option<&str>,
+    session_id: Option<&str>,
+    title: &str,
+    now: i64,
+) -> Result<Conversation> {
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO ai_conversations (id, profile_id, session_id, title, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![&id, profile_id, session_id, title.trim(), now],
+    )?;
+    Ok(Conversation {
+        id,
+        profile_id: profile_id.map(str::to_string),
+        session_id: session_id.map(str::to_string),
+        title: title.trim().to_string(),
+        created_at: now,
+        updated_at: now,
+        message_count: 0,
+    })
+}
+
+/// Conversations of a profile (None: conversations without a profile), most recent first
+pub fn list(conn: &Connection, profile_id: Option<&str>) -> Result<Vec<Conversation>> {
+    let sql = format!("{} WHERE c.profile_id IS ?1 ORDER BY c.updated_at DESC, c.rowid DESC", SELECT_CONVERSATION);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![profile_id], row_to_conversation)?;
+    rows.collect()
+}
+
+pub fn get(conn: &Connection, id: &str) -> Result<Option<Conversation>> {
+    let sql = format!("{} WHERE c.id = ?1", SELECT_CONVERSATION);
+    conn.query_row(&sql, params![id], row_to_conversation).optional()
+}
+
+/// All messages of a conversation, oldest first
+pub fn messages(conn: &Connection, id: &str) -> Result<Vec<ConversationMessage>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, role, content, context, ts FROM ai_messages
+         WHERE conversation_id = ?1
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![id], |row| {
+        let role: String = row.get(1)?;
+        Ok(ConversationMessage {
+            id: row.get(0)?,
+            role: ChatRole::parse(&role).unwrap_or(ChatRole::User),
+            content: row.get(2)?,
+            context: row.get(3)?,
+            ts: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Messages in the form they are sent to the model: questions without the
+/// terminal context they were asked in (old scrollback would crowd out the
+/// conversation and resend output the user has moved on from)
+pub fn history_turns(messages: &[ConversationMessage]) -> Vec<ChatTurn> {
+    messages
+        .iter()
+        .map(|m| ChatTurn {
+            role: m.role,
+            content: match m.role {
+                ChatRole::User => ai::user_message(&m.content, None),
+                ChatRole::Assistant => m.content.clone(),
+            },
+        })
+        .collect()
+}
+
+/// Store a question and its answer; an untitled conversation is named after the question
+pub fn append_exchange(
+    conn: &Connection,
+    id: &str,
+    prompt: &str,
+    context: Option<&str>,
+    response: &str,
+    now: i64,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO ai_messages (conversation_id, role, content, context, ts) VALUES (?1, 'user', ?2, ?3, ?4)",
+        params![id, prompt, context, now],
+    )?;
+    tx.execute(
+        "INSERT INTO ai_messages (conversation_id, role, content, context, ts) VALUES (?1, 'assistant', ?2, NULL, ?3)",
+        params![id, response, now],
+    )?;
+    tx.execute(
+        "UPDATE ai_conversations
+         SET updated_at = ?2, title = CASE WHEN title = '' THEN ?3 ELSE title END
+         WHERE id = ?1",
+        params![id, now, title_from(prompt)],
+    )?;
+    tx.commit()
+}
+
+/// First line of the question, shortened
+fn title_from(prompt: &str) -> String {
+    let line = prompt.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    if line.chars().count() > TITLE_MAX_CHARS {
+        format!("{}…", line.chars().take(TITLE_MAX_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Rename a conversation; false if it does not exist
+pub fn rename(conn: &Connection, id: &str, title: &str) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE ai_conversations SET title = ?2 WHERE id = ?1",
+        params![id, title.trim()],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Delete a conversation and its messages
+pub fn delete(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute("DELETE FROM ai_messages WHERE conversation_id = ?1", params![id])?;
+    conn.execute("DELETE FROM ai_conversations WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES ('p1', 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_conversation_lifecycle() {
+        let conn = setup();
+
+        let first = create(&conn, Some("p1"), Some("s1"), "", 100).unwrap();
+        let second = create(&conn, Some("p1"), None, "nginx 설정", 200).unwrap();
+        create(&conn, None, None, "local", 300).unwrap();
+
+        append_exchange(&conn, &first.id, "디스크가 가득 찼어요\n어떻게 하나요?", Some("cwd: /var"), "du -sh *", 400).unwrap();
+        append_exchange(&conn, &first.id, "로그만 지우려면?", None, "journalctl --vacuum-size=100M", 500).unwrap();
+
+        // Most recently used first, only the profile's conversations
+        let listed = list(&conn, Some("p1")).unwrap();
+        let ids: Vec<&str> = listed.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec![first.id.as_str(), second.id.as_str()]);
+        assert_eq!(listed[0].title, "디스크가 가득 찼어요");
+        assert_eq!(listed[0].message_count, 4);
+        assert_eq!(list(&conn, None).unwrap().len(), 1);
+
+        // The context is stored with the question but not replayed
+        let stored = messages(&conn, &first.id).unwrap();
+        assert_eq!(stored[0].context.as_deref(), Some("cwd: /var"));
+        let turns = history_turns(&stored);
+        assert_eq!(turns.len(), 4);
+        assert_eq!(turns[0], ChatTurn { role: ChatRole::User, content: ai::user_message("디스크가 가득 찼어요\n어떻게 하나요?", None) });
+        assert_eq!(turns[1], ChatTurn { role: ChatRole::Assistant, content: "du -sh *".into() });
+
+        // An explicit title is kept
+        append_exchange(&conn, &second.id, "reload 방법", None, "nginx -s reload", 600).unwrap();
+        assert_eq!(get(&conn, &second.id).unwrap().unwrap().title, "nginx 설정");
+
+        assert!(rename(&conn, &first.id, "  디스크 정리 ").unwrap());
+        assert_eq!(get(&conn, &first.id).unwrap().unwrap().title, "디스크 정리");
+        assert!(!rename(&conn, "missing", "x").unwrap());
+
+        delete(&conn, &first.id).unwrap();
+        assert!(get(&conn, &first.id).unwrap().is_none());
+        assert!(messages(&conn, &first.id).unwrap().is_empty());
+        assert_eq!(list(&conn, Some("p1")).unwrap().len(), 1);
+    }
+}
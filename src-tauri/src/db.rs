@@ -2,7 +2,7 @@ use rusqlite::{Connection, Result};
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 
-const CURRENT_DB_VERSION: i32 = 2; // Updated for keyring migration
+const CURRENT_DB_VERSION: i32 = 8; // Added profiles.import_remote_history
 
 /// Get the path to the SQLite database file
 pub fn get_db_path(app: &AppHandle) -> PathBuf {
@@ -82,6 +82,7 @@ pub fn init_db(conn: &Connection) -> Result<()> {
             auth_type TEXT NOT NULL,
             password TEXT,
             profile_group TEXT,
+            import_remote_history INTEGER NOT NULL DEFAULT 0,
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL
         )",
@@ -97,6 +98,10 @@ pub fn init_db(conn: &Connection) -> Result<()> {
             ts INTEGER NOT NULL,
             exit_code INTEGER,
             duration_ms INTEGER,
+            cwd TEXT,
+            hostname TEXT,
+            session_id TEXT,
+            shell TEXT,
             FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
         )",
         [],
@@ -122,6 +127,91 @@ pub fn init_db(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Aggregated per-command stats (frequency, last use, frecency key)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS history_stats (
+            profile_id TEXT NOT NULL,
+            cmd TEXT NOT NULL,
+            frequency INTEGER NOT NULL,
+            last_used INTEGER NOT NULL,
+            frecency_key REAL NOT NULL,
+            PRIMARY KEY (profile_id, cmd),
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_history_stats_frecency ON history_stats(profile_id, frecency_key)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_history_profile_cmd ON history(profile_id, cmd)",
+        [],
+    )?;
+
+    // Aggregates of history rows removed by retention/compaction
+    // (frecency_key was computed with half_life_days)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS history_archive (
+            profile_id TEXT NOT NULL,
+            cmd TEXT NOT NULL,
+            frequency INTEGER NOT NULL,
+            last_used INTEGER NOT NULL,
+            frecency_key REAL NOT NULL,
+            half_life_days REAL NOT NULL,
+            PRIMARY KEY (profile_id, cmd),
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_history_profile_ts ON history(profile_id, ts)",
+        [],
+    )?;
+
+    // Command sequence counts for next-command prediction
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS history_transitions (
+            profile_id TEXT NOT NULL,
+            ctx_order INTEGER NOT NULL,
+            context TEXT NOT NULL,
+            next_cmd TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            last_used INTEGER NOT NULL,
+            PRIMARY KEY (profile_id, ctx_order, context, next_cmd),
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Full-text index over history commands (trigram tokenizer = substring search)
+    // Kept in sync with the history table by triggers
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5(
+            cmd,
+            content='history',
+            content_rowid='rowid',
+            tokenize='trigram'
+        )",
+        [],
+    )?;
+
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS history_fts_insert AFTER INSERT ON history BEGIN
+            INSERT INTO history_fts(rowid, cmd) VALUES (new.rowid, new.cmd);
+         END;
+         CREATE TRIGGER IF NOT EXISTS history_fts_delete AFTER DELETE ON history BEGIN
+            INSERT INTO history_fts(history_fts, rowid, cmd) VALUES ('delete', old.rowid, old.cmd);
+         END;
+         CREATE TRIGGER IF NOT EXISTS history_fts_update AFTER UPDATE OF cmd ON history BEGIN
+            INSERT INTO history_fts(history_fts, rowid, cmd) VALUES ('delete', old.rowid, old.cmd);
+            INSERT INTO history_fts(rowid, cmd) VALUES (new.rowid, new.cmd);
+         END;",
+    )?;
+
     // Create macros table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS macros (
@@ -135,6 +225,163 @@ pub fn init_db(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Create fleet run tables (non-interactive commands over a profile group)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS fleet_runs (
+            id TEXT PRIMARY KEY,
+            profile_group TEXT NOT NULL,
+            command TEXT NOT NULL,
+            concurrency INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            finished_at INTEGER,
+            host_count INTEGER NOT NULL,
+            failed_count INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS fleet_results (
+            id TEXT PRIMARY KEY,
+            run_id TEXT NOT NULL,
+            profile_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            host TEXT NOT NULL,
+            stdout TEXT NOT NULL,
+            stderr TEXT NOT NULL,
+            exit_code INTEGER,
+            error TEXT,
+            duration_ms INTEGER NOT NULL,
+            FOREIGN KEY (run_id) REFERENCES fleet_runs(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_fleet_results_run_id ON fleet_results(run_id)",
+        [],
+    )?;
+
+    // Create AI conversation tables (multi-turn chat per profile)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_conversations (
+            id TEXT PRIMARY KEY,
+            profile_id TEXT,
+            session_id TEXT,
+            title TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            conversation_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            context TEXT,
+            ts INTEGER NOT NULL,
+            FOREIGN KEY (conversation_id) REFERENCES ai_conversations(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_ai_messages_conversation ON ai_messages(conversation_id, id)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_agent_runs (
+            id TEXT PRIMARY KEY,
+            profile_id TEXT,
+            session_id TEXT NOT NULL,
+            goal TEXT NOT NULL,
+            cwd TEXT,
+            os_info TEXT,
+            status TEXT NOT NULL,
+            summary TEXT,
+            max_steps INTEGER NOT NULL,
+            max_risk TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_agent_steps (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            run_id TEXT NOT NULL,
+            idx INTEGER NOT NULL,
+            thought TEXT NOT NULL,
+            command TEXT NOT NULL,
+            risk TEXT,
+            risk_explanation TEXT,
+            status TEXT NOT NULL,
+            note TEXT,
+            stdout TEXT,
+            stderr TEXT,
+            exit_code INTEGER,
+            error TEXT,
+            duration_ms INTEGER,
+            proposed_at INTEGER NOT NULL,
+            executed_at INTEGER,
+            FOREIGN KEY (run_id) REFERENCES ai_agent_runs(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_ai_agent_steps_run ON ai_agent_steps(run_id, idx)",
+        [],
+    )?;
+
+    // Runbook index: one row per markdown file, one per embedded chunk
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS runbook_docs (
+            path TEXT PRIMARY KEY,
+            hash TEXT NOT NULL,
+            model TEXT NOT NULL,
+            chunk_count INTEGER NOT NULL,
+            indexed_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS runbook_chunks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            path TEXT NOT NULL,
+            heading TEXT NOT NULL,
+            content TEXT NOT NULL,
+            start_line INTEGER NOT NULL,
+            embedding BLOB NOT NULL,
+            FOREIGN KEY (path) REFERENCES runbook_docs(path) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_runbook_chunks_path ON runbook_chunks(path)",
+        [],
+    )?;
+
+    // Embedding vectors of distinct history commands, per embedding model
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS history_embeddings (
+            cmd TEXT NOT NULL,
+            model TEXT NOT NULL,
+            embedding BLOB NOT NULL,
+            PRIMARY KEY (cmd, model)
+        )",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -198,6 +445,86 @@ fn migrate_v1_to_v2(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Check whether a table has a given column
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| {
+            let count: i32 = row.get(0)?;
+            Ok(count > 0)
+        },
+    )
+}
+
+/// Migrate from version 2 to version 3 (working directory captured by shell integration)
+fn migrate_v2_to_v3(conn: &Connection) -> Result<()> {
+    println!("[Migration] Starting v2 → v3 migration (history.cwd)");
+
+    if !has_column(conn, "history", "cwd")? {
+        conn.execute("ALTER TABLE history ADD COLUMN cwd TEXT", [])?;
+    }
+
+    println!("[Migration] v2 → v3 migration completed successfully");
+    Ok(())
+}
+
+/// Migrate from version 3 to version 4 (remote hostname, session and shell per history entry)
+fn migrate_v3_to_v4(conn: &Connection) -> Result<()> {
+    println!("[Migration] Starting v3 → v4 migration (history hostname/session_id/shell)");
+
+    for column in ["hostname", "session_id", "shell"] {
+        if !has_column(conn, "history", column)? {
+            conn.execute(&format!("ALTER TABLE history ADD COLUMN {} TEXT", column), [])?;
+        }
+    }
+
+    println!("[Migration] v3 → v4 migration completed successfully");
+    Ok(())
+}
+
+/// Migrate from version 4 to version 5 (index existing history in history_fts)
+fn migrate_v4_to_v5(conn: &Connection) -> Result<()> {
+    println!("[Migration] Starting v4 → v5 migration (history full-text index)");
+
+    // Table and triggers are created by init_db; fill the index from existing rows
+    conn.execute("INSERT INTO history_fts(history_fts) VALUES ('rebuild')", [])?;
+
+    println!("[Migration] v4 → v5 migration completed successfully");
+    Ok(())
+}
+
+/// Migrate from version 5 to version 6 (aggregate existing history into history_stats)
+fn migrate_v5_to_v6(conn: &Connection) -> Result<()> {
+    println!("[Migration] Starting v5 → v6 migration (frecency stats)");
+    crate::frecency::rebuild(conn)?;
+    println!("[Migration] v5 → v6 migration completed successfully");
+    Ok(())
+}
+
+/// Migrate from version 6 to version 7 (learn command sequences from existing history)
+fn migrate_v6_to_v7(conn: &Connection) -> Result<()> {
+    println!("[Migration] Starting v6 → v7 migration (command transitions)");
+    crate::prediction::rebuild(conn)?;
+    println!("[Migration] v6 → v7 migration completed successfully");
+    Ok(())
+}
+
+/// Migrate from version 7 to version 8 (per-profile remote history import opt-in)
+fn migrate_v7_to_v8(conn: &Connection) -> Result<()> {
+    println!("[Migration] Starting v7 → v8 migration (profiles.import_remote_history)");
+
+    if !has_column(conn, "profiles", "import_remote_history")? {
+        conn.execute(
+            "ALTER TABLE profiles ADD COLUMN import_remote_history INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    println!("[Migration] v7 → v8 migration completed successfully");
+    Ok(())
+}
+
 /// Run all necessary migrations
 pub fn run_migrations(conn: &Connection) -> Result<()> {
     let current_version = get_db_version(conn)?;
@@ -214,6 +541,36 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         set_db_version(conn, 2)?;
     }
 
+    if current_version < 3 {
+        migrate_v2_to_v3(conn)?;
+        set_db_version(conn, 3)?;
+    }
+
+    if current_version < 4 {
+        migrate_v3_to_v4(conn)?;
+        set_db_version(conn, 4)?;
+    }
+
+    if current_version < 5 {
+        migrate_v4_to_v5(conn)?;
+        set_db_version(conn, 5)?;
+    }
+
+    if current_version < 6 {
+        migrate_v5_to_v6(conn)?;
+        set_db_version(conn, 6)?;
+    }
+
+    if current_version < 7 {
+        migrate_v6_to_v7(conn)?;
+        set_db_version(conn, 7)?;
+    }
+
+    if current_version < 8 {
+        migrate_v7_to_v8(conn)?;
+        set_db_version(conn, 8)?;
+    }
+
     println!("[Migration] All migrations completed. DB version: {}", CURRENT_DB_VERSION);
     Ok(())
 }
@@ -225,6 +582,9 @@ pub fn open_connection(app: &AppHandle) -> Result<Connection> {
 
     let conn = Connection::open(db_path)?;
 
+    // The retention scheduler writes on its own connection: wait for it instead of failing
+    conn.busy_timeout(std::time::Duration::from_secs(10))?;
+
     // Initialize tables
     init_db(&conn)?;
 
@@ -257,5 +617,35 @@ mod tests {
         assert!(tables.contains(&"profiles".to_string()));
         assert!(tables.contains(&"history".to_string()));
         assert!(tables.contains(&"settings".to_string()));
+        assert!(tables.contains(&"fleet_runs".to_string()));
+        assert!(tables.contains(&"fleet_results".to_string()));
+    }
+
+    #[test]
+    fn test_history_column_migrations() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE history (
+                id TEXT PRIMARY KEY,
+                profile_id TEXT NOT NULL,
+                cmd TEXT NOT NULL,
+                ts INTEGER NOT NULL,
+                exit_code INTEGER,
+                duration_ms INTEGER
+            )",
+            [],
+        )
+        .unwrap();
+
+        migrate_v2_to_v3(&conn).unwrap();
+        assert!(has_column(&conn, "history", "cwd").unwrap());
+
+        // Running it again must be a no-op
+        migrate_v2_to_v3(&conn).unwrap();
+
+        migrate_v3_to_v4(&conn).unwrap();
+        assert!(has_column(&conn, "history", "hostname").unwrap());
+        assert!(has_column(&conn, "history", "session_id").unwrap());
+        assert!(has_column(&conn, "history", "shell").unwrap());
     }
 }
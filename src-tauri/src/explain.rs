@@ -0,0 +1,248 @@
+//! "Explain this error" for failed commands
+//!
+//! Packages a failed history entry — the command, its exit code, the output
+//! captured from the session (see `ssh::command_output`) and the environment —
+//! into a prompt that asks for a JSON diagnosis, then parses the answer into
+//! structured fields. Models that ignore the format still produce a usable
+//! result: the raw text becomes the explanation and fix commands are taken
+//! from its code blocks.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::{self, PromptLanguage};
+use crate::ai_provider::GenerateRequest;
+use crate::redact::Redactor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    /// "high" / "medium" / "low", or a 0-1 (or 0-100) score
+    fn from_value(value: &serde_json::Value) -> Self {
+        let score = match value {
+            serde_json::Value::String(s) => match s.trim().to_lowercase().as_str() {
+                "high" => return Confidence::High,
+                "medium" => return Confidence::Medium,
+                other => other.trim_end_matches('%').parse::<f64>().unwrap_or(0.0),
+            },
+            serde_json::Value::Number(n) => n.as_f64().unwrap_or(0.0),
+            _ => 0.0,
+        };
+        let score = if score > 1.0 { score / 100.0 } else { score };
+        if score >= 0.75 {
+            Confidence::High
+        } else if score >= 0.4 {
+            Confidence::Medium
+        } else {
+            Confidence::Low
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixCommand {
+    pub command: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A failed command and what is known about where it ran
+#[derive(Debug, Clone, Default)]
+pub struct FailedCommand {
+    pub cmd: String,
+    pub exit_code: i32,
+    pub cwd: Option<String>,
+    pub shell: Option<String>,
+    pub hostname: Option<String>,
+    pub os_info: Option<String>,
+    /// Plain output captured from the session, if still available
+    pub output: Option<String>,
+}
+
+/// Parsed diagnosis
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnosis {
+    pub cause: String,
+    pub explanation: String,
+    pub fix_commands: Vec<FixCommand>,
+    pub confidence: Confidence,
+    /// False if the model did not answer in the requested JSON format
+    pub structured: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorExplanation {
+    pub history_id: String,
+    pub cmd: String,
+    pub exit_code: i32,
+    /// Whether session output was included in the prompt
+    pub output_captured: bool,
+    #[serde(flatten)]
+    pub diagnosis: Diagnosis,
+    pub model: String,
+    pub request_id: Option<String>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawDiagnosis {
+    #[serde(default)]
+    cause: String,
+    #[serde(default)]
+    explanation: String,
+    #[serde(default)]
+    fix_commands: Vec<FixCommand>,
+    #[serde(default)]
+    confidence: serde_json::Value,
+}
+
+const SYSTEM_PROMPT: &str = r#"You are an expert Linux/Unix troubleshooter. A command run by the user in a terminal failed.
+Diagnose the most likely cause from the command, its exit code, its output and the environment, and propose a fix.
+
+Reply with a single JSON object and nothing else:
+{"cause": "one sentence", "explanation": "a short paragraph", "fix_commands": [{"command": "...", "description": "..."}], "confidence": "low" | "medium" | "high"}
+
+- fix_commands are shell commands to run in order; leave the list empty if no command can fix it
+- Say so in the description when a command is destructive or needs root
+- Use "low" confidence when the output is missing or the cause is a guess
+
+{language}"#;
+
+/// The prompt for a failed command; the output keeps its last `max_output_chars` characters
+pub fn build_request(
+    failed: &FailedCommand,
+    language: PromptLanguage,
+    redactor: &Redactor,
+    max_output_chars: usize,
+) -> GenerateRequest {
+    let mut user = format!(
+        "## Failed Command\n```\n{}\n```\nExit code: {}",
+        redactor.redact_text(&failed.cmd),
+        failed.exit_code
+    );
+    let environment = [
+        ("Working directory", &failed.cwd),
+        ("Shell", &failed.shell),
+        ("Host", &failed.hostname),
+        ("Operating System", &failed.os_info),
+    ];
+    for (label, value) in environment {
+        if let Some(value) = value {
+            user.push_str(&format!("\n{}: {}", label, value));
+        }
+    }
+
+    match failed.output.as_deref().map(str::trim).filter(|o| !o.is_empty()) {
+        Some(output) => {
+            let output = redactor.redact_text(output);
+            let skip = output.chars().count().saturating_sub(max_output_chars);
+            let tail: String = output.chars().skip(skip).collect();
+            let note = if skip > 0 { " (truncated, last part)" } else { "" };
+            user.push_str(&format!("\n\n## Output{}\n```\n{}\n```", note, tail));
+        }
+        None => user.push_str("\n\n## Output\n(not captured)"),
+    }
+
+    GenerateRequest {
+        system: SYSTEM_PROMPT.replace("{language}", language.rule()),
+        history: Vec::new(),
+        user,
+    }
+}
+
+/// Parse the model's answer (JSON, possibly fenced or surrounded by text)
+pub fn parse_response(text: &str) -> Diagnosis {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => "",
+    };
+
+    match serde_json::from_str::<RawDiagnosis>(json) {
+        Ok(raw) if !raw.cause.trim().is_empty() => Diagnosis {
+            cause: raw.cause.trim().to_string(),
+            explanation: raw.explanation.trim().to_string(),
+            fix_commands: raw
+                .fix_commands
+                .into_iter()
+                .filter(|f| !f.command.trim().is_empty())
+                .collect(),
+            confidence: Confidence::from_value(&raw.confidence),
+            structured: true,
+        },
+        _ => Diagnosis {
+            cause: text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("").to_string(),
+            explanation: text.trim().to_string(),
+            fix_commands: ai::extract_commands(text)
+                .into_iter()
+                .map(|command| FixCommand {
+                    command,
+                    description: String::new(),
+                })
+                .collect(),
+            confidence: Confidence::Low,
+            structured: false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_request() {
+        let redactor = Redactor::new(&[], false).unwrap();
+        let failed = FailedCommand {
+            cmd: "curl -H 'Authorization: Bearer abc.def' https://api/x".into(),
+            exit_code: 22,
+            cwd: Some("/srv".into()),
+            shell: Some("bash".into()),
+            os_info: Some("Linux 6.1.0 x86_64".into()),
+            output: Some(format!("{}\ncurl: (22) The requested URL returned error: 403", "x".repeat(500))),
+            ..Default::default()
+        };
+
+        let request = build_request(&failed, PromptLanguage::En, &redactor, 100);
+        assert!(request.system.contains("respond in English"));
+        assert!(request.user.contains("Exit code: 22\nWorking directory: /srv\nShell: bash\nOperating System: Linux 6.1.0 x86_64"));
+        assert!(request.user.contains("Bearer <redacted>"));
+        assert!(request.user.contains("(truncated, last part)"));
+        assert!(request.user.contains("returned error: 403\n```"));
+        assert!(!request.user.contains(&"x".repeat(100)));
+
+        let missing = FailedCommand { output: None, ..failed };
+        assert!(build_request(&missing, PromptLanguage::Ko, &redactor, 100).user.ends_with("(not captured)"));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let fenced = r#"```json
+{"cause": "Permission denied on /var/log", "explanation": "The file is owned by root.",
+ "fix_commands": [{"command": "sudo tail /var/log/syslog", "description": "needs root"}, {"command": " "}],
+ "confidence": "high"}
+```"#;
+        let diagnosis = parse_response(fenced);
+        assert!(diagnosis.structured);
+        assert_eq!(diagnosis.cause, "Permission denied on /var/log");
+        assert_eq!(diagnosis.fix_commands.len(), 1);
+        assert_eq!(diagnosis.fix_commands[0].command, "sudo tail /var/log/syslog");
+        assert_eq!(diagnosis.confidence, Confidence::High);
+
+        let numeric = parse_response(r#"Here you go: {"cause": "typo", "confidence": 0.5}"#);
+        assert_eq!(numeric.confidence, Confidence::Medium);
+        assert!(numeric.fix_commands.is_empty());
+        assert_eq!(parse_response(r#"{"cause": "x", "confidence": 90}"#).confidence, Confidence::High);
+
+        // Free text falls back to code blocks
+        let free = parse_response("The package is not installed.\n\n```bash\napt install jq\n```");
+        assert!(!free.structured);
+        assert_eq!(free.cause, "The package is not installed.");
+        assert_eq!(free.fix_commands, vec![FixCommand { command: "apt install jq".into(), description: String::new() }]);
+        assert_eq!(free.confidence, Confidence::Low);
+    }
+}
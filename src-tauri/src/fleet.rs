@@ -0,0 +1,470 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::ssh;
+
+/// Default number of hosts contacted at the same time
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// A host whose command runs longer than this is reported as failed
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Connection details for one host of a fleet run
+#[derive(Debug, Clone)]
+pub struct FleetTarget {
+    pub profile_id: String,
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetRun {
+    pub id: String,
+    pub profile_group: String,
+    pub command: String,
+    pub concurrency: i32,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub host_count: i32,
+    pub failed_count: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetResult {
+    pub id: String,
+    pub run_id: String,
+    pub profile_id: String,
+    pub name: String,
+    pub host: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>, // Connection/auth failure (no exit code)
+    pub duration_ms: i64,
+}
+
+/// A run together with its per-host results
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetRunReport {
+    pub run: FleetRun,
+    pub results: Vec<FleetResult>,
+}
+
+/// Hosts that produced the same output within a run
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputGroup {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", content = "line", rename_all = "lowercase")]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Run a command on every target with at most `concurrency` hosts in flight
+/// Results are returned in the same order as the targets
+pub fn run_targets(
+    run_id: &str,
+    targets: Vec<FleetTarget>,
+    command: &str,
+    concurrency: usize,
+) -> Vec<FleetResult> {
+    let total = targets.len();
+    let queue = Arc::new(Mutex::new(
+        targets.into_iter().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let (tx, rx) = mpsc::channel();
+
+    let workers = concurrency.max(1).min(total.max(1));
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let tx = tx.clone();
+        let run_id = run_id.to_string();
+        let command = command.to_string();
+
+        thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let Some((index, target)) = next else {
+                break;
+            };
+            let result = run_one(&run_id, &target, &command);
+            if tx.send((index, result)).is_err() {
+                break;
+            }
+        });
+    }
+    drop(tx);
+
+    let mut results: Vec<(usize, FleetResult)> = rx.iter().collect();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+fn run_one(run_id: &str, target: &FleetTarget, command: &str) -> FleetResult {
+    let started = Instant::now();
+    println!("[fleet:{}] running on {} ({})", run_id, target.name, target.host);
+
+    let outcome = match target.password.as_deref() {
+        Some(password) => ssh::connect(&target.host, target.port, &target.user, password)
+            .and_then(|sess| ssh::exec_capture(&sess, command, COMMAND_TIMEOUT)),
+        None => Err("Password not available for this profile".to_string()),
+    };
+
+    let mut result = FleetResult {
+        id: Uuid::new_v4().to_string(),
+        run_id: run_id.to_string(),
+        profile_id: target.profile_id.clone(),
+        name: target.name.clone(),
+        host: target.host.clone(),
+        stdout: String::new(),
+        stderr: String::new(),
+        exit_code: None,
+        error: None,
+        duration_ms: 0,
+    };
+
+    match outcome {
+        Ok(output) => {
+            result.stdout = output.stdout;
+            result.stderr = output.stderr;
+            result.exit_code = Some(output.exit_code);
+        }
+        Err(e) => {
+            println!("[fleet:{}] {} failed: {}", run_id, target.host, e);
+            result.error = Some(e);
+        }
+    }
+    result.duration_ms = started.elapsed().as_millis() as i64;
+    result
+}
+
+/// Store a finished run and all of its per-host results
+pub fn save_run(conn: &Connection, run: &FleetRun, results: &[FleetResult]) -> Result<()> {
+    conn.execute(
+        "INSERT INTO fleet_runs (id, profile_group, command, concurrency, started_at, finished_at, host_count, failed_count)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            &run.id,
+            &run.profile_group,
+            &run.command,
+            run.concurrency,
+            run.started_at,
+            run.finished_at,
+            run.host_count,
+            run.failed_count,
+        ],
+    )?;
+
+    for result in results {
+        conn.execute(
+            "INSERT INTO fleet_results (id, run_id, profile_id, name, host, stdout, stderr, exit_code, error, duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                &result.id,
+                &result.run_id,
+                &result.profile_id,
+                &result.name,
+                &result.host,
+                &result.stdout,
+                &result.stderr,
+                result.exit_code,
+                &result.error,
+                result.duration_ms,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// List past runs, newest first
+pub fn list_runs(conn: &Connection, limit: usize) -> Result<Vec<FleetRun>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_group, command, concurrency, started_at, finished_at, host_count, failed_count
+         FROM fleet_runs
+         ORDER BY started_at DESC
+         LIMIT ?1",
+    )?;
+
+    let runs = stmt
+        .query_map(params![limit as i64], row_to_run)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(runs)
+}
+
+/// Get a run by ID
+pub fn get_run(conn: &Connection, run_id: &str) -> Result<Option<FleetRun>> {
+    conn.query_row(
+        "SELECT id, profile_group, command, concurrency, started_at, finished_at, host_count, failed_count
+         FROM fleet_runs
+         WHERE id = ?1",
+        params![run_id],
+        row_to_run,
+    )
+    .optional()
+}
+
+/// Get per-host results of a run, ordered by host name
+pub fn get_results(conn: &Connection, run_id: &str) -> Result<Vec<FleetResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, run_id, profile_id, name, host, stdout, stderr, exit_code, error, duration_ms
+         FROM fleet_results
+         WHERE run_id = ?1
+         ORDER BY name",
+    )?;
+
+    let results = stmt
+        .query_map(params![run_id], row_to_result)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(results)
+}
+
+/// Get a single host result by ID
+pub fn get_result(conn: &Connection, result_id: &str) -> Result<Option<FleetResult>> {
+    conn.query_row(
+        "SELECT id, run_id, profile_id, name, host, stdout, stderr, exit_code, error, duration_ms
+         FROM fleet_results
+         WHERE id = ?1",
+        params![result_id],
+        row_to_result,
+    )
+    .optional()
+}
+
+/// Delete a run and its results
+pub fn delete_run(conn: &Connection, run_id: &str) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM fleet_results WHERE run_id = ?1", params![run_id])?;
+    tx.execute("DELETE FROM fleet_runs WHERE id = ?1", params![run_id])?;
+    tx.commit()
+}
+
+fn row_to_run(row: &rusqlite::Row) -> Result<FleetRun> {
+    Ok(FleetRun {
+        id: row.get(0)?,
+        profile_group: row.get(1)?,
+        command: row.get(2)?,
+        concurrency: row.get(3)?,
+        started_at: row.get(4)?,
+        finished_at: row.get(5)?,
+        host_count: row.get(6)?,
+        failed_count: row.get(7)?,
+    })
+}
+
+fn row_to_result(row: &rusqlite::Row) -> Result<FleetResult> {
+    Ok(FleetResult {
+        id: row.get(0)?,
+        run_id: row.get(1)?,
+        profile_id: row.get(2)?,
+        name: row.get(3)?,
+        host: row.get(4)?,
+        stdout: row.get(5)?,
+        stderr: row.get(6)?,
+        exit_code: row.get(7)?,
+        error: row.get(8)?,
+        duration_ms: row.get(9)?,
+    })
+}
+
+/// Group hosts by identical output so outliers stand out
+/// Largest groups come first
+pub fn group_outputs(results: &[FleetResult]) -> Vec<OutputGroup> {
+    let mut groups: Vec<OutputGroup> = Vec::new();
+
+    for result in results {
+        let existing = groups.iter_mut().find(|g| {
+            g.stdout == result.stdout
+                && g.stderr == result.stderr
+                && g.exit_code == result.exit_code
+                && g.error == result.error
+        });
+
+        match existing {
+            Some(group) => group.hosts.push(result.name.clone()),
+            None => groups.push(OutputGroup {
+                stdout: result.stdout.clone(),
+                stderr: result.stderr.clone(),
+                exit_code: result.exit_code,
+                error: result.error.clone(),
+                hosts: vec![result.name.clone()],
+            }),
+        }
+    }
+
+    groups.sort_by_key(|g| std::cmp::Reverse(g.hosts.len()));
+    groups
+}
+
+/// Largest LCS table `diff_lines` builds (cells; 8 bytes each)
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Line-based diff (LCS) between two outputs
+/// The common head and tail are matched first; if what is left is still too
+/// large for the LCS table, it is shown as removed then added
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    let head = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let tail = a[head..]
+        .iter()
+        .rev()
+        .zip(b[head..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[head..a.len() - tail], &b[head..b.len() - tail]);
+
+    let mut diff: Vec<DiffLine> = a[..head].iter().map(|l| DiffLine::Same(l.to_string())).collect();
+    if (a_mid.len() + 1).saturating_mul(b_mid.len() + 1) > MAX_DIFF_CELLS {
+        diff.extend(a_mid.iter().map(|l| DiffLine::Removed(l.to_string())));
+        diff.extend(b_mid.iter().map(|l| DiffLine::Added(l.to_string())));
+    } else {
+        diff.extend(lcs_diff(a_mid, b_mid));
+    }
+    diff.extend(a[a.len() - tail..].iter().map(|l| DiffLine::Same(l.to_string())));
+    diff
+}
+
+fn lcs_diff(a: &[&str], b: &[&str]) -> Vec<DiffLine> {
+    // lcs[i][j] = length of LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            diff.push(DiffLine::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(a[i..].iter().map(|l| DiffLine::Removed(l.to_string())));
+    diff.extend(b[j..].iter().map(|l| DiffLine::Added(l.to_string())));
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+
+    fn result(name: &str, stdout: &str, exit_code: Option<i32>) -> FleetResult {
+        FleetResult {
+            id: Uuid::new_v4().to_string(),
+            run_id: "run-1".to_string(),
+            profile_id: format!("profile-{}", name),
+            name: name.to_string(),
+            host: format!("{}.example.com", name),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code,
+            error: None,
+            duration_ms: 10,
+        }
+    }
+
+    #[test]
+    fn test_save_and_get_run() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let run = FleetRun {
+            id: "run-1".to_string(),
+            profile_group: "web".to_string(),
+            command: "uptime".to_string(),
+            concurrency: 4,
+            started_at: 100,
+            finished_at: Some(105),
+            host_count: 2,
+            failed_count: 1,
+        };
+        let results = vec![result("web1", "up 3 days", Some(0)), result("web2", "", Some(1))];
+        save_run(&conn, &run, &results).unwrap();
+
+        let runs = list_runs(&conn, 10).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].command, "uptime");
+
+        let stored = get_results(&conn, "run-1").unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].name, "web1");
+        assert_eq!(stored[1].exit_code, Some(1));
+
+        delete_run(&conn, "run-1").unwrap();
+        assert!(get_run(&conn, "run-1").unwrap().is_none());
+        assert!(get_results(&conn, "run-1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_group_outputs() {
+        let results = vec![
+            result("web1", "nginx 1.24", Some(0)),
+            result("web2", "nginx 1.22", Some(0)),
+            result("web3", "nginx 1.24", Some(0)),
+        ];
+
+        let groups = group_outputs(&results);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].hosts, vec!["web1", "web3"]);
+        assert_eq!(groups[1].hosts, vec!["web2"]);
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc", "a\nc\nd");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("a".to_string()),
+                DiffLine::Removed("b".to_string()),
+                DiffLine::Same("c".to_string()),
+                DiffLine::Added("d".to_string()),
+            ]
+        );
+
+        // Large outputs: shared head/tail still match, the rest skips the LCS table
+        let old: Vec<String> = (0..20_000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..20_000).map(|i| format!("new {}", i)).collect();
+        let diff = diff_lines(&format!("head\n{}\ntail", old.join("\n")), &format!("head\n{}\ntail", new.join("\n")));
+        assert_eq!(diff.len(), 40_002);
+        assert_eq!(diff[0], DiffLine::Same("head".to_string()));
+        assert_eq!(diff[1], DiffLine::Removed("old 0".to_string()));
+        assert_eq!(diff[40_001], DiffLine::Same("tail".to_string()));
+    }
+}
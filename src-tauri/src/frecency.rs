@@ -0,0 +1,175 @@
+//! Frecency: time-decayed command frequency
+//!
+//! Every use of a command contributes `0.5 ^ (age / half_life)`, so a command
+//! used 5 times today outranks one used 50 times last year.
+//!
+//! Scores are kept per (profile, command) in `history_stats` as a log2 "key"
+//! relative to the Unix epoch: `key = log2(Σ 2 ^ (ts_i / half_life))`.
+//! All scores decay by the same factor over time, so ordering by the stored
+//! key is the same as ordering by the current score and needs no math in SQL.
+//! The current score is `2 ^ (key - now / half_life)`.
+//!
+//! Uses compacted out of the history table (see `retention`) live on in
+//! `history_archive` and are folded back in whenever the stats are rebuilt.
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::collections::HashMap;
+
+use crate::settings;
+
+pub const DEFAULT_HALF_LIFE_DAYS: f64 = 14.0;
+const HALF_LIFE_SETTING: &str = "history_frecency_half_life_days";
+
+/// Configured half-life in days (falls back to the default if unset or invalid)
+pub fn half_life_days(conn: &Connection) -> Result<f64> {
+    let value = settings::get_setting(conn, HALF_LIFE_SETTING)?;
+    Ok(value
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .unwrap_or(DEFAULT_HALF_LIFE_DAYS))
+}
+
+/// Change the half-life (in days, must be positive) and recompute all stored keys
+pub fn set_half_life_days(conn: &Connection, days: f64) -> Result<()> {
+    settings::set_setting(conn, HALF_LIFE_SETTING, &days.to_string())?;
+    rebuild(conn)
+}
+
+pub(crate) fn exponent(ts: i64, half_life_days: f64) -> f64 {
+    ts as f64 / (half_life_days * 86_400.0)
+}
+
+/// log2(2^a + 2^b) without overflow
+pub(crate) fn log2_add(a: f64, b: f64) -> f64 {
+    let (hi, lo) = if a >= b { (a, b) } else { (b, a) };
+    hi + (1.0 + (lo - hi).exp2()).log2()
+}
+
+/// Key for `frequency` uses approximated as all happening at `last_used`
+/// (used when an archived key was computed with a different half-life)
+pub(crate) fn approx_key(frequency: i64, last_used: i64, half_life_days: f64) -> f64 {
+    (frequency.max(1) as f64).log2() + exponent(last_used, half_life_days)
+}
+
+/// Current score for a stored key
+pub fn score_at(key: f64, now: i64, half_life_days: f64) -> f64 {
+    (key - exponent(now, half_life_days)).exp2()
+}
+
+/// Account for one more use of a command at `ts`
+pub fn record_use(conn: &Connection, profile_id: &str, cmd: &str, ts: i64) -> Result<()> {
+    let half_life = half_life_days(conn)?;
+    let use_key = exponent(ts, half_life);
+
+    let existing: Option<f64> = conn
+        .query_row(
+            "SELECT frecency_key FROM history_stats WHERE profile_id = ?1 AND cmd = ?2",
+            params![profile_id, cmd],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    let key = match existing {
+        Some(old) => log2_add(old, use_key),
+        None => use_key,
+    };
+
+    conn.execute(
+        "INSERT INTO history_stats (profile_id, cmd, frequency, last_used, frecency_key)
+         VALUES (?1, ?2, 1, ?3, ?4)
+         ON CONFLICT(profile_id, cmd) DO UPDATE SET
+            frequency = frequency + 1,
+            last_used = MAX(last_used, excluded.last_used),
+            frecency_key = excluded.frecency_key",
+        params![profile_id, cmd, ts, key],
+    )?;
+
+    Ok(())
+}
+
+/// Recompute `history_stats` from the history and archive tables
+pub fn rebuild(conn: &Connection) -> Result<()> {
+    let half_life = half_life_days(conn)?;
+
+    // (profile_id, cmd) -> (frequency, last_used, key)
+    let mut stats: HashMap<(String, String), (i64, i64, f64)> = HashMap::new();
+    {
+        let mut stmt = conn.prepare(
+            "SELECT profile_id, cmd, frequency, last_used, frecency_key, half_life_days FROM history_archive",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let frequency: i64 = row.get(2)?;
+            let last_used: i64 = row.get(3)?;
+            let archived_key: f64 = row.get(4)?;
+            let archived_half_life: f64 = row.get(5)?;
+            let key = if (archived_half_life - half_life).abs() < 1e-9 {
+                archived_key
+            } else {
+                approx_key(frequency, last_used, half_life)
+            };
+            stats.insert((row.get(0)?, row.get(1)?), (frequency, last_used, key));
+        }
+    }
+    {
+        let mut stmt = conn.prepare("SELECT profile_id, cmd, ts FROM history")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let profile_id: String = row.get(0)?;
+            let cmd: String = row.get(1)?;
+            let ts: i64 = row.get(2)?;
+            let use_key = exponent(ts, half_life);
+
+            stats
+                .entry((profile_id, cmd))
+                .and_modify(|(frequency, last_used, key)| {
+                    *frequency += 1;
+                    *last_used = (*last_used).max(ts);
+                    *key = log2_add(*key, use_key);
+                })
+                .or_insert((1, ts, use_key));
+        }
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM history_stats", [])?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO history_stats (profile_id, cmd, frequency, last_used, frecency_key)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for ((profile_id, cmd), (frequency, last_used, key)) in &stats {
+            insert.execute(params![profile_id, cmd, frequency, last_used, key])?;
+        }
+    }
+    tx.commit()?;
+
+    println!("[Frecency] Rebuilt stats for {} commands", stats.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_matches_direct_sum() {
+        let half_life = 7.0;
+        let now = 1_700_000_000;
+        let day = 86_400;
+        let uses = [now, now - day, now - 7 * day, now - 30 * day];
+
+        let key = uses
+            .iter()
+            .map(|ts| exponent(*ts, half_life))
+            .reduce(log2_add)
+            .unwrap();
+
+        let expected: f64 = uses
+            .iter()
+            .map(|ts| 0.5f64.powf((now - ts) as f64 / (half_life * day as f64)))
+            .sum();
+
+        assert!((score_at(key, now, half_life) - expected).abs() < 1e-9);
+    }
+}
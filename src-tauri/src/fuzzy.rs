@@ -0,0 +1,214 @@
+//! fzf-style fuzzy subsequence matcher
+//!
+//! Finds the best-scoring alignment of the pattern characters inside the text
+//! (in order, not necessarily contiguous). Matches at word boundaries and runs
+//! of consecutive characters score higher, gaps are penalized, so "dclapi"
+//! prefers "docker compose logs -f api" over a random scattering of letters.
+//! Matching is case-insensitive unless the pattern contains uppercase (smart case).
+
+const SCORE_MATCH: i32 = 16;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+const BONUS_BOUNDARY_WHITE: i32 = 10; // after whitespace or at the start
+const BONUS_BOUNDARY: i32 = 9; // after / - _ . : = , ; |
+const BONUS_CAMEL: i32 = 7; // fooBar, foo123
+const BONUS_CONSECUTIVE: i32 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+const NONE: i32 = i32::MIN / 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    /// Matched character indices in the text, ascending
+    pub positions: Vec<usize>,
+}
+
+/// Match a single pattern (no whitespace splitting) against a text
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = pattern.chars().any(|c| c.is_uppercase());
+    let normalize = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+
+    let p: Vec<char> = pattern.chars().map(normalize).collect();
+    let original: Vec<char> = text.chars().collect();
+    let t: Vec<char> = original.iter().copied().map(normalize).collect();
+    let (n, m) = (p.len(), t.len());
+
+    if n == 0 {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    if n > m || !is_subsequence(&p, &t) {
+        return None;
+    }
+
+    let bonus: Vec<i32> = (0..m)
+        .map(|j| position_bonus(if j == 0 { None } else { Some(original[j - 1]) }, original[j]))
+        .collect();
+
+    // score[i][j]: best score with p[i] matched at t[j]
+    // from[i][j]: where p[i - 1] was matched on that best path
+    let mut score = vec![vec![NONE; m]; n];
+    let mut from = vec![vec![usize::MAX; m]; n];
+
+    for j in 0..m {
+        if t[j] == p[0] {
+            score[0][j] = SCORE_MATCH + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+        }
+    }
+
+    for i in 1..n {
+        // Best predecessor separated by a gap of at least one character
+        let mut gap_best = NONE;
+        let mut gap_from = usize::MAX;
+
+        for j in i..m {
+            if j >= 2 {
+                gap_best -= PENALTY_GAP_EXTENSION;
+                let candidate = score[i - 1][j - 2] - PENALTY_GAP_START;
+                if score[i - 1][j - 2] > NONE && candidate > gap_best {
+                    gap_best = candidate;
+                    gap_from = j - 2;
+                }
+            }
+
+            if t[j] != p[i] {
+                continue;
+            }
+
+            let consecutive = if score[i - 1][j - 1] > NONE {
+                score[i - 1][j - 1] + bonus[j].max(BONUS_CONSECUTIVE)
+            } else {
+                NONE
+            };
+            let gapped = if gap_best > NONE {
+                gap_best + bonus[j]
+            } else {
+                NONE
+            };
+
+            if consecutive == NONE && gapped == NONE {
+                continue;
+            }
+            if consecutive >= gapped {
+                score[i][j] = SCORE_MATCH + consecutive;
+                from[i][j] = j - 1;
+            } else {
+                score[i][j] = SCORE_MATCH + gapped;
+                from[i][j] = gap_from;
+            }
+        }
+    }
+
+    let (mut j, best) = score[n - 1]
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| **s > NONE)
+        .max_by_key(|(j, s)| (**s, std::cmp::Reverse(*j)))
+        .map(|(j, s)| (j, *s))?;
+
+    let mut positions = vec![0; n];
+    for i in (0..n).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some(FuzzyMatch {
+        score: best,
+        positions,
+    })
+}
+
+/// Match a query whose whitespace-separated terms must all match (like fzf)
+pub fn match_query(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let mut total = FuzzyMatch {
+        score: 0,
+        positions: Vec::new(),
+    };
+
+    for term in query.split_whitespace() {
+        let m = fuzzy_match(term, text)?;
+        total.score += m.score;
+        total.positions.extend(m.positions);
+    }
+
+    total.positions.sort_unstable();
+    total.positions.dedup();
+    Some(total)
+}
+
+/// Collapse matched positions into half-open `[start, end)` highlight ranges
+pub fn to_ranges(positions: &[usize]) -> Vec<[usize; 2]> {
+    let mut ranges: Vec<[usize; 2]> = Vec::new();
+
+    for &pos in positions {
+        match ranges.last_mut() {
+            Some(last) if last[1] == pos => last[1] = pos + 1,
+            _ => ranges.push([pos, pos + 1]),
+        }
+    }
+
+    ranges
+}
+
+fn is_subsequence(pattern: &[char], text: &[char]) -> bool {
+    let mut it = text.iter();
+    pattern.iter().all(|c| it.any(|t| t == c))
+}
+
+fn position_bonus(prev: Option<char>, current: char) -> i32 {
+    match prev {
+        None => BONUS_BOUNDARY_WHITE,
+        Some(p) if p.is_whitespace() => BONUS_BOUNDARY_WHITE,
+        Some(p) if "/-_.:=,;|".contains(p) => BONUS_BOUNDARY,
+        Some(p) if p.is_lowercase() && current.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_ascii_digit() && current.is_ascii_digit() => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match_abbreviation() {
+        let m = fuzzy_match("dclapi", "docker compose logs -f api").unwrap();
+        assert_eq!(m.positions, vec![0, 7, 15, 23, 24, 25]);
+        assert_eq!(
+            to_ranges(&m.positions),
+            vec![[0, 1], [7, 8], [15, 16], [23, 26]]
+        );
+
+        assert!(fuzzy_match("dclapix", "docker compose logs -f api").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_match_prefers_boundaries_and_runs() {
+        // 'c' of "compose" (word start) wins over the 'c' inside "docker"
+        let m = fuzzy_match("cl", "docker compose logs").unwrap();
+        assert_eq!(m.positions, vec![7, 15]);
+
+        let contiguous = fuzzy_match("logs", "docker logs api").unwrap();
+        let spread = fuzzy_match("logs", "ls -l /opt/git/src").unwrap();
+        assert!(contiguous.score > spread.score);
+    }
+
+    #[test]
+    fn test_match_query_smart_case_and_terms() {
+        assert!(fuzzy_match("Api", "docker logs api").is_none());
+        assert!(fuzzy_match("api", "docker logs API").is_some());
+
+        let m = match_query("logs api", "docker compose logs -f api").unwrap();
+        assert_eq!(to_ranges(&m.positions), vec![[15, 19], [23, 26]]);
+        assert!(match_query("logs nginx", "docker compose logs -f api").is_none());
+    }
+}
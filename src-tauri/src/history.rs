@@ -1,9 +1,20 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use uuid::Uuid;
 
 use crate::commands_dict;
+use crate::frecency;
+use crate::fuzzy;
+use crate::prediction;
+use crate::redact::Redactor;
+use crate::semantic_history;
+
+/// How many recent distinct commands the fuzzy matcher scans
+const FUZZY_CANDIDATE_LIMIT: usize = 5000;
+
+/// How many next-command predictions lead an empty-prefix suggestion list
+const PREDICTION_LIMIT: usize = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HistoryEntry {
@@ -13,6 +24,10 @@ pub struct HistoryEntry {
     pub ts: i64,
     pub exit_code: Option<i32>,
     pub duration_ms: Option<i32>,
+    pub cwd: Option<String>,
+    pub hostname: Option<String>,
+    pub session_id: Option<String>,
+    pub shell: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,6 +36,14 @@ pub struct SaveHistoryInput {
     pub cmd: String,
     pub exit_code: Option<i32>,
     pub duration_ms: Option<i32>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub shell: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,27 +51,163 @@ pub struct CommandSuggestion {
     pub cmd: String,
     pub frequency: i32,
     pub last_used: i64,
+    #[serde(default)]
+    pub frecency: f64,
+    /// Predicted from what usually follows the previous commands
+    #[serde(default)]
+    pub predicted: bool,
 }
 
-/// Search command history by prefix, ordered by frequency
-/// This function only returns history results (no dictionary)
-pub fn search_history(conn: &Connection, profile_id: &str, prefix: &str, limit: usize) -> Result<Vec<CommandSuggestion>> {
-    let query = format!("{}%", prefix);
+/// Ctrl+R-style search result with highlight ranges (character offsets, `[start, end)`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySearchResult {
+    pub cmd: String,
+    pub frequency: i32,
+    pub last_used: i64,
+    pub score: i32,
+    pub ranges: Vec<[usize; 2]>,
+}
+
+/// Full-text and fuzzy history search
+/// FTS5 finds substring matches across the whole history, the fuzzy matcher
+/// adds subsequence matches ("dclapi" → "docker compose logs -f api") over
+/// recent commands; everything is ranked by fuzzy score, then frequency
+pub fn search_history_fuzzy(
+    conn: &Connection,
+    profile_id: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<HistorySearchResult>> {
+    if query.trim().is_empty() {
+        let recent = search_history(conn, profile_id, "", None, limit)?;
+        return Ok(recent
+            .into_iter()
+            .map(|s| HistorySearchResult {
+                cmd: s.cmd,
+                frequency: s.frequency,
+                last_used: s.last_used,
+                score: 0,
+                ranges: Vec::new(),
+            })
+            .collect());
+    }
+
+    let mut candidates = fts_candidates(conn, profile_id, query)?;
+    let mut seen: HashSet<String> = candidates.iter().map(|c| c.cmd.clone()).collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT cmd, frequency, last_used
+         FROM history_stats
+         WHERE profile_id = ?1
+         ORDER BY frecency_key DESC
+         LIMIT ?2",
+    )?;
+    let recent = stmt
+        .query_map(params![profile_id, FUZZY_CANDIDATE_LIMIT as i64], row_to_suggestion)?
+        .collect::<Result<Vec<_>>>()?;
+    for suggestion in recent {
+        if seen.insert(suggestion.cmd.clone()) {
+            candidates.push(suggestion);
+        }
+    }
+
+    let mut results: Vec<HistorySearchResult> = candidates
+        .into_iter()
+        .filter_map(|c| {
+            let m = fuzzy::match_query(query, &c.cmd)?;
+            Some(HistorySearchResult {
+                ranges: fuzzy::to_ranges(&m.positions),
+                score: m.score,
+                cmd: c.cmd,
+                frequency: c.frequency,
+                last_used: c.last_used,
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.frequency.cmp(&a.frequency))
+            .then(b.last_used.cmp(&a.last_used))
+    });
+    results.truncate(limit);
+
+    Ok(results)
+}
+
+/// Commands containing every query term of 3+ characters (trigram index)
+fn fts_candidates(conn: &Connection, profile_id: &str, query: &str) -> Result<Vec<CommandSuggestion>> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|t| t.chars().count() >= 3)
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
 
     let mut stmt = conn.prepare(
         "SELECT cmd, COUNT(*) as frequency, MAX(ts) as last_used
          FROM history
-         WHERE profile_id = ?1 AND cmd LIKE ?2
-         GROUP BY cmd
-         ORDER BY frequency DESC, last_used DESC
-         LIMIT ?3"
+         WHERE profile_id = ?1
+           AND rowid IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?2)
+         GROUP BY cmd",
     )?;
 
-    let suggestions = stmt.query_map(params![profile_id, query, limit as i32], |row| {
+    let candidates = stmt
+        .query_map(params![profile_id, terms.join(" ")], row_to_suggestion)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(candidates)
+}
+
+fn row_to_suggestion(row: &rusqlite::Row) -> Result<CommandSuggestion> {
+    Ok(CommandSuggestion {
+        cmd: row.get(0)?,
+        frequency: row.get(1)?,
+        last_used: row.get(2)?,
+        frecency: 0.0,
+        predicted: false,
+    })
+}
+
+/// Search command history by prefix, ordered by frecency (time-decayed frequency)
+/// Commands previously run in `cwd` (if given) are ranked first
+/// This function only returns history results (no dictionary)
+pub fn search_history(
+    conn: &Connection,
+    profile_id: &str,
+    prefix: &str,
+    cwd: Option<&str>,
+    limit: usize,
+) -> Result<Vec<CommandSuggestion>> {
+    let query = format!("{}%", prefix);
+
+    let half_life = frecency::half_life_days(conn)?;
+    let now = chrono::Utc::now().timestamp();
+
+    let mut stmt = conn.prepare(
+        "SELECT s.cmd, s.frequency, s.last_used, s.frecency_key,
+                EXISTS (
+                    SELECT 1 FROM history h
+                    WHERE h.profile_id = s.profile_id AND h.cmd = s.cmd AND h.cwd = ?3
+                ) as used_in_dir
+         FROM history_stats s
+         WHERE s.profile_id = ?1 AND s.cmd LIKE ?2
+         ORDER BY used_in_dir DESC, s.frecency_key DESC, s.last_used DESC
+         LIMIT ?4"
+    )?;
+
+    let suggestions = stmt.query_map(params![profile_id, query, cwd, limit as i32], |row| {
+        let key: f64 = row.get(3)?;
         Ok(CommandSuggestion {
             cmd: row.get(0)?,
             frequency: row.get(1)?,
             last_used: row.get(2)?,
+            frecency: frecency::score_at(key, now, half_life),
+            predicted: false,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
@@ -56,34 +215,63 @@ pub fn search_history(conn: &Connection, profile_id: &str, prefix: &str, limit:
     Ok(suggestions)
 }
 
-/// Search command suggestions combining history and dictionary
-/// Returns history results first (by frequency), then dictionary suggestions
+/// Search command suggestions combining predictions, history and dictionary
+/// With an empty prefix, next-command predictions (for `session_id` if given) come first,
+/// then history results (by frecency), then dictionary suggestions
 /// Removes duplicates and limits to the specified number
-pub fn search_suggestions(conn: &Connection, profile_id: &str, prefix: &str, limit: usize) -> Result<Vec<CommandSuggestion>> {
-    // Get history suggestions
-    let history_results = search_history(conn, profile_id, prefix, limit)?;
-
-    // If we have enough history results, return them
-    if history_results.len() >= limit {
-        return Ok(history_results);
-    }
-
-    // Get dictionary suggestions
-    let dict_limit = limit - history_results.len();
-    let dict_results = commands_dict::get_dict_suggestions(prefix, dict_limit * 2);
-
+pub fn search_suggestions(
+    conn: &Connection,
+    profile_id: &str,
+    prefix: &str,
+    cwd: Option<&str>,
+    session_id: Option<&str>,
+    limit: usize,
+) -> Result<Vec<CommandSuggestion>> {
     // Track seen commands to avoid duplicates
     let mut seen = HashSet::new();
     let mut combined = Vec::new();
+    let now = chrono::Utc::now().timestamp();
+
+    // With nothing typed yet, predict what usually follows the previous commands
+    if prefix.trim().is_empty() {
+        let previous: Vec<String> = prediction::recent_commands(conn, profile_id, session_id, now, 2)?
+            .into_iter()
+            .map(|(cmd, _)| cmd)
+            .collect();
+        for p in prediction::predict(conn, profile_id, &previous, PREDICTION_LIMIT.min(limit))? {
+            seen.insert(p.cmd.clone());
+            combined.push(CommandSuggestion {
+                cmd: p.cmd,
+                frequency: p.count as i32,
+                last_used: p.last_used,
+                frecency: 0.0,
+                predicted: true,
+            });
+        }
+    }
+
+    // Get history suggestions
+    let history_results = search_history(conn, profile_id, prefix, cwd, limit)?;
 
-    // Add history results first (they have higher priority)
+    // Add history results next (they have higher priority than the dictionary)
     for hist in history_results {
-        seen.insert(hist.cmd.clone());
-        combined.push(hist);
+        if combined.len() >= limit {
+            return Ok(combined);
+        }
+        if seen.insert(hist.cmd.clone()) {
+            combined.push(hist);
+        }
+    }
+
+    if combined.len() >= limit {
+        return Ok(combined);
     }
 
+    // Get dictionary suggestions
+    let dict_limit = limit - combined.len();
+    let dict_results = commands_dict::get_dict_suggestions(prefix, dict_limit * 2);
+
     // Add dictionary suggestions (with default frequency and timestamp)
-    let now = chrono::Utc::now().timestamp();
     for dict_cmd in dict_results {
         if !seen.contains(&dict_cmd) && combined.len() < limit {
             seen.insert(dict_cmd.clone());
@@ -91,6 +279,8 @@ pub fn search_suggestions(conn: &Connection, profile_id: &str, prefix: &str, lim
                 cmd: dict_cmd,
                 frequency: 0, // Dictionary commands have 0 frequency
                 last_used: now,
+                frecency: 0.0,
+                predicted: false,
             });
         }
     }
@@ -98,23 +288,97 @@ pub fn search_suggestions(conn: &Connection, profile_id: &str, prefix: &str, lim
     Ok(combined)
 }
 
-/// Save a command to history
-pub fn save_history(conn: &Connection, input: SaveHistoryInput) -> Result<HistoryEntry> {
+/// Save a command to history after applying the privacy filters
+/// Returns None if the command must not be recorded (space-prefixed or ignored by a rule)
+pub fn save_history(conn: &Connection, input: SaveHistoryInput) -> Result<Option<HistoryEntry>> {
+    let Some(cmd) = Redactor::load(conn)?.filter(&input.cmd) else {
+        return Ok(None);
+    };
+    let cmd = cmd.trim().to_string();
+    if cmd.is_empty() {
+        return Ok(None);
+    }
+
     let now = chrono::Utc::now().timestamp();
     let id = Uuid::new_v4().to_string();
 
     let entry = HistoryEntry {
         id: id.clone(),
         profile_id: input.profile_id,
-        cmd: input.cmd,
+        cmd,
         ts: now,
         exit_code: input.exit_code,
         duration_ms: input.duration_ms,
+        cwd: input.cwd,
+        hostname: input.hostname,
+        session_id: input.session_id,
+        shell: input.shell,
     };
 
+    insert_entry(conn, &entry)?;
+
+    Ok(Some(entry))
+}
+
+/// Most recent history entries, newest first
+/// With `session_id`, only commands run in that session
+pub fn recent_entries(
+    conn: &Connection,
+    profile_id: &str,
+    session_id: Option<&str>,
+    limit: usize,
+) -> Result<Vec<HistoryEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, profile_id, cmd, ts, exit_code, duration_ms, cwd, hostname, session_id, shell
+         FROM history
+         WHERE profile_id = ?1 AND (?2 IS NULL OR session_id = ?2)
+         ORDER BY ts DESC, rowid DESC
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![profile_id, session_id, limit as i64], row_to_entry)?;
+    rows.collect()
+}
+
+/// A single history entry by id
+pub fn get_entry(conn: &Connection, id: &str) -> Result<Option<HistoryEntry>> {
+    conn.query_row(
+        "SELECT id, profile_id, cmd, ts, exit_code, duration_ms, cwd, hostname, session_id, shell
+         FROM history WHERE id = ?1",
+        params![id],
+        row_to_entry,
+    )
+    .optional()
+}
+
+fn row_to_entry(row: &rusqlite::Row) -> Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        profile_id: row.get(1)?,
+        cmd: row.get(2)?,
+        ts: row.get(3)?,
+        exit_code: row.get(4)?,
+        duration_ms: row.get(5)?,
+        cwd: row.get(6)?,
+        hostname: row.get(7)?,
+        session_id: row.get(8)?,
+        shell: row.get(9)?,
+    })
+}
+
+/// Insert a history row (with its own timestamp) and update the aggregates
+pub fn insert_entry(conn: &Connection, entry: &HistoryEntry) -> Result<()> {
+    // Learn the sequence before the command itself becomes the latest entry
+    prediction::record_transition(
+        conn,
+        &entry.profile_id,
+        entry.session_id.as_deref(),
+        &entry.cmd,
+        entry.ts,
+    )?;
+
     conn.execute(
-        "INSERT INTO history (id, profile_id, cmd, ts, exit_code, duration_ms)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        "INSERT INTO history (id, profile_id, cmd, ts, exit_code, duration_ms, cwd, hostname, session_id, shell)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
         params![
             &entry.id,
             &entry.profile_id,
@@ -122,10 +386,16 @@ pub fn save_history(conn: &Connection, input: SaveHistoryInput) -> Result<Histor
             entry.ts,
             entry.exit_code,
             entry.duration_ms,
+            &entry.cwd,
+            &entry.hostname,
+            &entry.session_id,
+            &entry.shell,
         ],
     )?;
 
-    Ok(entry)
+    frecency::record_use(conn, &entry.profile_id, &entry.cmd, entry.ts)?;
+
+    Ok(())
 }
 
 /// Clear all history for a specific profile
@@ -134,6 +404,19 @@ pub fn clear_history(conn: &Connection, profile_id: &str) -> Result<usize> {
         "DELETE FROM history WHERE profile_id = ?1",
         params![profile_id],
     )?;
+    conn.execute(
+        "DELETE FROM history_stats WHERE profile_id = ?1",
+        params![profile_id],
+    )?;
+    conn.execute(
+        "DELETE FROM history_transitions WHERE profile_id = ?1",
+        params![profile_id],
+    )?;
+    conn.execute(
+        "DELETE FROM history_archive WHERE profile_id = ?1",
+        params![profile_id],
+    )?;
+    semantic_history::remove_orphans(conn)?;
 
     Ok(count)
 }
@@ -141,6 +424,10 @@ pub fn clear_history(conn: &Connection, profile_id: &str) -> Result<usize> {
 /// Clear all history (all profiles)
 pub fn clear_all_history(conn: &Connection) -> Result<usize> {
     let count = conn.execute("DELETE FROM history", [])?;
+    conn.execute("DELETE FROM history_stats", [])?;
+    conn.execute("DELETE FROM history_transitions", [])?;
+    conn.execute("DELETE FROM history_archive", [])?;
+    conn.execute("DELETE FROM history_embeddings", [])?;
     Ok(count)
 }
 
@@ -159,9 +446,13 @@ mod tests {
             cmd: "ls -la".to_string(),
             exit_code: Some(0),
             duration_ms: Some(100),
+            cwd: None,
+            hostname: None,
+            session_id: None,
+            shell: None,
         };
 
-        let entry = save_history(&conn, input).unwrap();
+        let entry = save_history(&conn, input).unwrap().unwrap();
         assert_eq!(entry.cmd, "ls -la");
         assert_eq!(entry.exit_code, Some(0));
 
@@ -201,6 +492,10 @@ mod tests {
             cmd: "ls -la".to_string(),
             exit_code: Some(0),
             duration_ms: Some(100),
+            cwd: None,
+            hostname: None,
+            session_id: None,
+            shell: None,
         }).unwrap();
 
         save_history(&conn, SaveHistoryInput {
@@ -208,6 +503,10 @@ mod tests {
             cmd: "ls -lh".to_string(),
             exit_code: Some(0),
             duration_ms: Some(100),
+            cwd: None,
+            hostname: None,
+            session_id: None,
+            shell: None,
         }).unwrap();
 
         // Save same command twice to test frequency ordering
@@ -216,6 +515,10 @@ mod tests {
             cmd: "ls -la".to_string(),
             exit_code: Some(0),
             duration_ms: Some(100),
+            cwd: None,
+            hostname: None,
+            session_id: None,
+            shell: None,
         }).unwrap();
 
         save_history(&conn, SaveHistoryInput {
@@ -223,10 +526,14 @@ mod tests {
             cmd: "cd /home".to_string(),
             exit_code: Some(0),
             duration_ms: Some(100),
+            cwd: None,
+            hostname: None,
+            session_id: None,
+            shell: None,
         }).unwrap();
 
         // Search for "ls" commands
-        let results = search_history(&conn, profile_id, "ls", 10).unwrap();
+        let results = search_history(&conn, profile_id, "ls", None, 10).unwrap();
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].cmd, "ls -la"); // Most frequent
         assert_eq!(results[0].frequency, 2);
@@ -234,8 +541,142 @@ mod tests {
         assert_eq!(results[1].frequency, 1);
 
         // Search for "cd" commands
-        let results = search_history(&conn, profile_id, "cd", 10).unwrap();
+        let results = search_history(&conn, profile_id, "cd", None, 10).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].cmd, "cd /home");
     }
+
+    #[test]
+    fn test_search_history_ranks_current_directory_first() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let profile_id = "test-profile";
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES (?1, 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [profile_id],
+        ).unwrap();
+
+        let save = |cmd: &str, cwd: &str| {
+            save_history(&conn, SaveHistoryInput {
+                profile_id: profile_id.to_string(),
+                cmd: cmd.to_string(),
+                exit_code: Some(0),
+                duration_ms: Some(100),
+                cwd: Some(cwd.to_string()),
+                hostname: Some("web1".to_string()),
+                session_id: Some("session-1".to_string()),
+                shell: Some("bash".to_string()),
+            }).unwrap();
+        };
+
+        save("kubectl get pods", "/srv/ops");
+        save("kubectl get pods", "/srv/ops");
+        save("make test", "/home/dev/project");
+
+        let results = search_history(&conn, profile_id, "", Some("/home/dev/project"), 10).unwrap();
+        assert_eq!(results[0].cmd, "make test");
+
+        let results = search_history(&conn, profile_id, "", Some("/srv/ops"), 10).unwrap();
+        assert_eq!(results[0].cmd, "kubectl get pods");
+
+        // Without a directory, plain frequency wins
+        let results = search_history(&conn, profile_id, "", None, 10).unwrap();
+        assert_eq!(results[0].cmd, "kubectl get pods");
+    }
+
+    #[test]
+    fn test_search_history_fuzzy() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let profile_id = "test-profile";
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES (?1, 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [profile_id],
+        ).unwrap();
+
+        for cmd in ["docker compose logs -f api", "docker ps", "cat /var/log/syslog"] {
+            save_history(&conn, SaveHistoryInput {
+                profile_id: profile_id.to_string(),
+                cmd: cmd.to_string(),
+                exit_code: Some(0),
+                duration_ms: None,
+                cwd: None,
+                hostname: None,
+                session_id: None,
+                shell: None,
+            }).unwrap();
+        }
+
+        let results = search_history_fuzzy(&conn, profile_id, "dclapi", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].cmd, "docker compose logs -f api");
+        assert_eq!(results[0].ranges, vec![[0, 1], [7, 8], [15, 16], [23, 26]]);
+
+        // Substring terms go through the FTS index
+        let results = search_history_fuzzy(&conn, profile_id, "syslog", 10).unwrap();
+        assert_eq!(results[0].cmd, "cat /var/log/syslog");
+
+        // Deleted rows disappear from the index
+        clear_history(&conn, profile_id).unwrap();
+        let fts_rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM history_fts WHERE history_fts MATCH 'syslog'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(fts_rows, 0);
+    }
+
+    #[test]
+    fn test_search_history_prefers_recent_over_old_frequent() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+
+        let profile_id = "test-profile";
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES (?1, 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [profile_id],
+        ).unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        let insert = |cmd: &str, ts: i64| {
+            insert_entry(&conn, &HistoryEntry {
+                id: Uuid::new_v4().to_string(),
+                profile_id: profile_id.to_string(),
+                cmd: cmd.to_string(),
+                ts,
+                exit_code: Some(0),
+                duration_ms: None,
+                cwd: None,
+                hostname: None,
+                session_id: None,
+                shell: None,
+            }).unwrap();
+        };
+
+        for _ in 0..50 {
+            insert("service old-app restart", now - 365 * 86_400);
+        }
+        for _ in 0..5 {
+            insert("service new-app restart", now);
+        }
+
+        let results = search_history(&conn, profile_id, "service", None, 10).unwrap();
+        assert_eq!(results[0].cmd, "service new-app restart");
+        assert_eq!(results[0].frequency, 5);
+        assert!((results[0].frecency - 5.0).abs() < 0.01);
+        assert_eq!(results[1].frequency, 50);
+
+        // Rebuilding from raw history gives the same ranking
+        frecency::rebuild(&conn).unwrap();
+        let rebuilt = search_history(&conn, profile_id, "service", None, 10).unwrap();
+        assert_eq!(rebuilt[0].cmd, "service new-app restart");
+
+        // A very long half-life turns frecency back into plain frequency
+        frecency::set_half_life_days(&conn, 100_000.0).unwrap();
+        let results = search_history(&conn, profile_id, "service", None, 10).unwrap();
+        assert_eq!(results[0].cmd, "service old-app restart");
+    }
 }
@@ -1,15 +1,36 @@
+mod agent;
 mod ai;
+mod ai_provider;
+mod analytics;
+mod command_extract;
 mod commands_dict;
+mod completion;
+mod conversation;
 mod db;
+mod embedding;
+mod explain;
+mod fleet;
+mod frecency;
+mod fuzzy;
 mod history;
 mod logger;
 mod macros;
+mod prediction;
 mod profile;
+mod redact;
+mod remote_complete;
+mod retention;
+mod risk;
+mod runbooks;
+mod semantic_history;
 mod settings;
+mod shell_history;
+mod shell_integration;
 mod ssh;
+mod terminal_context;
 
 use std::sync::Mutex;
-use tauri::{Manager, State};
+use tauri::{Emitter, Manager, State};
 
 pub struct AppState {
     db: Mutex<Option<rusqlite::Connection>>,
@@ -24,6 +45,7 @@ pub fn run() {
             if let Some(app_data) = app.path().app_data_dir().ok() {
                 logger::init_logger(app_data.clone());
                 crate::log!("[Setup] AIT application starting...");
+                commands_dict::load_user_specs(&app_data.join("completions"));
             }
 
             // Initialize database
@@ -31,6 +53,7 @@ pub fn run() {
             app.manage(AppState {
                 db: Mutex::new(Some(conn)),
             });
+            retention::start_scheduler(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -39,6 +62,8 @@ pub fn run() {
             ssh::ssh_resize,
             ssh::ssh_close,
             ssh::ssh_exec,
+            ssh::ssh_broadcast,
+            ssh_broadcast_group,
             profile_create,
             profile_list,
             profile_get,
@@ -49,16 +74,66 @@ pub fn run() {
             history_save,
             history_search,
             history_suggestions,
+            history_fuzzy_search,
+            history_semantic_index,
+            history_semantic_search,
+            history_get_frecency_half_life,
+            history_set_frecency_half_life,
+            history_import_remote,
+            history_export,
+            history_import_file,
+            history_get_redaction_rules,
+            history_set_redaction_rules,
+            history_scrub,
+            history_get_retention,
+            history_set_retention,
+            history_compact,
             history_clear,
             history_clear_all,
+            analytics_top_commands,
+            analytics_failure_rates,
+            analytics_slowest_commands,
+            analytics_heatmap,
+            completion_complete,
+            completion_reload_specs,
+            completion_remote,
             settings_get,
             settings_set,
             settings_get_all,
             ai_ask,
+            ai_cancel,
+            ai_context_preview,
+            ai_explain_error,
+            ai_config_get,
+            ai_config_set,
+            ai_test_connection,
+            ai_conversation_create,
+            ai_conversation_list,
+            ai_conversation_messages,
+            ai_conversation_rename,
+            ai_conversation_delete,
+            ai_agent_start,
+            ai_agent_next,
+            ai_agent_decide,
+            ai_agent_stop,
+            ai_agent_runs,
+            ai_agent_get,
+            ai_agent_delete,
+            runbooks_status,
+            runbooks_set_dir,
+            runbooks_index,
+            runbooks_search,
             ai_extract_commands,
+            risk_assess,
             macros_get,
             macros_set,
             macros_delete,
+            fleet_run,
+            fleet_list_runs,
+            fleet_get_run,
+            fleet_diff,
+            fleet_compare,
+            fleet_delete_run,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -171,11 +246,32 @@ fn profile_set_password(state: State<AppState>, profile_id: String, password: St
     }
 }
 
+#[tauri::command]
+fn ssh_broadcast_group(
+    state: State<AppState>,
+    profile_group: String,
+    data: String,
+    confirmed: Option<bool>,
+) -> Result<Vec<ssh::BroadcastResult>, String> {
+    let profile_ids: Vec<String> = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        profile::list_profiles_in_group(conn, &profile_group)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|p| p.id)
+            .collect()
+    };
+
+    let ids = ssh::session_ids_for_profiles(&profile_ids);
+    Ok(ssh::broadcast(&ids, &data, confirmed.unwrap_or(false)))
+}
+
 #[tauri::command]
 fn history_save(
     state: State<AppState>,
     input: history::SaveHistoryInput,
-) -> Result<history::HistoryEntry, String> {
+) -> Result<Option<history::HistoryEntry>, String> {
     let db_guard = state.db.lock().unwrap();
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     history::save_history(conn, input).map_err(|e| e.to_string())
@@ -187,11 +283,15 @@ fn history_search(
     profile_id: String,
     prefix: String,
     limit: Option<usize>,
+    cwd: Option<String>,
+    session_id: Option<String>,
 ) -> Result<Vec<history::CommandSuggestion>, String> {
+    // Fall back to the directory reported by the session's shell integration
+    let cwd = cwd.or_else(|| session_id.as_deref().and_then(ssh::session_cwd));
     let db_guard = state.db.lock().unwrap();
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     let limit = limit.unwrap_or(10);
-    history::search_history(conn, &profile_id, &prefix, limit).map_err(|e| e.to_string())
+    history::search_history(conn, &profile_id, &prefix, cwd.as_deref(), limit).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -200,11 +300,308 @@ fn history_suggestions(
     profile_id: String,
     prefix: String,
     limit: Option<usize>,
+    cwd: Option<String>,
+    session_id: Option<String>,
 ) -> Result<Vec<history::CommandSuggestion>, String> {
+    // Fall back to the directory reported by the session's shell integration
+    let cwd = cwd.or_else(|| session_id.as_deref().and_then(ssh::session_cwd));
     let db_guard = state.db.lock().unwrap();
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     let limit = limit.unwrap_or(10);
-    history::search_suggestions(conn, &profile_id, &prefix, limit).map_err(|e| e.to_string())
+    history::search_suggestions(conn, &profile_id, &prefix, cwd.as_deref(), session_id.as_deref(), limit).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn history_fuzzy_search(
+    state: State<AppState>,
+    profile_id: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<history::HistorySearchResult>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let limit = limit.unwrap_or(20);
+    history::search_history_fuzzy(conn, &profile_id, &query, limit).map_err(|e| e.to_string())
+}
+
+/// Embed history commands that have no vector for the current embedding model yet
+/// Stops after `limit` commands; call again while `remaining` > 0
+#[tauri::command]
+async fn history_semantic_index(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<semantic_history::IndexProgress, String> {
+    let config = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        let config = ai::AIConfig::load(conn).map_err(|e| e.to_string())?;
+        semantic_history::prune(conn, &config.embedding_model).map_err(|e| e.to_string())?;
+        config
+    };
+    let provider = ai_provider::from_config(&config);
+    let limit = limit.unwrap_or(1000);
+
+    let mut embedded = 0;
+    while embedded < limit {
+        let batch = embed_pending_history(&state, provider.as_ref(), &config.embedding_model, limit - embedded).await?;
+        if batch == 0 {
+            break;
+        }
+        embedded += batch;
+    }
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let remaining = semantic_history::pending_count(conn, &config.embedding_model).map_err(|e| e.to_string())?;
+    println!("[History] Embedded {} command(s), {} remaining", embedded, remaining);
+    Ok(semantic_history::IndexProgress { embedded, remaining })
+}
+
+/// Embed and store one batch of history commands without vectors; returns how many
+async fn embed_pending_history(
+    state: &State<'_, AppState>,
+    provider: &dyn ai_provider::AiProvider,
+    model: &str,
+    limit: usize,
+) -> Result<usize, String> {
+    let cmds = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        semantic_history::pending(conn, model, limit.min(semantic_history::EMBED_BATCH)).map_err(|e| e.to_string())?
+    };
+    if cmds.is_empty() {
+        return Ok(0);
+    }
+    let embeddings = provider.embed(model, &cmds).await.map_err(|e| e.to_string())?;
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    semantic_history::store(conn, model, &cmds, &embeddings).map_err(|e| e.to_string())
+}
+
+/// Natural-language history search ("that command I used to clean docker images last month")
+/// Commands run since the last index are embedded first (one batch)
+#[tauri::command]
+async fn history_semantic_search(
+    state: State<'_, AppState>,
+    profile_id: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<semantic_history::SemanticMatch>, String> {
+    let config = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        ai::AIConfig::load(conn).map_err(|e| e.to_string())?
+    };
+    let provider = ai_provider::from_config(&config);
+    let model = &config.embedding_model;
+    if let Err(e) = embed_pending_history(&state, provider.as_ref(), model, semantic_history::EMBED_BATCH).await {
+        println!("[History] Failed to embed new commands: {}", e);
+    }
+
+    let (text, since) = semantic_history::parse_time(&query, chrono::Utc::now().timestamp());
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    let embedding = provider
+        .embed(model, &[text])
+        .await
+        .map_err(|e| e.to_string())?
+        .remove(0);
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    semantic_history::search(conn, &profile_id, &embedding, model, since, limit.unwrap_or(20)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn history_get_frecency_half_life(state: State<AppState>) -> Result<f64, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    frecency::half_life_days(conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn history_set_frecency_half_life(state: State<AppState>, days: f64) -> Result<(), String> {
+    if !days.is_finite() || days <= 0.0 {
+        return Err(format!("Half-life must be a positive number of days, got {}", days));
+    }
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    frecency::set_half_life_days(conn, days).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn analytics_top_commands(
+    state: State<AppState>,
+    query: analytics::AnalyticsQuery,
+) -> Result<Vec<analytics::CommandUsage>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    analytics::top_commands(conn, &query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn analytics_failure_rates(
+    state: State<AppState>,
+    query: analytics::AnalyticsQuery,
+    min_runs: Option<i64>,
+) -> Result<Vec<analytics::FailureRate>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    analytics::failure_rates(conn, &query, min_runs.unwrap_or(3)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn analytics_slowest_commands(
+    state: State<AppState>,
+    query: analytics::AnalyticsQuery,
+) -> Result<Vec<analytics::DurationStats>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    analytics::slowest_commands(conn, &query).map_err(|e| e.to_string())
+}
+
+/// Activity by weekday/hour; `tz_offset_minutes` is the local offset from UTC (e.g. 540 for KST)
+#[tauri::command]
+fn analytics_heatmap(
+    state: State<AppState>,
+    query: analytics::AnalyticsQuery,
+    tz_offset_minutes: Option<i32>,
+) -> Result<Vec<analytics::HeatmapCell>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    analytics::activity_heatmap(conn, &query, tz_offset_minutes.unwrap_or(0)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn completion_complete(line: String, limit: Option<usize>) -> completion::CompletionResult {
+    completion::complete(&line, limit.unwrap_or(20))
+}
+
+/// Complete a command line with values from the session's host (paths, branches, units...)
+#[tauri::command]
+async fn completion_remote(
+    session_id: String,
+    line: String,
+    limit: Option<usize>,
+) -> Result<completion::CompletionResult, String> {
+    let limit = limit.unwrap_or(20);
+    tokio::task::spawn_blocking(move || remote_complete::complete(&session_id, &line, limit))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Reload user command specs from <app data>/completions
+#[tauri::command]
+fn completion_reload_specs(app: tauri::AppHandle) -> Result<usize, String> {
+    let app_data = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(commands_dict::load_user_specs(&app_data.join("completions")))
+}
+
+/// Import the shell history files of an open session's server into a profile
+#[tauri::command]
+async fn history_import_remote(
+    state: State<'_, AppState>,
+    session_id: String,
+    profile_id: String,
+) -> Result<shell_history::ImportSummary, String> {
+    let output = tokio::task::spawn_blocking(move || {
+        ssh::ssh_exec(session_id, shell_history::fetch_script())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    shell_history::import_fetched(conn, &profile_id, &output).map_err(|e| e.to_string())
+}
+
+/// Export history of one profile (or all profiles) to a file; returns the number of commands
+#[tauri::command]
+fn history_export(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    format: shell_history::ExportFormat,
+    path: String,
+) -> Result<usize, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let records = shell_history::load_records(conn, profile_id.as_deref()).map_err(|e| e.to_string())?;
+    std::fs::write(&path, shell_history::export_records(format, &records)).map_err(|e| e.to_string())?;
+    crate::log!("[HistoryExport] Exported {} commands as {:?} to {}", records.len(), format, path);
+    Ok(records.len())
+}
+
+/// Import a bash/zsh/JSON lines/CSV history file into a profile
+/// The format is guessed from the file name when not given
+#[tauri::command]
+fn history_import_file(
+    state: State<AppState>,
+    profile_id: String,
+    path: String,
+    format: Option<shell_history::ExportFormat>,
+) -> Result<shell_history::ImportSummary, String> {
+    let format = format
+        .or_else(|| shell_history::ExportFormat::from_path(&path))
+        .ok_or("Unknown history file format")?;
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let mtime = std::fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_else(|| chrono::Utc::now().timestamp());
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    shell_history::import_file_content(conn, &profile_id, format, &content, mtime)
+}
+
+#[tauri::command]
+fn history_get_redaction_rules(state: State<AppState>) -> Result<Vec<redact::RedactionRule>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    redact::get_rules(conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn history_set_redaction_rules(
+    state: State<AppState>,
+    rules: Vec<redact::RedactionRule>,
+) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    redact::set_rules(conn, &rules)
+}
+
+/// Apply the redaction rules to already stored history
+#[tauri::command]
+fn history_scrub(state: State<AppState>) -> Result<redact::ScrubSummary, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    redact::scrub_history(conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn history_get_retention(state: State<AppState>) -> Result<retention::RetentionPolicy, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    retention::get_policy(conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn history_set_retention(state: State<AppState>, policy: retention::RetentionPolicy) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    retention::set_policy(conn, &policy).map_err(|e| e.to_string())
+}
+
+/// Apply the retention policy now and VACUUM the database
+#[tauri::command]
+fn history_compact(state: State<AppState>) -> Result<retention::CompactionReport, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    retention::run(conn, chrono::Utc::now().timestamp(), true).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -221,8 +618,18 @@ fn history_clear_all(state: State<AppState>) -> Result<usize, String> {
     history::clear_all_history(conn).map_err(|e| e.to_string())
 }
 
+/// Settings the generic commands can't read or write (the AI API key fallback
+/// is only handled by ai_config_get/ai_config_set)
+fn check_setting_key(key: &str) -> Result<(), String> {
+    if key == ai::API_KEY_SETTING {
+        return Err(format!("Setting {} is not accessible", key));
+    }
+    Ok(())
+}
+
 #[tauri::command]
 fn settings_get(state: State<AppState>, key: String) -> Result<Option<String>, String> {
+    check_setting_key(&key)?;
     let db_guard = state.db.lock().unwrap();
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     settings::get_setting(conn, &key).map_err(|e| e.to_string())
@@ -230,6 +637,7 @@ fn settings_get(state: State<AppState>, key: String) -> Result<Option<String>, S
 
 #[tauri::command]
 fn settings_set(state: State<AppState>, key: String, value: String) -> Result<(), String> {
+    check_setting_key(&key)?;
     let db_guard = state.db.lock().unwrap();
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     settings::set_setting(conn, &key, &value).map_err(|e| e.to_string())
@@ -239,37 +647,695 @@ fn settings_set(state: State<AppState>, key: String, value: String) -> Result<()
 fn settings_get_all(state: State<AppState>) -> Result<Vec<settings::Setting>, String> {
     let db_guard = state.db.lock().unwrap();
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
-    settings::get_all_settings(conn).map_err(|e| e.to_string())
+    let mut all = settings::get_all_settings(conn).map_err(|e| e.to_string())?;
+    all.retain(|s| check_setting_key(&s.key).is_ok());
+    Ok(all)
 }
 
 // ============================================================================
 // AI Commands
 // ============================================================================
 
+/// Ask the configured AI provider; tokens are streamed as `ai:token` events
+/// ({ request_id, token }) and the full response is returned when generation
+/// finishes or is cancelled
 #[tauri::command]
 async fn ai_ask(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
     prompt: String,
     context: Option<String>,
-    server_url: Option<String>,
-    model: Option<String>,
+    request_id: Option<String>,
+    conversation_id: Option<String>,
+    session_id: Option<String>,
 ) -> Result<ai::AIResponse, String> {
-    let mut config = ai::AIConfig::default();
+    let terminal = session_id.as_deref().and_then(|id| Some((id, terminal_context::capture(id)?)));
+    let (config, history, context, runbooks) = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        let config = ai::AIConfig::load(conn).map_err(|e| e.to_string())?;
+        let context = with_terminal_context(conn, context, terminal, config.context_size)?;
+        let history = match conversation_id.as_deref() {
+            Some(id) => {
+                conversation::get(conn, id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("Conversation not found: {}", id))?;
+                conversation::history_turns(&conversation::messages(conn, id).map_err(|e| e.to_string())?)
+            }
+            None => Vec::new(),
+        };
+        let runbooks = runbooks::has_index(conn, &config.embedding_model).map_err(|e| e.to_string())?;
+        (config, history, context, runbooks)
+    };
+    let provider = ai_provider::from_config(&config);
+
+    // 런북 인덱스가 있으면 질문과 가까운 구절을 근거로 덧붙인다 (실패해도 질문은 계속)
+    let (context, sources) = match runbooks {
+        true => match retrieve_runbooks(&state, provider.as_ref(), &config.embedding_model, &prompt).await {
+            Ok((section, sources)) if !sources.is_empty() => {
+                let context = match context.filter(|c| !c.trim().is_empty()) {
+                    Some(context) => format!("{}\n\n{}", context.trim(), section),
+                    None => section,
+                };
+                (Some(context), sources)
+            }
+            Ok(_) => (context, Vec::new()),
+            Err(e) => {
+                println!("[Runbooks] Retrieval failed, asking without runbooks: {}", e);
+                (context, Vec::new())
+            }
+        },
+        false => (context, Vec::new()),
+    };
+
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let mut on_token = |token: &str| {
+        let _ = window.emit_to(
+            window.label(),
+            "ai:token",
+            serde_json::json!({
+                "request_id": request_id,
+                "token": token,
+            }),
+        );
+    };
+
+    let mut response = ai::ask(
+        provider.as_ref(),
+        config.language,
+        &prompt,
+        context.as_deref(),
+        &history,
+        &request_id,
+        &mut on_token,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    response.sources = sources;
+
+    // 대화에 기록 (취소되어 아무것도 받지 못한 경우는 제외)
+    if let Some(id) = conversation_id.as_deref().filter(|_| !response.response.is_empty()) {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        conversation::append_exchange(
+            conn,
+            id,
+            &prompt,
+            context.as_deref(),
+            &response.response,
+            chrono::Utc::now().timestamp(),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(response)
+}
+
+/// Runbook passages closest to `query`, rendered for the prompt
+async fn retrieve_runbooks(
+    state: &State<'_, AppState>,
+    provider: &dyn ai_provider::AiProvider,
+    model: &str,
+    query: &str,
+) -> Result<(String, Vec<runbooks::RunbookSource>), String> {
+    let embedding = provider
+        .embed(model, &[query.to_string()])
+        .await
+        .map_err(|e| e.to_string())?
+        .remove(0);
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let hits = runbooks::search(conn, &embedding, model, runbooks::SEARCH_LIMIT, runbooks::MIN_SCORE)
+        .map_err(|e| e.to_string())?;
+    Ok(runbooks::context_section(&hits, runbooks::CONTEXT_CHARS))
+}
+
+/// Append a captured session's terminal context (scrollback, cwd, OS, recent commands) to the caller's context
+fn with_terminal_context(
+    conn: &rusqlite::Connection,
+    context: Option<String>,
+    terminal: Option<(&str, terminal_context::TerminalSnapshot)>,
+    context_size: Option<u32>,
+) -> Result<Option<String>, String> {
+    let Some((session_id, snapshot)) = terminal else {
+        return Ok(context);
+    };
+    let budget = terminal_context::budget_for(context_size);
+    let terminal = Some(terminal_context::for_session(conn, session_id, &snapshot, budget).map_err(|e| e.to_string())?)
+        .filter(|t| !t.is_empty());
+    Ok(match (context.filter(|c| !c.trim().is_empty()), terminal) {
+        (Some(context), Some(terminal)) => Some(format!("{}\n\n{}", context.trim(), terminal)),
+        (context, terminal) => context.or(terminal),
+    })
+}
+
+/// The context `ai_ask` would send for a session, for review before asking
+#[tauri::command]
+fn ai_context_preview(state: State<AppState>, session_id: String) -> Result<String, String> {
+    let snapshot = terminal_context::capture(&session_id).ok_or_else(|| format!("session {} not found", session_id))?;
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let config = ai::AIConfig::load(conn).map_err(|e| e.to_string())?;
+    Ok(with_terminal_context(conn, None, Some((&session_id, snapshot)), config.context_size)?.unwrap_or_default())
+}
+
+/// Ask the AI why a recorded command failed, with its captured output and environment
+/// Tokens are streamed as `ai:token` events like `ai_ask`
+#[tauri::command]
+async fn ai_explain_error(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    history_id: String,
+    request_id: Option<String>,
+) -> Result<explain::ErrorExplanation, String> {
+    let entry = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        history::get_entry(conn, &history_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("History entry not found: {}", history_id))?
+    };
+    let exit_code = entry.exit_code.filter(|code| *code != 0).ok_or("이 명령은 실패하지 않았습니다")?;
+
+    // Session state first, then the database (see terminal_context::capture)
+    let session_id = entry.session_id.clone().unwrap_or_default();
+    let output = ssh::command_output(&session_id, &history_id);
+    let os_info = terminal_context::capture(&session_id).and_then(|s| s.os_info);
+
+    let (config, request, output_captured) = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        let config = ai::AIConfig::load(conn).map_err(|e| e.to_string())?;
+        let redactor = redact::Redactor::load(conn).map_err(|e| e.to_string())?;
+        let failed = explain::FailedCommand {
+            cmd: entry.cmd.clone(),
+            exit_code,
+            cwd: entry.cwd,
+            shell: entry.shell,
+            hostname: entry.hostname,
+            os_info,
+            output,
+        };
+        let budget = terminal_context::budget_for(config.context_size);
+        let request = explain::build_request(&failed, config.language, &redactor, budget);
+        (config, request, failed.output.is_some())
+    };
+    let provider = ai_provider::from_config(&config);
+
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let mut on_token = |token: &str| {
+        let _ = window.emit_to(
+            window.label(),
+            "ai:token",
+            serde_json::json!({
+                "request_id": request_id,
+                "token": token,
+            }),
+        );
+    };
+    let response = ai::generate(provider.as_ref(), &request, &request_id, &mut on_token)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(explain::ErrorExplanation {
+        history_id,
+        cmd: entry.cmd,
+        exit_code,
+        output_captured,
+        diagnosis: explain::parse_response(&response.response),
+        model: response.model,
+        request_id: response.request_id,
+        cancelled: response.cancelled,
+    })
+}
+
+/// Start a conversation for the session's profile (no session: a local conversation)
+#[tauri::command]
+fn ai_conversation_create(
+    state: State<AppState>,
+    session_id: Option<String>,
+    title: Option<String>,
+) -> Result<conversation::Conversation, String> {
+    // Look up the session before locking the database (see terminal_context::capture)
+    let profile_id = session_id.as_deref().and_then(ssh::session_profile);
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    conversation::create(
+        conn,
+        profile_id.as_deref(),
+        session_id.as_deref(),
+        title.as_deref().unwrap_or(""),
+        chrono::Utc::now().timestamp(),
+    )
+    .map_err(|e| e.to_string())
+}
 
-    if let Some(url) = server_url {
-        config.server_url = url;
+/// Conversations of a profile, given directly or through an open session
+#[tauri::command]
+fn ai_conversation_list(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    session_id: Option<String>,
+) -> Result<Vec<conversation::Conversation>, String> {
+    let profile_id = profile_id.or_else(|| session_id.as_deref().and_then(ssh::session_profile));
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    conversation::list(conn, profile_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn ai_conversation_messages(
+    state: State<AppState>,
+    conversation_id: String,
+) -> Result<Vec<conversation::ConversationMessage>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    conversation::messages(conn, &conversation_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn ai_conversation_rename(state: State<AppState>, conversation_id: String, title: String) -> Result<bool, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    conversation::rename(conn, &conversation_id, &title).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn ai_conversation_delete(state: State<AppState>, conversation_id: String) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    conversation::delete(conn, &conversation_id).map_err(|e| e.to_string())
+}
+
+/// Start an agent run on a session; steps are then requested with `ai_agent_next`
+#[tauri::command]
+fn ai_agent_start(
+    state: State<AppState>,
+    session_id: String,
+    goal: String,
+    max_steps: Option<u32>,
+    max_risk: Option<risk::Severity>,
+) -> Result<agent::AgentRun, String> {
+    if goal.trim().is_empty() {
+        return Err("목표를 입력하세요".into());
+    }
+    // Session state first, then the database (see terminal_context::capture)
+    let snapshot = terminal_context::capture(&session_id).ok_or_else(|| format!("session {} not found", session_id))?;
+    let (max_steps, max_risk) = agent::clamp_limits(max_steps, max_risk);
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    agent::create_run(
+        conn,
+        snapshot.profile_id.as_deref(),
+        &session_id,
+        &goal,
+        snapshot.cwd.as_deref(),
+        snapshot.os_info.as_deref(),
+        max_steps,
+        max_risk,
+        chrono::Utc::now().timestamp(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Ask the model for the next step of a run (streamed as `ai:token` events)
+/// The step is stored as proposed, or blocked if it is over the run's risk limit
+#[tauri::command]
+async fn ai_agent_next(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    run_id: String,
+    request_id: Option<String>,
+) -> Result<agent::AgentTurn, String> {
+    let (run, config, request) = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        let run = agent::get_run(conn, &run_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Agent run not found: {}", run_id))?;
+        if run.status != agent::RunStatus::Running {
+            return Err("이미 끝난 작업입니다".into());
+        }
+        let steps = agent::steps(conn, &run_id).map_err(|e| e.to_string())?;
+        if steps
+            .iter()
+            .any(|s| matches!(s.status, agent::StepStatus::Proposed | agent::StepStatus::Running))
+        {
+            return Err("승인을 기다리는 단계가 있습니다".into());
+        }
+        let config = ai::AIConfig::load(conn).map_err(|e| e.to_string())?;
+        if steps.len() as u32 >= run.max_steps {
+            let now = chrono::Utc::now().timestamp();
+            agent::set_run_status(conn, &run_id, agent::RunStatus::LimitReached, None, now).map_err(|e| e.to_string())?;
+            println!("[Agent] Run {} reached the step limit ({})", run_id, run.max_steps);
+            return Ok(agent::AgentTurn {
+                run: agent::get_run(conn, &run_id).map_err(|e| e.to_string())?.unwrap_or(run),
+                step: None,
+                model: config.model,
+                cancelled: false,
+            });
+        }
+        let redactor = redact::Redactor::load(conn).map_err(|e| e.to_string())?;
+        let request = agent::build_request(&run, &steps, config.language, &redactor, config.context_size);
+        (run, config, request)
+    };
+    let provider = ai_provider::from_config(&config);
+
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let mut on_token = |token: &str| {
+        let _ = window.emit_to(
+            window.label(),
+            "ai:token",
+            serde_json::json!({
+                "request_id": request_id,
+                "token": token,
+            }),
+        );
+    };
+    let response = ai::generate(provider.as_ref(), &request, &request_id, &mut on_token)
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.cancelled {
+        return Ok(agent::AgentTurn {
+            run,
+            step: None,
+            model: response.model,
+            cancelled: true,
+        });
     }
-    if let Some(m) = model {
-        config.model = m;
+
+    // Parsed before locking: nothing in the model's text should be able to poison the DB mutex
+    let proposal = agent::parse_proposal(&response.response);
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let now = chrono::Utc::now().timestamp();
+    // The run may have been stopped while the model was answering
+    let run = agent::get_run(conn, &run_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Agent run not found: {}", run_id))?;
+    let step = match (run.status, proposal) {
+        (agent::RunStatus::Running, agent::Proposal::Step { thought, command }) => {
+            Some(agent::add_step(conn, &run, &thought, &command, now).map_err(|e| e.to_string())?)
+        }
+        (agent::RunStatus::Running, agent::Proposal::Done { summary }) => {
+            agent::set_run_status(conn, &run_id, agent::RunStatus::Completed, Some(&summary), now)
+                .map_err(|e| e.to_string())?;
+            println!("[Agent] Run {} completed", run_id);
+            None
+        }
+        _ => None,
+    };
+
+    Ok(agent::AgentTurn {
+        run: agent::get_run(conn, &run_id).map_err(|e| e.to_string())?.unwrap_or(run),
+        step,
+        model: response.model,
+        cancelled: false,
+    })
+}
+
+/// Approve (optionally with an edited command) or reject a proposed step
+/// An approved step runs silently on the session and its output is stored for the next turn
+#[tauri::command]
+async fn ai_agent_decide(
+    state: State<'_, AppState>,
+    step_id: i64,
+    approve: bool,
+    command: Option<String>,
+    note: Option<String>,
+) -> Result<agent::AgentStep, String> {
+    let (session_id, exec_command) = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        let step = agent::get_step(conn, step_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Agent step not found: {}", step_id))?;
+        let run = agent::get_run(conn, &step.run_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Agent run not found: {}", step.run_id))?;
+        if run.status != agent::RunStatus::Running {
+            return Err("이미 끝난 작업입니다".into());
+        }
+        if step.status != agent::StepStatus::Proposed {
+            return Err("이미 처리된 단계입니다".into());
+        }
+
+        if !approve {
+            agent::reject_step(conn, step_id, note.as_deref()).map_err(|e| e.to_string())?;
+            return agent::get_step(conn, step_id).map_err(|e| e.to_string())?.ok_or("Agent step not found".into());
+        }
+
+        let command = command.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+        let command = match command {
+            Some(edited) if edited != step.command => {
+                if !agent::edit_step(conn, &run, step_id, &edited).map_err(|e| e.to_string())? {
+                    // Over the limit after editing: stays blocked
+                    return agent::get_step(conn, step_id).map_err(|e| e.to_string())?.ok_or("Agent step not found".into());
+                }
+                edited
+            }
+            _ => step.command,
+        };
+        if !agent::start_step(conn, step_id).map_err(|e| e.to_string())? {
+            return Err("이미 처리된 단계입니다".into());
+        }
+        (run.session_id.clone(), agent::exec_command(&run, &command))
+    };
+
+    println!("[Agent] Executing step {} on session {}", step_id, session_id);
+    let started = std::time::Instant::now();
+    let result = tokio::task::spawn_blocking(move || {
+        ssh::exec_in_session(&session_id, &exec_command, std::time::Duration::from_secs(agent::STEP_TIMEOUT_SECS))
+    })
+    .await
+    // A panicked exec still finishes the step (as failed) so the run can go on
+    .unwrap_or_else(|e| Err(format!("실행 작업이 중단되었습니다: {}", e)));
+    let duration_ms = started.elapsed().as_millis() as i64;
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    agent::record_execution(conn, step_id, &result, duration_ms, chrono::Utc::now().timestamp())
+        .map_err(|e| e.to_string())?;
+    agent::get_step(conn, step_id).map_err(|e| e.to_string())?.ok_or("Agent step not found".into())
+}
+
+/// Stop a run; its steps stay for review
+#[tauri::command]
+fn ai_agent_stop(state: State<AppState>, run_id: String) -> Result<agent::AgentRun, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let run = agent::get_run(conn, &run_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Agent run not found: {}", run_id))?;
+    if run.status != agent::RunStatus::Running {
+        return Ok(run);
     }
+    agent::set_run_status(conn, &run_id, agent::RunStatus::Cancelled, None, chrono::Utc::now().timestamp())
+        .map_err(|e| e.to_string())?;
+    agent::get_run(conn, &run_id).map_err(|e| e.to_string())?.ok_or("Agent run not found".into())
+}
 
-    ai::ask_ollama(&config, &prompt, context.as_deref())
+/// Agent runs of a profile, given directly or through an open session
+#[tauri::command]
+fn ai_agent_runs(
+    state: State<AppState>,
+    profile_id: Option<String>,
+    session_id: Option<String>,
+) -> Result<Vec<agent::AgentRun>, String> {
+    let profile_id = profile_id.or_else(|| session_id.as_deref().and_then(ssh::session_profile));
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    agent::list_runs(conn, profile_id.as_deref()).map_err(|e| e.to_string())
+}
+
+/// A run with its step log
+#[tauri::command]
+fn ai_agent_get(state: State<AppState>, run_id: String) -> Result<agent::AgentRunReport, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let run = agent::get_run(conn, &run_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Agent run not found: {}", run_id))?;
+    let steps = agent::steps(conn, &run_id).map_err(|e| e.to_string())?;
+    Ok(agent::AgentRunReport { run, steps })
+}
+
+#[tauri::command]
+fn ai_agent_delete(state: State<AppState>, run_id: String) -> Result<bool, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    agent::delete_run(conn, &run_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn runbooks_status(state: State<AppState>) -> Result<runbooks::IndexStatus, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    runbooks::status(conn).map_err(|e| e.to_string())
+}
+
+/// Set the runbook directory (empty to turn runbooks off)
+#[tauri::command]
+fn runbooks_set_dir(state: State<AppState>, dir: String) -> Result<runbooks::IndexStatus, String> {
+    if !dir.trim().is_empty() && !std::path::Path::new(dir.trim()).is_dir() {
+        return Err(format!("디렉터리를 찾을 수 없습니다: {}", dir.trim()));
+    }
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    runbooks::set_dir(conn, &dir).map_err(|e| e.to_string())?;
+    runbooks::status(conn).map_err(|e| e.to_string())
+}
+
+/// (Re)index the runbook directory; only new or changed files are embedded
+#[tauri::command]
+async fn runbooks_index(state: State<'_, AppState>) -> Result<runbooks::IndexSummary, String> {
+    let (config, dir, plan) = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        let config = ai::AIConfig::load(conn).map_err(|e| e.to_string())?;
+        let dir = runbooks::get_dir(conn)
+            .map_err(|e| e.to_string())?
+            .ok_or("런북 디렉터리가 설정되지 않았습니다")?;
+        let files = runbooks::scan(std::path::Path::new(&dir))
+            .map_err(|e| format!("런북 디렉터리를 읽을 수 없습니다: {}", e))?;
+        let plan = runbooks::plan(conn, files, &config.embedding_model).map_err(|e| e.to_string())?;
+        for path in &plan.removed {
+            runbooks::remove_document(conn, path).map_err(|e| e.to_string())?;
+        }
+        (config, dir, plan)
+    };
+    let provider = ai_provider::from_config(&config);
+    let model = &config.embedding_model;
+    println!(
+        "[Runbooks] Indexing {} file(s) with {} ({} unchanged, {} removed)",
+        plan.changed.len(),
+        model,
+        plan.unchanged,
+        plan.removed.len()
+    );
+
+    let mut summary = runbooks::IndexSummary {
+        unchanged: plan.unchanged,
+        removed: plan.removed.len(),
+        ..Default::default()
+    };
+    for file in &plan.changed {
+        let chunks = runbooks::chunk_markdown(&file.content);
+        let texts: Vec<String> = chunks.iter().map(|c| runbooks::embedding_text(&file.path, c)).collect();
+        let mut embeddings = Vec::with_capacity(texts.len());
+        let mut failure = None;
+        for batch in texts.chunks(runbooks::EMBED_BATCH) {
+            match provider.embed(model, batch).await {
+                Ok(vectors) => embeddings.extend(vectors),
+                Err(e) => {
+                    failure = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        if let Some(error) = failure {
+            println!("[Runbooks] Failed to embed {}: {}", file.path, error);
+            summary.failed.push(format!("{}: {}", file.path, error));
+            continue;
+        }
+
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        // The directory may have been cleared or changed while embedding
+        if runbooks::get_dir(conn).map_err(|e| e.to_string())?.as_deref() != Some(dir.as_str()) {
+            println!("[Runbooks] Directory changed during indexing, stopping");
+            return Err("인덱싱 중 런북 디렉터리가 변경되어 중단했습니다".into());
+        }
+        let now = chrono::Utc::now().timestamp();
+        runbooks::store_document(conn, file, model, &chunks, &embeddings, now).map_err(|e| e.to_string())?;
+        summary.indexed += 1;
+        summary.chunks += chunks.len();
+    }
+    Ok(summary)
+}
+
+/// Runbook passages closest to a query, for checking the index
+#[tauri::command]
+async fn runbooks_search(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<runbooks::RunbookHit>, String> {
+    let config = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        ai::AIConfig::load(conn).map_err(|e| e.to_string())?
+    };
+    let provider = ai_provider::from_config(&config);
+    let embedding = provider
+        .embed(&config.embedding_model, &[query])
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?
+        .remove(0);
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    runbooks::search(
+        conn,
+        &embedding,
+        &config.embedding_model,
+        limit.unwrap_or(runbooks::SEARCH_LIMIT),
+        0.0,
+    )
+    .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn ai_extract_commands(response: String) -> Result<Vec<String>, String> {
-    Ok(ai::extract_commands(&response))
+fn ai_config_get(state: State<AppState>) -> Result<ai::AIConfig, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    ai::AIConfig::load(conn).map_err(|e| e.to_string())
+}
+
+/// Validate and store the AI settings
+#[tauri::command]
+fn ai_config_set(state: State<AppState>, config: ai::AIConfig) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    config.save(conn)
+}
+
+/// Check that the AI server is reachable and list its models
+/// Tests the given (unsaved) config, or the stored one
+#[tauri::command]
+async fn ai_test_connection(
+    state: State<'_, AppState>,
+    config: Option<ai::AIConfig>,
+) -> Result<ai::ConnectionTest, String> {
+    let stored = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        ai::AIConfig::load(conn).map_err(|e| e.to_string())?
+    };
+    // The webview never has the stored key: use it unless a new one was typed
+    let config = match config {
+        Some(config) if config.api_key.is_none() => ai::AIConfig { api_key: stored.api_key, ..config },
+        Some(config) => config,
+        None => stored,
+    };
+    Ok(ai::test_connection(&config).await)
+}
+
+/// Stop a streaming `ai_ask`; it returns what was generated so far
+#[tauri::command]
+fn ai_cancel(request_id: String) -> bool {
+    ai::cancel_request(&request_id)
+}
+
+#[tauri::command]
+fn ai_extract_commands(response: String) -> Result<Vec<command_extract::ExtractedCommand>, String> {
+    Ok(command_extract::extract(&response))
+}
+
+/// Classify commands before they are run (AI suggestions, macros, pasted text)
+#[tauri::command]
+fn risk_assess(commands: Vec<String>) -> Result<Vec<risk::RiskReport>, String> {
+    Ok(commands.iter().map(|c| risk::assess(c)).collect())
 }
 
 // ============================================================================
@@ -304,3 +1370,134 @@ fn macros_delete(state: State<AppState>, profile_id: Option<String>) -> Result<(
     let conn = db_guard.as_ref().ok_or("Database not initialized")?;
     macros::delete_macros(conn, profile_id).map_err(|e| e.to_string())
 }
+
+// ============================================================================
+// Fleet Commands
+// ============================================================================
+
+#[tauri::command]
+async fn fleet_run(
+    state: State<'_, AppState>,
+    profile_group: String,
+    command: String,
+    concurrency: Option<usize>,
+    confirmed: Option<bool>,
+) -> Result<fleet::FleetRunReport, String> {
+    // Same gate as interactive writes: nothing runs on any host until a risky command is confirmed
+    if !confirmed.unwrap_or(false) {
+        let report = risk::assess(&command);
+        if report.requires_confirmation {
+            println!("[Risk] fleet run on {}: confirmation required for {:?}", profile_group, command);
+            return Err(risk::confirmation_error(&[report]));
+        }
+    }
+
+    let targets: Vec<fleet::FleetTarget> = {
+        let db_guard = state.db.lock().unwrap();
+        let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+        profile::list_profiles_in_group(conn, &profile_group)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|p| fleet::FleetTarget {
+                password: profile::resolve_password(&p),
+                profile_id: p.id,
+                name: p.name,
+                host: p.host,
+                port: p.port,
+                user: p.user,
+            })
+            .collect()
+    };
+
+    if targets.is_empty() {
+        return Err(format!("No profiles in group: {}", profile_group));
+    }
+
+    let concurrency = concurrency.unwrap_or(fleet::DEFAULT_CONCURRENCY).max(1);
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let started_at = chrono::Utc::now().timestamp();
+    let host_count = targets.len() as i32;
+
+    let results = {
+        let run_id = run_id.clone();
+        let command = command.clone();
+        tokio::task::spawn_blocking(move || {
+            fleet::run_targets(&run_id, targets, &command, concurrency)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    };
+
+    let run = fleet::FleetRun {
+        id: run_id,
+        profile_group,
+        command,
+        concurrency: concurrency as i32,
+        started_at,
+        finished_at: Some(chrono::Utc::now().timestamp()),
+        host_count,
+        failed_count: results
+            .iter()
+            .filter(|r| r.exit_code != Some(0))
+            .count() as i32,
+    };
+
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    fleet::save_run(conn, &run, &results).map_err(|e| e.to_string())?;
+
+    Ok(fleet::FleetRunReport { run, results })
+}
+
+#[tauri::command]
+fn fleet_list_runs(
+    state: State<AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<fleet::FleetRun>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    fleet::list_runs(conn, limit.unwrap_or(50)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn fleet_get_run(state: State<AppState>, run_id: String) -> Result<fleet::FleetRunReport, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let run = fleet::get_run(conn, &run_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Fleet run not found: {}", run_id))?;
+    let results = fleet::get_results(conn, &run_id).map_err(|e| e.to_string())?;
+    Ok(fleet::FleetRunReport { run, results })
+}
+
+#[tauri::command]
+fn fleet_diff(state: State<AppState>, run_id: String) -> Result<Vec<fleet::OutputGroup>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let results = fleet::get_results(conn, &run_id).map_err(|e| e.to_string())?;
+    Ok(fleet::group_outputs(&results))
+}
+
+#[tauri::command]
+fn fleet_compare(
+    state: State<AppState>,
+    result_a: String,
+    result_b: String,
+) -> Result<Vec<fleet::DiffLine>, String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    let a = fleet::get_result(conn, &result_a)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Fleet result not found: {}", result_a))?;
+    let b = fleet::get_result(conn, &result_b)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Fleet result not found: {}", result_b))?;
+    Ok(fleet::diff_lines(&a.stdout, &b.stdout))
+}
+
+#[tauri::command]
+fn fleet_delete_run(state: State<AppState>, run_id: String) -> Result<(), String> {
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref().ok_or("Database not initialized")?;
+    fleet::delete_run(conn, &run_id).map_err(|e| e.to_string())
+}
@@ -0,0 +1,292 @@
+//! Next-command prediction from command sequences
+//!
+//! Every time a command is recorded we count the transition from the commands
+//! that preceded it in the same session (n-grams of order 1 and 2, plus a
+//! "stem" context made of the first two words, so `git add -A` and `git add .`
+//! share what usually follows them). Predictions back off from the longest
+//! matching context to the shortest, weighting each by its relative frequency.
+
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Commands further apart than this are not treated as a sequence
+const MAX_GAP_SECS: i64 = 60 * 60;
+
+const ORDER_STEM: i32 = 0;
+const ORDER_1: i32 = 1;
+const ORDER_2: i32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prediction {
+    pub cmd: String,
+    pub score: f64,
+    pub count: i64,
+    pub last_used: i64,
+}
+
+fn weight(order: i32) -> f64 {
+    match order {
+        ORDER_2 => 1.0,
+        ORDER_1 => 0.6,
+        _ => 0.3,
+    }
+}
+
+/// First two words of a command (None if that is the whole command)
+fn stem(cmd: &str) -> Option<String> {
+    let words: Vec<&str> = cmd.split_whitespace().collect();
+    if words.len() <= 2 {
+        return None;
+    }
+    Some(words[..2].join(" "))
+}
+
+/// Contexts for a window of previous commands (most recent last)
+fn contexts(previous: &[String]) -> Vec<(i32, String)> {
+    let mut result = Vec::new();
+    let Some(last) = previous.last() else {
+        return result;
+    };
+
+    if previous.len() >= 2 {
+        let before = &previous[previous.len() - 2];
+        result.push((ORDER_2, format!("{}\n{}", before, last)));
+    }
+    result.push((ORDER_1, last.clone()));
+    if let Some(s) = stem(last) {
+        result.push((ORDER_STEM, s));
+    }
+
+    result
+}
+
+fn bump(conn: &Connection, profile_id: &str, previous: &[String], next: &str, ts: i64) -> Result<()> {
+    for (order, context) in contexts(previous) {
+        conn.execute(
+            "INSERT INTO history_transitions (profile_id, ctx_order, context, next_cmd, count, last_used)
+             VALUES (?1, ?2, ?3, ?4, 1, ?5)
+             ON CONFLICT(profile_id, ctx_order, context, next_cmd) DO UPDATE SET
+                count = count + 1,
+                last_used = MAX(last_used, excluded.last_used)",
+            params![profile_id, order, context, next, ts],
+        )?;
+    }
+    Ok(())
+}
+
+/// Most recent commands of a profile (or a single session), oldest first
+pub fn recent_commands(
+    conn: &Connection,
+    profile_id: &str,
+    session_id: Option<&str>,
+    before_ts: i64,
+    limit: usize,
+) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT cmd, ts FROM history
+         WHERE profile_id = ?1 AND (?2 IS NULL OR session_id = ?2) AND ts <= ?3
+         ORDER BY ts DESC, rowid DESC
+         LIMIT ?4",
+    )?;
+    let mut rows = stmt
+        .query_map(params![profile_id, session_id, before_ts, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<(String, i64)>>>()?;
+    rows.reverse();
+    Ok(rows)
+}
+
+/// Learn the transition into `cmd` (call before the command itself is inserted)
+pub fn record_transition(
+    conn: &Connection,
+    profile_id: &str,
+    session_id: Option<&str>,
+    cmd: &str,
+    ts: i64,
+) -> Result<()> {
+    let previous: Vec<String> = recent_commands(conn, profile_id, session_id, ts, 2)?
+        .into_iter()
+        .filter(|(_, prev_ts)| ts - prev_ts <= MAX_GAP_SECS)
+        .map(|(prev, _)| prev)
+        .collect();
+
+    bump(conn, profile_id, &previous, cmd, ts)
+}
+
+/// Predict the next commands after `previous` (most recent last)
+pub fn predict(
+    conn: &Connection,
+    profile_id: &str,
+    previous: &[String],
+    limit: usize,
+) -> Result<Vec<Prediction>> {
+    let mut scores: HashMap<String, Prediction> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT next_cmd, count, last_used FROM history_transitions
+         WHERE profile_id = ?1 AND ctx_order = ?2 AND context = ?3",
+    )?;
+
+    for (order, context) in contexts(previous) {
+        let rows = stmt
+            .query_map(params![profile_id, order, context], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        let total: i64 = rows.iter().map(|(_, count, _)| count).sum();
+        if total == 0 {
+            continue;
+        }
+
+        for (cmd, count, last_used) in rows {
+            let p = scores.entry(cmd.clone()).or_insert(Prediction {
+                cmd,
+                score: 0.0,
+                count: 0,
+                last_used: 0,
+            });
+            p.score += weight(order) * count as f64 / total as f64;
+            p.count = p.count.max(count);
+            p.last_used = p.last_used.max(last_used);
+        }
+    }
+
+    let mut predictions: Vec<Prediction> = scores.into_values().collect();
+    predictions.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.last_used.cmp(&a.last_used))
+    });
+    predictions.truncate(limit);
+    Ok(predictions)
+}
+
+/// Recompute `history_transitions` from the history table
+pub fn rebuild(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM history_transitions", [])?;
+
+    {
+        let mut stmt = tx.prepare(
+            "SELECT profile_id, session_id, cmd, ts FROM history
+             ORDER BY profile_id, session_id, ts, rowid",
+        )?;
+        let mut rows = stmt.query([])?;
+
+        let mut current: Option<(String, Option<String>)> = None;
+        let mut window: Vec<(String, i64)> = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let profile_id: String = row.get(0)?;
+            let session_id: Option<String> = row.get(1)?;
+            let cmd: String = row.get(2)?;
+            let ts: i64 = row.get(3)?;
+
+            let key = (profile_id, session_id);
+            if current.as_ref() != Some(&key) {
+                window.clear();
+                current = Some(key.clone());
+            }
+
+            window.retain(|(_, prev_ts)| ts - prev_ts <= MAX_GAP_SECS);
+            let previous: Vec<String> = window.iter().map(|(c, _)| c.clone()).collect();
+            bump(&tx, &key.0, &previous, &cmd, ts)?;
+
+            window.push((cmd, ts));
+            if window.len() > 2 {
+                window.remove(0);
+            }
+        }
+    }
+
+    tx.commit()?;
+    println!("[Prediction] Rebuilt command transitions");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+    use crate::history::{insert_entry, search_suggestions, HistoryEntry};
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES ('p1', 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [],
+        ).unwrap();
+        conn
+    }
+
+    fn run(conn: &Connection, session: &str, cmd: &str, ts: i64) {
+        insert_entry(conn, &HistoryEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            profile_id: "p1".to_string(),
+            cmd: cmd.to_string(),
+            ts,
+            exit_code: Some(0),
+            duration_ms: None,
+            cwd: None,
+            hostname: None,
+            session_id: Some(session.to_string()),
+            shell: None,
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_predicts_following_command() {
+        let conn = setup();
+        let mut ts = 1_700_000_000;
+        for (i, add) in ["git add .", "git add -A", "git add src"].iter().enumerate() {
+            let session = format!("s{}", i);
+            for cmd in [*add, "git commit", "git push"] {
+                run(&conn, &session, cmd, ts);
+                ts += 10;
+            }
+        }
+        run(&conn, "s9", "cd deploy", ts);
+        run(&conn, "s9", "./deploy.sh", ts + 5);
+
+        let after_add = predict(&conn, "p1", &["git add README.md".to_string()], 5).unwrap();
+        assert_eq!(after_add[0].cmd, "git commit");
+
+        let after_commit = predict(&conn, "p1", &["git add .".to_string(), "git commit".to_string()], 5).unwrap();
+        assert_eq!(after_commit[0].cmd, "git push");
+
+        // Commands far apart in time are not a sequence
+        run(&conn, "s9", "uptime", ts + 2 * MAX_GAP_SECS);
+        let after_deploy = predict(&conn, "p1", &["./deploy.sh".to_string()], 5).unwrap();
+        assert!(after_deploy.is_empty());
+
+        // Rebuilding from history learns the same transitions
+        rebuild(&conn).unwrap();
+        let rebuilt = predict(&conn, "p1", &["cd deploy".to_string()], 5).unwrap();
+        assert_eq!(rebuilt[0].cmd, "./deploy.sh");
+    }
+
+    #[test]
+    fn test_empty_prefix_suggestions_start_with_prediction() {
+        let conn = setup();
+        let mut ts = chrono::Utc::now().timestamp() - 1000;
+        for _ in 0..3 {
+            for cmd in ["cd deploy", "./deploy.sh", "ls"] {
+                run(&conn, "s1", cmd, ts);
+                ts += 10;
+            }
+        }
+        run(&conn, "s2", "cd deploy", ts);
+
+        let suggestions = search_suggestions(&conn, "p1", "", None, Some("s2"), 5).unwrap();
+        assert_eq!(suggestions[0].cmd, "./deploy.sh");
+        assert!(suggestions[0].predicted);
+        assert!(!suggestions[1].predicted);
+
+        let with_prefix = search_suggestions(&conn, "p1", "l", None, Some("s2"), 5).unwrap();
+        assert!(with_prefix.iter().all(|s| !s.predicted));
+    }
+}
@@ -14,6 +14,9 @@ pub struct Profile {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>, // Fallback storage when keyring unavailable
     pub profile_group: Option<String>,
+    /// Import the server's shell history on connect (opt-in)
+    #[serde(default)]
+    pub import_remote_history: bool,
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -27,6 +30,8 @@ pub struct CreateProfileInput {
     pub auth_type: String,
     pub password: Option<String>,
     pub profile_group: Option<String>,
+    #[serde(default)]
+    pub import_remote_history: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,6 +43,7 @@ pub struct UpdateProfileInput {
     pub user: Option<String>,
     pub auth_type: Option<String>,
     pub profile_group: Option<String>,
+    pub import_remote_history: Option<bool>,
 }
 
 /// Check if keyring should be used based on environment
@@ -87,6 +93,11 @@ lazy_static::lazy_static! {
     };
 }
 
+/// Whether secrets go to the OS keychain (otherwise the caller stores them in the database)
+pub fn keyring_available() -> bool {
+    *KEYRING_AVAILABLE
+}
+
 /// Get keyring entry for a profile
 fn get_keyring_entry(profile_id: &str) -> Result<Entry, String> {
     // Use a simpler target name format for Windows
@@ -170,6 +181,14 @@ pub fn get_password(profile_id: &str) -> Result<Option<String>, String> {
     }
 }
 
+/// Resolve the password for a loaded profile (keyring first, then database fallback)
+pub fn resolve_password(profile: &Profile) -> Option<String> {
+    match get_password(&profile.id) {
+        Ok(Some(password)) => Some(password),
+        _ => profile.password.clone(),
+    }
+}
+
 /// Delete password from OS keychain
 pub fn delete_password(profile_id: &str) -> Result<(), String> {
     let entry = get_keyring_entry(profile_id)?;
@@ -254,13 +273,14 @@ pub fn create_profile(conn: &Connection, input: CreateProfileInput) -> Result<Pr
         auth_type: input.auth_type,
         password: db_password.clone(),
         profile_group: input.profile_group,
+        import_remote_history: input.import_remote_history,
         created_at: now,
         updated_at: now,
     };
 
     conn.execute(
-        "INSERT INTO profiles (id, name, host, port, user, auth_type, password, profile_group, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        "INSERT INTO profiles (id, name, host, port, user, auth_type, password, profile_group, import_remote_history, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
         params![
             &profile.id,
             &profile.name,
@@ -270,6 +290,7 @@ pub fn create_profile(conn: &Connection, input: CreateProfileInput) -> Result<Pr
             &profile.auth_type,
             &db_password,
             &profile.profile_group,
+            profile.import_remote_history,
             profile.created_at,
             profile.updated_at,
         ],
@@ -281,7 +302,7 @@ pub fn create_profile(conn: &Connection, input: CreateProfileInput) -> Result<Pr
 /// Get all profiles
 pub fn list_profiles(conn: &Connection) -> Result<Vec<Profile>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name, host, port, user, auth_type, password, profile_group, created_at, updated_at
+        "SELECT id, name, host, port, user, auth_type, password, profile_group, import_remote_history, created_at, updated_at
          FROM profiles
          ORDER BY profile_group, name",
     )?;
@@ -297,8 +318,39 @@ pub fn list_profiles(conn: &Connection) -> Result<Vec<Profile>> {
                 auth_type: row.get(5)?,
                 password: row.get(6)?,
                 profile_group: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
+                import_remote_history: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(profiles)
+}
+
+/// Get all profiles that belong to a profile group
+pub fn list_profiles_in_group(conn: &Connection, group: &str) -> Result<Vec<Profile>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, host, port, user, auth_type, password, profile_group, import_remote_history, created_at, updated_at
+         FROM profiles
+         WHERE profile_group = ?1
+         ORDER BY name",
+    )?;
+
+    let profiles = stmt
+        .query_map([group], |row| {
+            Ok(Profile {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                host: row.get(2)?,
+                port: row.get(3)?,
+                user: row.get(4)?,
+                auth_type: row.get(5)?,
+                password: row.get(6)?,
+                profile_group: row.get(7)?,
+                import_remote_history: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -309,7 +361,7 @@ pub fn list_profiles(conn: &Connection) -> Result<Vec<Profile>> {
 /// Get a profile by ID
 pub fn get_profile(conn: &Connection, id: &str) -> Result<Option<Profile>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name, host, port, user, auth_type, password, profile_group, created_at, updated_at
+        "SELECT id, name, host, port, user, auth_type, password, profile_group, import_remote_history, created_at, updated_at
          FROM profiles
          WHERE id = ?1",
     )?;
@@ -325,8 +377,9 @@ pub fn get_profile(conn: &Connection, id: &str) -> Result<Option<Profile>> {
                 auth_type: row.get(5)?,
                 password: row.get(6)?,
                 profile_group: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
+                import_remote_history: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
             })
         })
         .optional()?;
@@ -351,14 +404,18 @@ pub fn update_profile(conn: &Connection, input: UpdateProfileInput) -> Result<Pr
         auth_type: input.auth_type.unwrap_or(existing.auth_type),
         password: existing.password, // Keep existing password
         profile_group: input.profile_group.or(existing.profile_group),
+        import_remote_history: input
+            .import_remote_history
+            .unwrap_or(existing.import_remote_history),
         created_at: existing.created_at,
         updated_at: now,
     };
 
     conn.execute(
         "UPDATE profiles
-         SET name = ?1, host = ?2, port = ?3, user = ?4, auth_type = ?5, profile_group = ?6, updated_at = ?7
-         WHERE id = ?8",
+         SET name = ?1, host = ?2, port = ?3, user = ?4, auth_type = ?5, profile_group = ?6,
+             import_remote_history = ?7, updated_at = ?8
+         WHERE id = ?9",
         params![
             &updated.name,
             &updated.host,
@@ -366,6 +423,7 @@ pub fn update_profile(conn: &Connection, input: UpdateProfileInput) -> Result<Pr
             &updated.user,
             &updated.auth_type,
             &updated.profile_group,
+            updated.import_remote_history,
             updated.updated_at,
             &updated.id,
         ],
@@ -0,0 +1,297 @@
+//! Dynamic argument completion sourced from the remote host
+//!
+//! When the spec-based completer expects a file, branch, container, unit...
+//! we ask the host over the session's exec channel (`ssh_exec`) and cache the
+//! answer per session for a short TTL, so typing doesn't round-trip per key.
+//! The cache for a session is dropped when it runs a command or closes, since
+//! that is when branches, files and containers usually change.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::commands_dict::ArgKind;
+use crate::completion::{self, CompletionKind, CompletionResult};
+use crate::ssh;
+
+/// Paths change often, everything else rarely
+const PATH_TTL: Duration = Duration::from_secs(10);
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate {
+    value: String,
+    description: Option<String>,
+}
+
+struct CacheEntry {
+    fetched: Instant,
+    candidates: Vec<Candidate>,
+}
+
+lazy_static::lazy_static! {
+    // session id -> query key -> candidates
+    static ref CACHE: Mutex<HashMap<String, HashMap<String, CacheEntry>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Drop all cached candidates of a session
+pub fn forget_session(session_id: &str) {
+    CACHE.lock().unwrap().remove(session_id);
+}
+
+fn single_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Quote a directory for the remote shell, keeping a leading `~` expandable
+fn quote_dir(dir: &str) -> String {
+    if dir.is_empty() {
+        return ".".to_string();
+    }
+    if let Some(rest) = dir.strip_prefix('~') {
+        let (user, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if user.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c)) {
+            return if path.is_empty() {
+                format!("~{}", user)
+            } else {
+                format!("~{}{}", user, single_quote(path))
+            };
+        }
+    }
+    single_quote(dir)
+}
+
+/// Backslash-escape characters the shell would otherwise interpret
+fn shell_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_whitespace() || "'\"\\$`&|;()<>*?!#[]{}".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Escape the directory part of a completed path like a name, keeping a leading `~user`
+fn escape_dir(dir: &str) -> String {
+    if let Some(rest) = dir.strip_prefix('~') {
+        let (user, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if user.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c)) {
+            return format!("~{}{}", user, shell_escape(path));
+        }
+    }
+    shell_escape(dir)
+}
+
+/// Split a partial path into the directory part (with trailing '/') and the name prefix
+fn split_path(partial: &str) -> (&str, &str) {
+    match partial.rfind('/') {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    }
+}
+
+/// Remote command listing candidates for an argument kind
+fn remote_command(kind: ArgKind, dir: &str) -> Option<String> {
+    let command = match kind {
+        ArgKind::File | ArgKind::Directory => {
+            format!("ls -1Ap -- {} 2>/dev/null", quote_dir(dir))
+        }
+        ArgKind::Branch => {
+            "git for-each-ref --format='%(refname:short)' refs/heads refs/remotes refs/tags 2>/dev/null"
+                .to_string()
+        }
+        ArgKind::Container => "docker ps -a --format '{{.Names}}' 2>/dev/null".to_string(),
+        ArgKind::Service => {
+            "systemctl list-unit-files --type=service --no-legend --plain 2>/dev/null | awk '{print $1}'"
+                .to_string()
+        }
+        ArgKind::Pod => "kubectl get pods -o name 2>/dev/null | sed 's|^pod/||'".to_string(),
+        ArgKind::Host => {
+            "awk 'tolower($1)==\"host\" {for (i=2;i<=NF;i++) if ($i !~ /[*?]/) print $i}' ~/.ssh/config 2>/dev/null; \
+             cut -d' ' -f1 ~/.ssh/known_hosts 2>/dev/null | tr ',' '\\n' | grep -v '^|'"
+                .to_string()
+        }
+        ArgKind::User => "cut -d: -f1 /etc/passwd".to_string(),
+        ArgKind::Process => "ps -eo pid=,comm=".to_string(),
+        ArgKind::Package | ArgKind::Command | ArgKind::Any => return None,
+    };
+    Some(command)
+}
+
+fn parse_output(kind: ArgKind, output: &str) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let candidate = match kind {
+            ArgKind::Directory if !line.ends_with('/') => continue,
+            ArgKind::Process => {
+                let (pid, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                Candidate {
+                    value: pid.to_string(),
+                    description: Some(name.trim().to_string()).filter(|n| !n.is_empty()),
+                }
+            }
+            _ => Candidate {
+                value: line.to_string(),
+                description: None,
+            },
+        };
+        if !candidates.iter().any(|c| c.value == candidate.value) {
+            candidates.push(candidate);
+        }
+    }
+
+    candidates
+}
+
+/// Get candidates from the cache or fetch them
+fn cached(
+    session_id: &str,
+    key: &str,
+    ttl: Duration,
+    fetch: impl FnOnce() -> Result<Vec<Candidate>, String>,
+) -> Result<Vec<Candidate>, String> {
+    if let Some(entry) = CACHE.lock().unwrap().get(session_id).and_then(|m| m.get(key)) {
+        if entry.fetched.elapsed() < ttl {
+            return Ok(entry.candidates.clone());
+        }
+    }
+
+    // Fetch without holding the lock; a concurrent fetch just overwrites
+    let candidates = fetch()?;
+    CACHE
+        .lock()
+        .unwrap()
+        .entry(session_id.to_string())
+        .or_default()
+        .insert(
+            key.to_string(),
+            CacheEntry {
+                fetched: Instant::now(),
+                candidates: candidates.clone(),
+            },
+        );
+    Ok(candidates)
+}
+
+/// Complete `line` with spec candidates plus remote values for the expected argument
+pub fn complete(session_id: &str, line: &str, limit: usize) -> Result<CompletionResult, String> {
+    let mut result = completion::complete(line, limit);
+    let Some(kind) = result.expecting else {
+        return Ok(result);
+    };
+
+    let is_path = matches!(kind, ArgKind::File | ArgKind::Directory);
+    let (dir, name_prefix) = if is_path {
+        split_path(&result.partial)
+    } else {
+        ("", result.partial.as_str())
+    };
+    let Some(command) = remote_command(kind, dir) else {
+        return Ok(result);
+    };
+
+    // Relative paths, branches and the like depend on where the shell is
+    let cwd = ssh::session_cwd(session_id);
+    let command = match &cwd {
+        Some(cwd) => format!("cd {} 2>/dev/null; {}", single_quote(cwd), command),
+        None => command,
+    };
+    let key = format!("{:?}\n{}\n{}", kind, cwd.as_deref().unwrap_or(""), dir);
+    let ttl = if is_path { PATH_TTL } else { DEFAULT_TTL };
+
+    let candidates = cached(session_id, &key, ttl, || {
+        let output = ssh::ssh_exec(session_id.to_string(), command)?;
+        Ok(parse_output(kind, &output))
+    })?;
+
+    let head: String = line.chars().take(result.replace_from).collect();
+    let show_hidden = name_prefix.starts_with('.');
+
+    for candidate in candidates {
+        if result.completions.len() >= limit {
+            break;
+        }
+        if !candidate.value.starts_with(name_prefix) || (is_path && !show_hidden && candidate.value.starts_with('.')) {
+            continue;
+        }
+
+        let value = if is_path {
+            format!("{}{}", escape_dir(dir), shell_escape(&candidate.value))
+        } else {
+            shell_escape(&candidate.value)
+        };
+        if result.completions.iter().any(|c| c.value == value) {
+            continue;
+        }
+        result.completions.push(completion::make_completion(
+            &head,
+            value,
+            candidate.description,
+            CompletionKind::Argument,
+        ));
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_commands_and_parsing() {
+        assert_eq!(split_path("src/ma"), ("src/", "ma"));
+        assert_eq!(split_path("ma"), ("", "ma"));
+        assert_eq!(quote_dir(""), ".");
+        assert_eq!(quote_dir("~/my dir/"), "~'/my dir/'");
+        assert_eq!(quote_dir("it's/"), "'it'\\''s/'");
+        // The directory goes back into the line escaped, like the name
+        assert_eq!(escape_dir("~/my dir/"), "~/my\\ dir/");
+        assert_eq!(escape_dir("~ops/a b/"), "~ops/a\\ b/");
+        assert_eq!(escape_dir("src/"), "src/");
+        assert_eq!(
+            remote_command(ArgKind::Directory, "/var/").unwrap(),
+            "ls -1Ap -- '/var/' 2>/dev/null"
+        );
+        assert!(remote_command(ArgKind::Any, "").is_none());
+
+        let dirs = parse_output(ArgKind::Directory, "log/\nfile.txt\nlib/\n");
+        assert_eq!(dirs.iter().map(|c| c.value.as_str()).collect::<Vec<_>>(), vec!["log/", "lib/"]);
+
+        let procs = parse_output(ArgKind::Process, "    1 systemd\n  812 nginx\n");
+        assert_eq!(procs[1].value, "812");
+        assert_eq!(procs[1].description.as_deref(), Some("nginx"));
+
+        assert_eq!(shell_escape("my file (1).txt"), "my\\ file\\ \\(1\\).txt");
+    }
+
+    #[test]
+    fn test_cache_ttl_and_forget() {
+        let session = "test-session-cache";
+        let fetches = std::cell::Cell::new(0);
+        let fetch = || {
+            fetches.set(fetches.get() + 1);
+            Ok(vec![Candidate {
+                value: "main".to_string(),
+                description: None,
+            }])
+        };
+
+        cached(session, "branch", DEFAULT_TTL, fetch).unwrap();
+        cached(session, "branch", DEFAULT_TTL, fetch).unwrap();
+        assert_eq!(fetches.get(), 1);
+
+        // Expired entries are fetched again
+        cached(session, "branch", Duration::ZERO, fetch).unwrap();
+        assert_eq!(fetches.get(), 2);
+
+        forget_session(session);
+        let values = cached(session, "branch", DEFAULT_TTL, fetch).unwrap();
+        assert_eq!(fetches.get(), 3);
+        assert_eq!(values[0].value, "main");
+    }
+}
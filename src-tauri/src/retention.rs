@@ -0,0 +1,364 @@
+//! History retention and compaction
+//!
+//! The history table keeps one row per execution. Rows older than the
+//! configured max age, or beyond the newest N per profile, are collapsed into
+//! `history_archive` (one row per profile and command with its use count,
+//! last use and frecency key) and deleted. `history_stats` already counts
+//! those uses, and `frecency::rebuild` reads the archive, so suggestion
+//! ranking is unaffected. A scheduler runs compaction periodically and
+//! VACUUMs the database once per configured interval.
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{frecency, settings};
+
+const MAX_AGE_SETTING: &str = "history_retention_max_age_days";
+const MAX_ROWS_SETTING: &str = "history_retention_max_rows";
+const VACUUM_INTERVAL_SETTING: &str = "history_vacuum_interval_days";
+const LAST_VACUUM_SETTING: &str = "history_last_vacuum";
+
+pub const DEFAULT_VACUUM_INTERVAL_DAYS: u32 = 7;
+/// How often the background scheduler wakes up
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// Delay before the first scheduled compaction
+const SCHEDULER_STARTUP_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Retention settings; None / 0 means unlimited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    #[serde(default)]
+    pub max_rows_per_profile: Option<u32>,
+    /// 0 disables automatic VACUUM
+    #[serde(default = "default_vacuum_interval")]
+    pub vacuum_interval_days: u32,
+}
+
+fn default_vacuum_interval() -> u32 {
+    DEFAULT_VACUUM_INTERVAL_DAYS
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_age_days: None,
+            max_rows_per_profile: None,
+            vacuum_interval_days: DEFAULT_VACUUM_INTERVAL_DAYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompactionReport {
+    /// History rows removed from the history table
+    pub compacted_rows: usize,
+    /// Distinct (profile, command) aggregates they were folded into
+    pub archived_commands: usize,
+    pub vacuumed: bool,
+}
+
+fn get_u32(conn: &Connection, key: &str) -> Result<Option<u32>> {
+    Ok(settings::get_setting(conn, key)?.and_then(|v| v.trim().parse().ok()))
+}
+
+pub fn get_policy(conn: &Connection) -> Result<RetentionPolicy> {
+    Ok(RetentionPolicy {
+        max_age_days: get_u32(conn, MAX_AGE_SETTING)?.filter(|d| *d > 0),
+        max_rows_per_profile: get_u32(conn, MAX_ROWS_SETTING)?.filter(|n| *n > 0),
+        vacuum_interval_days: get_u32(conn, VACUUM_INTERVAL_SETTING)?
+            .unwrap_or(DEFAULT_VACUUM_INTERVAL_DAYS),
+    })
+}
+
+pub fn set_policy(conn: &Connection, policy: &RetentionPolicy) -> Result<()> {
+    let opt = |v: Option<u32>| v.unwrap_or(0).to_string();
+    settings::set_setting(conn, MAX_AGE_SETTING, &opt(policy.max_age_days))?;
+    settings::set_setting(conn, MAX_ROWS_SETTING, &opt(policy.max_rows_per_profile))?;
+    settings::set_setting(conn, VACUUM_INTERVAL_SETTING, &policy.vacuum_interval_days.to_string())
+}
+
+/// Add aggregated uses of a command to the archive
+pub(crate) fn archive_add(
+    conn: &Connection,
+    profile_id: &str,
+    cmd: &str,
+    frequency: i64,
+    last_used: i64,
+    key: f64,
+    half_life: f64,
+) -> Result<()> {
+    let existing: Option<(i64, i64, f64, f64)> = conn
+        .query_row(
+            "SELECT frequency, last_used, frecency_key, half_life_days
+             FROM history_archive WHERE profile_id = ?1 AND cmd = ?2",
+            params![profile_id, cmd],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+
+    let (frequency, last_used, key) = match existing {
+        Some((old_freq, old_last, old_key, old_half_life)) => {
+            let old_key = if (old_half_life - half_life).abs() < 1e-9 {
+                old_key
+            } else {
+                frecency::approx_key(old_freq, old_last, half_life)
+            };
+            (old_freq + frequency, old_last.max(last_used), frecency::log2_add(old_key, key))
+        }
+        None => (frequency, last_used, key),
+    };
+
+    conn.execute(
+        "INSERT INTO history_archive (profile_id, cmd, frequency, last_used, frecency_key, half_life_days)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(profile_id, cmd) DO UPDATE SET
+            frequency = excluded.frequency,
+            last_used = excluded.last_used,
+            frecency_key = excluded.frecency_key,
+            half_life_days = excluded.half_life_days",
+        params![profile_id, cmd, frequency, last_used, key, half_life],
+    )?;
+    Ok(())
+}
+
+/// Move history rows outside the retention policy into the archive
+pub fn compact(conn: &Connection, policy: &RetentionPolicy, now: i64) -> Result<CompactionReport> {
+    let mut report = CompactionReport::default();
+    if policy.max_age_days.is_none() && policy.max_rows_per_profile.is_none() {
+        return Ok(report);
+    }
+
+    let cutoff = policy
+        .max_age_days
+        .map(|d| now - d as i64 * 86_400)
+        .unwrap_or(i64::MIN);
+    let max_rows = policy.max_rows_per_profile.map(i64::from).unwrap_or(i64::MAX);
+
+    let expired: Vec<(String, String, String, i64)> = conn
+        .prepare(
+            "SELECT id, profile_id, cmd, ts FROM (
+                SELECT id, profile_id, cmd, ts,
+                       ROW_NUMBER() OVER (PARTITION BY profile_id ORDER BY ts DESC, rowid DESC) AS n
+                FROM history
+             )
+             WHERE ts < ?1 OR n > ?2",
+        )?
+        .query_map(params![cutoff, max_rows], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    if expired.is_empty() {
+        return Ok(report);
+    }
+
+    let half_life = frecency::half_life_days(conn)?;
+
+    // (profile_id, cmd) -> (frequency, last_used, key)
+    let mut aggregates: HashMap<(&str, &str), (i64, i64, f64)> = HashMap::new();
+    for (_, profile_id, cmd, ts) in &expired {
+        let use_key = frecency::exponent(*ts, half_life);
+        aggregates
+            .entry((profile_id, cmd))
+            .and_modify(|(frequency, last_used, key)| {
+                *frequency += 1;
+                *last_used = (*last_used).max(*ts);
+                *key = frecency::log2_add(*key, use_key);
+            })
+            .or_insert((1, *ts, use_key));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for ((profile_id, cmd), (frequency, last_used, key)) in &aggregates {
+        archive_add(&tx, profile_id, cmd, *frequency, *last_used, *key, half_life)?;
+    }
+    {
+        let mut delete = tx.prepare("DELETE FROM history WHERE id = ?1")?;
+        for (id, ..) in &expired {
+            delete.execute(params![id])?;
+        }
+    }
+    tx.commit()?;
+
+    report.compacted_rows = expired.len();
+    report.archived_commands = aggregates.len();
+    println!(
+        "[Retention] Compacted {} history rows into {} archived commands",
+        report.compacted_rows, report.archived_commands
+    );
+    Ok(report)
+}
+
+/// VACUUM if the configured interval has passed since the last one (or `force`)
+pub fn vacuum_if_due(conn: &Connection, policy: &RetentionPolicy, now: i64, force: bool) -> Result<bool> {
+    if !force {
+        if policy.vacuum_interval_days == 0 {
+            return Ok(false);
+        }
+        let last: i64 = settings::get_setting(conn, LAST_VACUUM_SETTING)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        if now - last < policy.vacuum_interval_days as i64 * 86_400 {
+            return Ok(false);
+        }
+    }
+
+    conn.execute_batch("VACUUM")?;
+    settings::set_setting(conn, LAST_VACUUM_SETTING, &now.to_string())?;
+    println!("[Retention] Database vacuumed");
+    Ok(true)
+}
+
+/// Compact with the stored policy and VACUUM when due
+pub fn run(conn: &Connection, now: i64, force_vacuum: bool) -> Result<CompactionReport> {
+    let policy = get_policy(conn)?;
+    let mut report = compact(conn, &policy, now)?;
+    report.vacuumed = vacuum_if_due(conn, &policy, now, force_vacuum)?;
+    Ok(report)
+}
+
+/// Periodically compact and vacuum in the background
+/// Runs on its own connection so the shared one (history saves from every
+/// session) is not locked for the whole compaction; the first run waits a
+/// little so it doesn't compete with sessions opening at startup
+pub fn start_scheduler(app: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        std::thread::sleep(SCHEDULER_STARTUP_DELAY);
+        let conn = match crate::db::open_connection(&app) {
+            Ok(conn) => conn,
+            Err(e) => {
+                crate::log!("[Retention] Scheduler could not open the database: {}", e);
+                return;
+            }
+        };
+        loop {
+            let now = chrono::Utc::now().timestamp();
+            if let Err(e) = run(&conn, now, false) {
+                crate::log!("[Retention] Scheduled compaction failed: {}", e);
+            }
+            std::thread::sleep(SCHEDULER_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+    use crate::history::{insert_entry, search_history, HistoryEntry};
+
+    const DAY: i64 = 86_400;
+    const NOW: i64 = 1_700_000_000;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES ('p1', 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [],
+        )
+        .unwrap();
+
+        // (cmd, days ago) - old frequent commands and recent ones
+        let mut uses = Vec::new();
+        for i in 0..40 {
+            uses.push(("make build", 60 + i));
+        }
+        for i in 0..6 {
+            uses.push(("git status", i * 3));
+            uses.push(("docker ps", 20 + i * 10));
+        }
+        uses.push(("git pull", 1));
+        for (i, (cmd, days)) in uses.into_iter().enumerate() {
+            insert_entry(
+                &conn,
+                &HistoryEntry {
+                    id: format!("h{}", i),
+                    profile_id: "p1".to_string(),
+                    cmd: cmd.to_string(),
+                    ts: NOW - days * DAY,
+                    exit_code: Some(0),
+                    duration_ms: None,
+                    cwd: None,
+                    hostname: None,
+                    session_id: None,
+                    shell: None,
+                },
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn ranking(conn: &Connection) -> Vec<(String, i32)> {
+        search_history(conn, "p1", "", None, 10)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.cmd, s.frequency))
+            .collect()
+    }
+
+    #[test]
+    fn test_rankings_survive_compaction() {
+        let conn = setup();
+        let before = ranking(&conn);
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(14),
+            max_rows_per_profile: Some(5),
+            vacuum_interval_days: 0,
+        };
+        let report = compact(&conn, &policy, NOW).unwrap();
+        assert!(report.compacted_rows > 40);
+
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM history", [], |r| r.get(0))
+            .unwrap();
+        assert!(remaining <= 5);
+        assert_eq!(ranking(&conn), before);
+
+        // Recomputing the stats from history + archive gives the same order
+        frecency::rebuild(&conn).unwrap();
+        assert_eq!(ranking(&conn), before);
+
+        // Compacting again only adds to the archive
+        compact(&conn, &RetentionPolicy { max_rows_per_profile: Some(1), ..policy }, NOW).unwrap();
+        frecency::rebuild(&conn).unwrap();
+        assert_eq!(ranking(&conn), before);
+    }
+
+    #[test]
+    fn test_policy_settings_and_vacuum_schedule() {
+        let conn = setup();
+        assert!(compact(&conn, &get_policy(&conn).unwrap(), NOW).unwrap().compacted_rows == 0);
+
+        set_policy(
+            &conn,
+            &RetentionPolicy {
+                max_age_days: Some(30),
+                max_rows_per_profile: None,
+                vacuum_interval_days: 7,
+            },
+        )
+        .unwrap();
+        let policy = get_policy(&conn).unwrap();
+        assert_eq!(policy.max_age_days, Some(30));
+        assert_eq!(policy.max_rows_per_profile, None);
+
+        let report = run(&conn, NOW, false).unwrap();
+        assert!(report.vacuumed);
+        assert!(!vacuum_if_due(&conn, &policy, NOW + DAY, false).unwrap());
+        assert!(vacuum_if_due(&conn, &policy, NOW + 8 * DAY, false).unwrap());
+
+        // Only uses within the last 30 days are left as rows
+        let oldest: i64 = conn
+            .query_row("SELECT MIN(ts) FROM history", [], |r| r.get(0))
+            .unwrap();
+        assert!(oldest >= NOW - 30 * DAY);
+    }
+}
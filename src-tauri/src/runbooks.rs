@@ -0,0 +1,525 @@
+//! Team runbooks: retrieval over a local directory of markdown files
+//!
+//! The directory (`RUNBOOKS_DIR_SETTING`) is scanned for `.md` files, each
+//! file is split into chunks along its headings, and every chunk is stored
+//! with an embedding vector from the configured embedding model
+//! (`ai::AIConfig::embedding_model`). Re-indexing only embeds files whose
+//! content or model changed and drops files that disappeared.
+//!
+//! When asking the AI, the question is embedded with the same model and the
+//! closest chunks (cosine similarity, brute force — a team's runbooks are a
+//! few thousand chunks at most) are added to the prompt as numbered passages
+//! the answer can cite; the cited files are returned as `RunbookSource`s.
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::embedding;
+use crate::settings;
+
+/// Setting key for the runbook directory
+pub const RUNBOOKS_DIR_SETTING: &str = "runbooks_dir";
+
+/// Chunks are packed from whole paragraphs up to this size (a code block is never split)
+const MAX_CHUNK_CHARS: usize = 1500;
+/// Larger files are not runbooks (logs, dumps) and are skipped
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Chunks sent to the embedding API per request
+pub const EMBED_BATCH: usize = 16;
+/// Passages added to a question
+pub const SEARCH_LIMIT: usize = 4;
+/// Below this similarity a passage is unrelated to the question
+pub const MIN_SCORE: f32 = 0.45;
+/// Budget for the passages in the prompt
+pub const CONTEXT_CHARS: usize = 6000;
+
+/// A markdown file found under the runbook directory
+#[derive(Debug, Clone)]
+pub struct RunbookFile {
+    /// Path relative to the directory, with `/` separators
+    pub path: String,
+    pub content: String,
+    pub hash: String,
+}
+
+/// A section of a runbook, the unit that is embedded and retrieved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// Heading breadcrumbs, e.g. "Deploy > Rollback"
+    pub heading: String,
+    pub content: String,
+    /// 1-based line of the first line of `content`
+    pub start_line: usize,
+}
+
+/// What a re-index has to do
+#[derive(Debug, Default)]
+pub struct IndexPlan {
+    /// New files, or files whose content or embedding model changed
+    pub changed: Vec<RunbookFile>,
+    /// Indexed files that no longer exist
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+/// Result of `runbooks_index`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexSummary {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub chunks: usize,
+    /// Files that could not be embedded, with the error
+    pub failed: Vec<String>,
+}
+
+/// State of the index for the settings panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub dir: Option<String>,
+    pub documents: usize,
+    pub chunks: usize,
+    pub model: Option<String>,
+    pub indexed_at: Option<i64>,
+}
+
+/// A retrieved chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunbookHit {
+    pub path: String,
+    pub heading: String,
+    pub content: String,
+    pub start_line: usize,
+    pub score: f32,
+}
+
+/// A passage given to the model, cited in the answer as `[index]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunbookSource {
+    pub index: usize,
+    pub path: String,
+    pub heading: String,
+    pub start_line: usize,
+    pub score: f32,
+}
+
+/// Configured runbook directory (None if unset)
+pub fn get_dir(conn: &Connection) -> Result<Option<String>> {
+    Ok(settings::get_setting(conn, RUNBOOKS_DIR_SETTING)?.filter(|dir| !dir.trim().is_empty()))
+}
+
+/// Set the runbook directory; an empty one turns runbooks off and drops the index
+pub fn set_dir(conn: &Connection, dir: &str) -> Result<()> {
+    if dir.trim().is_empty() {
+        conn.execute("DELETE FROM runbook_chunks", [])?;
+        conn.execute("DELETE FROM runbook_docs", [])?;
+    }
+    settings::set_setting(conn, RUNBOOKS_DIR_SETTING, dir.trim())
+}
+
+/// Markdown files under `dir`, recursively, sorted by path (hidden directories are skipped)
+pub fn scan(dir: &Path) -> std::io::Result<Vec<RunbookFile>> {
+    let mut files = Vec::new();
+    scan_into(dir, dir, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn scan_into(root: &Path, dir: &Path, files: &mut Vec<RunbookFile>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+        if name.starts_with('.') {
+            continue;
+        }
+        if file_type.is_dir() {
+            scan_into(root, &path, files)?;
+            continue;
+        }
+        let is_markdown = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+            .unwrap_or(false);
+        if !file_type.is_file() || !is_markdown || entry.metadata()?.len() > MAX_FILE_BYTES {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            println!("[Runbooks] Skipping non-UTF-8 file: {}", path.display());
+            continue;
+        };
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        files.push(RunbookFile {
+            path: relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            hash: content_hash(&content),
+            content,
+        });
+    }
+    Ok(())
+}
+
+/// FNV-1a of the content, to detect changed files
+fn content_hash(content: &str) -> String {
+    let hash = content
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+/// Split markdown into chunks: a new chunk at every heading, and within a
+/// section whole paragraphs are packed up to `MAX_CHUNK_CHARS`
+pub fn chunk_markdown(text: &str) -> Vec<Chunk> {
+    let mut chunker = Chunker::default();
+    let mut fence: Option<char> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let marker = fence_marker(line);
+        match fence {
+            Some(open) => {
+                chunker.push_line(line, number);
+                if marker == Some(open) {
+                    fence = None;
+                }
+            }
+            None if marker.is_some() => {
+                fence = marker;
+                chunker.push_line(line, number);
+            }
+            None => match heading(line) {
+                Some((level, title)) => chunker.heading(level, title),
+                None if line.trim().is_empty() => chunker.end_paragraph(),
+                None => chunker.push_line(line, number),
+            },
+        }
+    }
+    chunker.finish()
+}
+
+fn fence_marker(line: &str) -> Option<char> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("```") {
+        Some('`')
+    } else if trimmed.starts_with("~~~") {
+        Some('~')
+    } else {
+        None
+    }
+}
+
+/// ATX heading level and title
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+#[derive(Default)]
+struct Chunker {
+    headings: Vec<(usize, String)>,
+    chunks: Vec<Chunk>,
+    current: String,
+    current_start: usize,
+    paragraph: String,
+    paragraph_start: usize,
+}
+
+impl Chunker {
+    fn push_line(&mut self, line: &str, number: usize) {
+        if self.paragraph.is_empty() {
+            self.paragraph_start = number;
+        }
+        self.paragraph.push_str(line);
+        self.paragraph.push('\n');
+    }
+
+    fn end_paragraph(&mut self) {
+        let paragraph = std::mem::take(&mut self.paragraph);
+        if paragraph.trim().is_empty() {
+            return;
+        }
+        if !self.current.is_empty() && self.current.len() + paragraph.len() > MAX_CHUNK_CHARS {
+            self.flush();
+        }
+        if self.current.is_empty() {
+            self.current_start = self.paragraph_start;
+        } else {
+            self.current.push('\n');
+        }
+        self.current.push_str(&paragraph);
+    }
+
+    fn flush(&mut self) {
+        let content = std::mem::take(&mut self.current);
+        if content.trim().is_empty() {
+            return;
+        }
+        self.chunks.push(Chunk {
+            heading: self
+                .headings
+                .iter()
+                .map(|(_, title)| title.as_str())
+                .collect::<Vec<_>>()
+                .join(" > "),
+            content: content.trim_end().to_string(),
+            start_line: self.current_start,
+        });
+    }
+
+    fn heading(&mut self, level: usize, title: &str) {
+        self.end_paragraph();
+        self.flush();
+        while self.headings.last().is_some_and(|(l, _)| *l >= level) {
+            self.headings.pop();
+        }
+        self.headings.push((level, title.to_string()));
+    }
+
+    fn finish(mut self) -> Vec<Chunk> {
+        self.end_paragraph();
+        self.flush();
+        self.chunks
+    }
+}
+
+/// Text that is embedded for a chunk: where it is, then what it says
+pub fn embedding_text(path: &str, chunk: &Chunk) -> String {
+    format!("{}\n{}\n\n{}", path, chunk.heading, chunk.content)
+}
+
+/// Compare the scanned files with the index
+pub fn plan(conn: &Connection, files: Vec<RunbookFile>, model: &str) -> Result<IndexPlan> {
+    let mut stmt = conn.prepare("SELECT path, hash, model FROM runbook_docs")?;
+    let indexed = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, (row.get::<_, String>(1)?, row.get::<_, String>(2)?))))?
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let present: HashSet<String> = files.iter().map(|f| f.path.clone()).collect();
+    let mut plan = IndexPlan {
+        removed: indexed.keys().filter(|path| !present.contains(*path)).cloned().collect(),
+        ..Default::default()
+    };
+    for file in files {
+        match indexed.get(&file.path) {
+            Some((hash, indexed_model)) if *hash == file.hash && indexed_model == model => plan.unchanged += 1,
+            _ => plan.changed.push(file),
+        }
+    }
+    plan.removed.sort();
+    Ok(plan)
+}
+
+pub fn remove_document(conn: &Connection, path: &str) -> Result<()> {
+    conn.execute("DELETE FROM runbook_chunks WHERE path = ?1", params![path])?;
+    conn.execute("DELETE FROM runbook_docs WHERE path = ?1", params![path])?;
+    Ok(())
+}
+
+/// Replace a file's chunks with freshly embedded ones
+pub fn store_document(
+    conn: &Connection,
+    file: &RunbookFile,
+    model: &str,
+    chunks: &[Chunk],
+    embeddings: &[Vec<f32>],
+    now: i64,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    remove_document(&tx, &file.path)?;
+    tx.execute(
+        "INSERT INTO runbook_docs (path, hash, model, chunk_count, indexed_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![file.path, file.hash, model, chunks.len() as i64, now],
+    )?;
+    for (chunk, vector) in chunks.iter().zip(embeddings) {
+        tx.execute(
+            "INSERT INTO runbook_chunks (path, heading, content, start_line, embedding) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![file.path, chunk.heading, chunk.content, chunk.start_line as i64, embedding::to_blob(vector)],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Whether runbooks are turned on and have chunks embedded with `model` to search
+pub fn has_index(conn: &Connection, model: &str) -> Result<bool> {
+    if get_dir(conn)?.is_none() {
+        return Ok(false);
+    }
+    conn.query_row("SELECT EXISTS(SELECT 1 FROM runbook_docs WHERE model = ?1 AND chunk_count > 0)", params![model], |row| {
+        row.get(0)
+    })
+}
+
+/// The chunks closest to `query`, best first
+pub fn search(conn: &Connection, query: &[f32], model: &str, limit: usize, min_score: f32) -> Result<Vec<RunbookHit>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.path, c.heading, c.content, c.start_line, c.embedding
+         FROM runbook_chunks c JOIN runbook_docs d ON d.path = c.path
+         WHERE d.model = ?1",
+    )?;
+    let rows = stmt.query_map(params![model], |row| {
+        let blob: Vec<u8> = row.get(4)?;
+        Ok(RunbookHit {
+            path: row.get(0)?,
+            heading: row.get(1)?,
+            content: row.get(2)?,
+            start_line: row.get::<_, i64>(3)? as usize,
+            score: embedding::cosine(query, &embedding::from_blob(&blob)),
+        })
+    })?;
+
+    let mut hits = Vec::new();
+    for hit in rows {
+        let hit = hit?;
+        if hit.score >= min_score {
+            hits.push(hit);
+        }
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Numbered passages for the prompt within `max_chars`, and the sources they cite
+pub fn context_section(hits: &[RunbookHit], max_chars: usize) -> (String, Vec<RunbookSource>) {
+    let mut section = String::from("Team runbook passages (use them when relevant and cite them as [n]):\n");
+    let mut sources = Vec::new();
+    let mut remaining = max_chars;
+
+    for hit in hits {
+        let index = sources.len() + 1;
+        let title = if hit.heading.is_empty() {
+            format!("[{}] {} (line {})", index, hit.path, hit.start_line)
+        } else {
+            format!("[{}] {} > {} (line {})", index, hit.path, hit.heading, hit.start_line)
+        };
+        let available = remaining.saturating_sub(title.len() + 2);
+        if available < 200 {
+            break;
+        }
+        let content: String = hit.content.chars().take(available).collect();
+        remaining = remaining.saturating_sub(title.len() + content.len() + 2);
+        section.push_str(&format!("\n{}\n{}\n", title, content));
+        sources.push(RunbookSource {
+            index,
+            path: hit.path.clone(),
+            heading: hit.heading.clone(),
+            start_line: hit.start_line,
+            score: hit.score,
+        });
+    }
+
+    if sources.is_empty() {
+        (String::new(), sources)
+    } else {
+        (section, sources)
+    }
+}
+
+pub fn status(conn: &Connection) -> Result<IndexStatus> {
+    let (documents, chunks, indexed_at): (i64, Option<i64>, Option<i64>) = conn.query_row(
+        "SELECT COUNT(*), SUM(chunk_count), MAX(indexed_at) FROM runbook_docs",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let model = conn
+        .query_row("SELECT model FROM runbook_docs ORDER BY indexed_at DESC LIMIT 1", [], |row| row.get(0))
+        .optional()?;
+    Ok(IndexStatus {
+        dir: get_dir(conn)?,
+        documents: documents as usize,
+        chunks: chunks.unwrap_or(0) as usize,
+        model,
+        indexed_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::MockProvider;
+    use crate::db;
+
+    #[test]
+    fn test_chunk_markdown() {
+        let text = "Intro line\n\n# Deploy\n\nSteps below.\n\n## Rollback\n\n```bash\nkubectl rollout undo deploy/api\n\nkubectl get pods\n```\n\n# Alerts ##\nPage the on-call.\n";
+        let chunks = chunk_markdown(text);
+
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0], Chunk { heading: String::new(), content: "Intro line".into(), start_line: 1 });
+        assert_eq!(chunks[1].heading, "Deploy");
+        assert_eq!(chunks[1].start_line, 5);
+        // A code block stays whole across its blank line
+        assert_eq!(chunks[2].heading, "Deploy > Rollback");
+        assert!(chunks[2].content.starts_with("```bash") && chunks[2].content.ends_with("```"));
+        assert_eq!(chunks[2].start_line, 9);
+        assert_eq!(chunks[3].heading, "Alerts");
+
+        // A long section is split between paragraphs
+        let long = format!("# Big\n\n{}\n\n{}\n", "a".repeat(1000), "b".repeat(1000));
+        let chunks = chunk_markdown(&long);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].start_line, 5);
+    }
+
+    #[test]
+    fn test_index_and_search() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+
+        let file = |path: &str, content: &str| RunbookFile {
+            path: path.into(),
+            content: content.into(),
+            hash: content_hash(content),
+        };
+        let files = vec![
+            file("db/restore.md", "# Restore postgres backup\n\nRun pg_restore against the replica first.\n"),
+            file("web/nginx.md", "# Reload nginx\n\nRun nginx -t then systemctl reload nginx.\n"),
+        ];
+
+        let todo = plan(&conn, files.clone(), "mock").unwrap();
+        assert_eq!(todo.changed.len(), 2);
+        for f in &todo.changed {
+            let chunks = chunk_markdown(&f.content);
+            let embeddings: Vec<_> = chunks.iter().map(|c| MockProvider::embedding(&embedding_text(&f.path, c))).collect();
+            store_document(&conn, f, "mock", &chunks, &embeddings, 100).unwrap();
+        }
+        assert!(!has_index(&conn, "mock").unwrap(), "runbooks are off without a directory");
+        set_dir(&conn, "/srv/runbooks").unwrap();
+        assert!(has_index(&conn, "mock").unwrap());
+        assert!(!has_index(&conn, "other").unwrap());
+
+        let query = MockProvider::embedding("how do I restore the postgres backup");
+        let hits = search(&conn, &query, "mock", SEARCH_LIMIT, 0.1).unwrap();
+        assert_eq!(hits[0].path, "db/restore.md");
+        assert_eq!(hits[0].heading, "Restore postgres backup");
+
+        let (section, sources) = context_section(&hits[..1], CONTEXT_CHARS);
+        assert!(section.contains("[1] db/restore.md > Restore postgres backup (line 3)"));
+        assert_eq!(sources[0].index, 1);
+
+        // Unchanged files are skipped, deleted ones removed
+        let todo = plan(&conn, files[..1].to_vec(), "mock").unwrap();
+        assert_eq!((todo.changed.len(), todo.unchanged), (0, 1));
+        assert_eq!(todo.removed, vec!["web/nginx.md".to_string()]);
+        remove_document(&conn, "web/nginx.md").unwrap();
+        assert_eq!(status(&conn).unwrap().documents, 1);
+        // A new embedding model re-embeds everything
+        assert_eq!(plan(&conn, files[..1].to_vec(), "bge-m3").unwrap().changed.len(), 1);
+
+        // Turning runbooks off drops the index
+        set_dir(&conn, "").unwrap();
+        assert!(!has_index(&conn, "mock").unwrap());
+        assert_eq!(status(&conn).unwrap().documents, 0);
+    }
+}
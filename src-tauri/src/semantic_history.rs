@@ -0,0 +1,313 @@
+//! Natural-language history search
+//!
+//! Distinct commands (`history_stats`) are embedded with the configured
+//! embedding model (`ai::AIConfig::embedding_model`) in batches; only commands
+//! without a vector for that model are sent (`pending`), so indexing after new
+//! history is cheap. A query like "that command I used to clean docker images
+//! last month" is embedded the same way and ranked against the profile's
+//! commands by cosine similarity. A time phrase in the query ("last month",
+//! "지난주") is taken out before embedding and limits the results to commands
+//! used since then.
+//!
+//! This complements the prefix (`history::search_history`) and fuzzy searches,
+//! which need the command's own words.
+
+use regex::Regex;
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::embedding;
+
+/// Commands sent to the embedding API per request (commands are short)
+pub const EMBED_BATCH: usize = 64;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// A command ranked for a natural-language query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticMatch {
+    pub cmd: String,
+    pub frequency: i32,
+    pub last_used: i64,
+    pub score: f32,
+}
+
+/// Progress of the embedding index
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexProgress {
+    /// Commands embedded by this call
+    pub embedded: usize,
+    /// Commands still without a vector for the current model
+    pub remaining: usize,
+}
+
+lazy_static::lazy_static! {
+    static ref RELATIVE_TIME: Regex = Regex::new(
+        r"(?i)\b(\d+)\s*(day|week|month|year)s?\s+ago\b|(\d+)\s*(일|주|개월|달|년)\s*전"
+    ).unwrap();
+    static ref NAMED_TIME: Regex = Regex::new(
+        r"(?i)\b(today|yesterday|(?:this|last|past)\s+(?:week|month|year))\b|오늘|어제|(?:이번|지난)\s*(?:주|달)|올해|작년"
+    ).unwrap();
+}
+
+/// Split a time phrase off the query: the rest of the query, and the earliest
+/// `last_used` it allows. Windows are generous ("last week" is the past two
+/// weeks) since people remember roughly when they ran something.
+pub fn parse_time(query: &str, now: i64) -> (String, Option<i64>) {
+    let (range, days) = if let Some(caps) = RELATIVE_TIME.captures(query) {
+        let (count, unit) = match caps.get(1) {
+            Some(count) => (count.as_str(), caps.get(2).map(|m| m.as_str().to_lowercase())),
+            None => (&caps[3], caps.get(4).map(|m| m.as_str().to_string())),
+        };
+        let unit_days = match unit.as_deref() {
+            Some("week" | "주") => 7,
+            Some("month" | "개월" | "달") => 31,
+            Some("year" | "년") => 366,
+            _ => 1,
+        };
+        let count: i64 = count.parse().unwrap_or(1);
+        (caps.get(0).unwrap().range(), (count + 1) * unit_days)
+    } else if let Some(m) = NAMED_TIME.find(query) {
+        let phrase = m.as_str().to_lowercase();
+        let days = match phrase.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["today"] | ["오늘"] => 1,
+            ["yesterday"] | ["어제"] => 2,
+            [_, "week"] => 14,
+            [_, "month"] => 62,
+            ["this", "year"] | ["올해"] => 366,
+            [_, "year"] | ["작년"] => 2 * 366,
+            _ if phrase.ends_with('주') => 14,
+            _ => 62,
+        };
+        (m.range(), days)
+    } else {
+        return (query.trim().to_string(), None);
+    };
+
+    let rest = format!("{} {}", &query[..range.start], &query[range.end..]);
+    (rest.split_whitespace().collect::<Vec<_>>().join(" "), Some(now - days * DAY))
+}
+
+/// Commands without a vector for `model`, most recently used first
+pub fn pending(conn: &Connection, model: &str, limit: usize) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.cmd FROM history_stats s
+         WHERE NOT EXISTS (SELECT 1 FROM history_embeddings e WHERE e.cmd = s.cmd AND e.model = ?1)
+         GROUP BY s.cmd
+         ORDER BY MAX(s.last_used) DESC
+         LIMIT ?2",
+    )?;
+    let cmds = stmt
+        .query_map(params![model, limit as i64], |row| row.get(0))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(cmds)
+}
+
+pub fn pending_count(conn: &Connection, model: &str) -> Result<usize> {
+    conn.query_row(
+        "SELECT COUNT(DISTINCT s.cmd) FROM history_stats s
+         WHERE NOT EXISTS (SELECT 1 FROM history_embeddings e WHERE e.cmd = s.cmd AND e.model = ?1)",
+        params![model],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count as usize)
+}
+
+/// Save vectors of commands still in history; returns how many were saved
+/// (history may have been cleared or scrubbed while the batch was being embedded)
+pub fn store(conn: &Connection, model: &str, cmds: &[String], embeddings: &[Vec<f32>]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut stored = 0;
+    for (cmd, vector) in cmds.iter().zip(embeddings) {
+        stored += tx.execute(
+            "INSERT OR REPLACE INTO history_embeddings (cmd, model, embedding)
+             SELECT ?1, ?2, ?3 WHERE EXISTS (SELECT 1 FROM history_stats WHERE cmd = ?1)",
+            params![cmd, model, embedding::to_blob(vector)],
+        )?;
+    }
+    tx.commit()?;
+    Ok(stored)
+}
+
+/// Drop vectors of another model or of commands no longer in history
+pub fn prune(conn: &Connection, model: &str) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM history_embeddings
+         WHERE model != ?1 OR cmd NOT IN (SELECT cmd FROM history_stats)",
+        params![model],
+    )
+}
+
+/// Drop vectors of commands no longer in history, whatever the model
+/// (after history is cleared or scrubbed, so removed command text doesn't stay on disk)
+pub fn remove_orphans(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM history_embeddings WHERE cmd NOT IN (SELECT cmd FROM history_stats)",
+        [],
+    )
+}
+
+/// The profile's commands closest to `query`, best first
+pub fn search(
+    conn: &Connection,
+    profile_id: &str,
+    query: &[f32],
+    model: &str,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Vec<SemanticMatch>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.cmd, s.frequency, s.last_used, e.embedding
+         FROM history_stats s JOIN history_embeddings e ON e.cmd = s.cmd AND e.model = ?2
+         WHERE s.profile_id = ?1 AND s.last_used >= ?3",
+    )?;
+    let rows = stmt.query_map(params![profile_id, model, since.unwrap_or(i64::MIN)], |row| {
+        let blob: Vec<u8> = row.get(3)?;
+        Ok(SemanticMatch {
+            cmd: row.get(0)?,
+            frequency: row.get(1)?,
+            last_used: row.get(2)?,
+            score: embedding::cosine(query, &embedding::from_blob(&blob)),
+        })
+    })?;
+
+    let mut matches = rows.collect::<Result<Vec<_>>>()?;
+    matches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.frequency.cmp(&a.frequency))
+            .then(b.last_used.cmp(&a.last_used))
+    });
+    matches.truncate(limit);
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::MockProvider;
+    use crate::{db, history, redact};
+
+    #[test]
+    fn test_parse_time() {
+        let now = 100 * DAY;
+        assert_eq!(
+            parse_time("that command I used to clean docker images last month", now),
+            ("that command I used to clean docker images".to_string(), Some(now - 62 * DAY))
+        );
+        assert_eq!(parse_time("nginx reload 3 days ago", now), ("nginx reload".to_string(), Some(now - 4 * DAY)));
+        assert_eq!(parse_time("어제 쓴 도커 정리 명령", now), ("쓴 도커 정리 명령".to_string(), Some(now - 2 * DAY)));
+        assert_eq!(parse_time("지난주 배포", now).1, Some(now - 14 * DAY));
+        assert_eq!(parse_time("restart postgres", now), ("restart postgres".to_string(), None));
+    }
+
+    #[test]
+    fn test_index_and_search() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES ('p1', 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [],
+        )
+        .unwrap();
+        let day = DAY;
+        for (cmd, last_used) in [
+            ("docker image prune -a", 90 * day),
+            ("docker ps", 99 * day),
+            ("systemctl restart nginx", 20 * day),
+        ] {
+            conn.execute(
+                "INSERT INTO history_stats (profile_id, cmd, frequency, last_used, frecency_key) VALUES ('p1', ?1, 1, ?2, 0)",
+                params![cmd, last_used],
+            )
+            .unwrap();
+        }
+
+        let todo = pending(&conn, "mock", EMBED_BATCH).unwrap();
+        assert_eq!(todo[0], "docker ps");
+        let vectors: Vec<_> = todo.iter().map(|c| MockProvider::embedding(c)).collect();
+        store(&conn, "mock", &todo, &vectors).unwrap();
+        assert_eq!(pending_count(&conn, "mock").unwrap(), 0);
+        assert_eq!(pending_count(&conn, "other").unwrap(), 3);
+
+        let (text, since) = parse_time("restart nginx last month", 100 * day);
+        let hits = search(&conn, "p1", &MockProvider::embedding(&text), "mock", None, 5).unwrap();
+        assert_eq!(hits[0].cmd, "systemctl restart nginx");
+        // Used 80 days ago: outside "last month"
+        let hits = search(&conn, "p1", &MockProvider::embedding(&text), "mock", since, 5).unwrap();
+        assert!(hits.iter().all(|h| h.cmd != "systemctl restart nginx"));
+
+        conn.execute("DELETE FROM history_stats WHERE cmd = 'docker ps'", []).unwrap();
+        assert_eq!(prune(&conn, "mock").unwrap(), 1);
+    }
+
+    fn embedded(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT cmd FROM history_embeddings ORDER BY cmd")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_embeddings_follow_history_removal() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+        for id in ["p1", "p2"] {
+            conn.execute(
+                "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+                 VALUES (?1, 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+                [id],
+            )
+            .unwrap();
+        }
+        let save = |profile_id: &str, cmd: &str| {
+            history::save_history(
+                &conn,
+                history::SaveHistoryInput {
+                    profile_id: profile_id.into(),
+                    cmd: cmd.into(),
+                    exit_code: Some(0),
+                    duration_ms: None,
+                    cwd: None,
+                    hostname: None,
+                    session_id: None,
+                    shell: None,
+                },
+            )
+            .unwrap();
+        };
+        let embed_pending = || {
+            let todo = pending(&conn, "mock", EMBED_BATCH).unwrap();
+            let vectors: Vec<_> = todo.iter().map(|c| MockProvider::embedding(c)).collect();
+            store(&conn, "mock", &todo, &vectors).unwrap()
+        };
+        save("p1", "deploy --key=abc");
+        save("p1", "vault kv get secret/db");
+        save("p1", "ls");
+        save("p2", "uptime");
+        assert_eq!(embed_pending(), 4);
+
+        // A batch embedded before its commands were removed is not stored afterwards
+        let stale = pending(&conn, "other", EMBED_BATCH).unwrap();
+        history::clear_history(&conn, "p2").unwrap();
+        let vectors: Vec<_> = stale.iter().map(|c| MockProvider::embedding(c)).collect();
+        assert_eq!(store(&conn, "other", &stale, &vectors).unwrap(), 3);
+        assert_eq!(embedded(&conn), vec!["deploy --key=abc", "deploy --key=abc", "ls", "ls", "vault kv get secret/db", "vault kv get secret/db"]);
+
+        // Scrubbing drops the vectors of the original (secret) text
+        redact::set_rules(&conn, &[
+            redact::RedactionRule { name: "key".into(), pattern: r"--key=\S+".into(), replacement: None, ignore: false },
+            redact::RedactionRule { name: "vault".into(), pattern: r"^vault\b".into(), replacement: None, ignore: true },
+        ])
+        .unwrap();
+        redact::scrub_history(&conn).unwrap();
+        assert_eq!(embedded(&conn), vec!["ls", "ls"]);
+
+        embed_pending();
+        history::clear_all_history(&conn).unwrap();
+        assert!(embedded(&conn).is_empty());
+    }
+}
@@ -0,0 +1,729 @@
+//! Shell history file formats (bash, zsh, fish)
+//!
+//! Used to import a server's existing history so suggestions are useful from the
+//! first connection. Timestamps are kept when the file has them (bash with
+//! HISTTIMEFORMAT, zsh EXTENDED_HISTORY, fish); otherwise commands are dated
+//! just before the file's modification time, oldest first.
+//!
+//! History can also be exported to and imported from files: bash (with
+//! `#<epoch>` lines), zsh extended history, JSON lines and CSV. JSON lines and
+//! CSV carry everything we record (exit code, duration, cwd, host, shell).
+
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::history::{self, HistoryEntry};
+use crate::redact::Redactor;
+
+/// Lines fetched from the end of each remote history file
+const REMOTE_TAIL_LINES: usize = 5000;
+/// Bytes fetched at most per file, so all five candidates and their markers fit
+/// in the exec output kept by `ssh::exec_capture` (256 KiB) and no marker is cut off
+const REMOTE_TAIL_BYTES: usize = 48 * 1024;
+const FILE_MARKER: &str = "@@AIT_HISTORY ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryFormat {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl HistoryFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HistoryFormat::Bash => "bash",
+            HistoryFormat::Zsh => "zsh",
+            HistoryFormat::Fish => "fish",
+        }
+    }
+
+    /// Guess the format from a history file path
+    pub fn from_path(path: &str) -> Option<Self> {
+        if path.ends_with("fish_history") {
+            Some(HistoryFormat::Fish)
+        } else if path.contains("zsh") || path.ends_with(".zhistory") || path.ends_with(".histfile") {
+            Some(HistoryFormat::Zsh)
+        } else if path.contains("bash") || path.ends_with(".history") {
+            Some(HistoryFormat::Bash)
+        } else {
+            None
+        }
+    }
+}
+
+/// File format for history export/import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Bash,
+    Zsh,
+    Jsonl,
+    Csv,
+}
+
+impl ExportFormat {
+    /// Guess the format from a file name
+    pub fn from_path(path: &str) -> Option<Self> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".jsonl") || lower.ends_with(".ndjson") || lower.ends_with(".json") {
+            Some(ExportFormat::Jsonl)
+        } else if lower.ends_with(".csv") {
+            Some(ExportFormat::Csv)
+        } else {
+            match HistoryFormat::from_path(path)? {
+                HistoryFormat::Bash => Some(ExportFormat::Bash),
+                HistoryFormat::Zsh => Some(ExportFormat::Zsh),
+                HistoryFormat::Fish => None,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedCommand {
+    pub cmd: String,
+    pub ts: Option<i64>,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i32>,
+    pub cwd: Option<String>,
+    pub hostname: Option<String>,
+    pub shell: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub files: usize,
+    pub parsed: usize,
+    pub imported: usize,
+    pub skipped_duplicates: usize,
+}
+
+pub fn parse(format: HistoryFormat, content: &str) -> Vec<ParsedCommand> {
+    match format {
+        HistoryFormat::Bash => parse_bash(content),
+        HistoryFormat::Zsh => parse_zsh(content),
+        HistoryFormat::Fish => parse_fish(content),
+    }
+}
+
+/// One command per line; `#<epoch>` lines (HISTTIMEFORMAT) date the next command,
+/// and everything up to the next `#<epoch>` line belongs to it (bash writes
+/// multi-line commands as is)
+pub fn parse_bash(content: &str) -> Vec<ParsedCommand> {
+    let mut commands: Vec<ParsedCommand> = Vec::new();
+    let mut pending_ts = None;
+    let mut in_dated = false;
+
+    for line in content.lines() {
+        if let Some(ts) = line.strip_prefix('#').and_then(|t| t.trim().parse::<i64>().ok()) {
+            pending_ts = Some(ts);
+            in_dated = false;
+            continue;
+        }
+        match commands.last_mut() {
+            Some(last) if in_dated => {
+                last.cmd.push('\n');
+                last.cmd.push_str(line);
+            }
+            _ if line.trim().is_empty() => {}
+            _ => {
+                in_dated = pending_ts.is_some();
+                commands.push(ParsedCommand {
+                    cmd: line.to_string(),
+                    ts: pending_ts.take(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    // Blank lines before the next timestamp aren't part of the command
+    for c in commands.iter_mut().filter(|c| c.cmd.contains('\n')) {
+        c.cmd.truncate(c.cmd.trim_end().len());
+    }
+    commands
+}
+
+/// Plain lines or EXTENDED_HISTORY `: <epoch>:<duration>;<command>`,
+/// with multi-line commands continued by a trailing backslash
+pub fn parse_zsh(content: &str) -> Vec<ParsedCommand> {
+    let mut commands: Vec<ParsedCommand> = Vec::new();
+    let mut continuing = false;
+
+    for line in content.lines() {
+        if continuing {
+            if let Some(last) = commands.last_mut() {
+                last.cmd.push('\n');
+                last.cmd.push_str(line.strip_suffix('\\').unwrap_or(line));
+            }
+            continuing = line.ends_with('\\');
+            continue;
+        }
+
+        let (ts, duration, cmd) = match parse_zsh_extended(line) {
+            Some((ts, duration, cmd)) => (Some(ts), duration, cmd),
+            None => (None, 0, line),
+        };
+        continuing = cmd.ends_with('\\');
+        let cmd = cmd.strip_suffix('\\').unwrap_or(cmd);
+
+        if cmd.trim().is_empty() && !continuing {
+            continue;
+        }
+        commands.push(ParsedCommand {
+            cmd: cmd.to_string(),
+            ts,
+            // 0 means "under a second" or "not recorded"
+            duration_ms: Some(duration.saturating_mul(1000)).filter(|d| *d > 0),
+            ..Default::default()
+        });
+    }
+
+    commands
+}
+
+/// (timestamp, duration in seconds, command)
+fn parse_zsh_extended(line: &str) -> Option<(i64, i32, &str)> {
+    let rest = line.strip_prefix(": ")?;
+    let (meta, cmd) = rest.split_once(';')?;
+    let (ts, duration) = meta.split_once(':')?;
+    if !duration.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((ts.trim().parse().ok()?, duration.parse().unwrap_or(0), cmd))
+}
+
+/// fish_history is YAML-like: `- cmd: <command>` followed by `  when: <epoch>`
+pub fn parse_fish(content: &str) -> Vec<ParsedCommand> {
+    let mut commands: Vec<ParsedCommand> = Vec::new();
+
+    for line in content.lines() {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            commands.push(ParsedCommand {
+                cmd: unescape_fish(cmd),
+                ts: None,
+                ..Default::default()
+            });
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some(last) = commands.last_mut() {
+                last.ts = when.trim().parse().ok();
+            }
+        }
+    }
+
+    commands
+}
+
+fn unescape_fish(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Remote script printing the tail of every readable history file, each preceded
+/// by a marker line with the file's mtime and path
+/// A file larger than `REMOTE_TAIL_BYTES` is read from that many bytes before its
+/// end, dropping the partial first line
+pub fn fetch_script() -> String {
+    format!(
+        "for f in \"${{HISTFILE:-}}\" ~/.bash_history ~/.zsh_history ~/.zhistory ~/.local/share/fish/fish_history; do \
+         [ -n \"$f\" ] && [ -r \"$f\" ] && {{ echo \"{marker}$(stat -c %Y \"$f\" 2>/dev/null || date +%s) $f\"; \
+         if [ \"$(wc -c < \"$f\")\" -gt {bytes} ]; then tail -c {bytes} \"$f\" | tail -n +2; else cat \"$f\"; fi | tail -n {lines}; echo; }}; \
+         done 2>/dev/null",
+        marker = FILE_MARKER,
+        bytes = REMOTE_TAIL_BYTES,
+        lines = REMOTE_TAIL_LINES,
+    )
+}
+
+/// A history file found in the output of `fetch_script`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedFile {
+    pub path: String,
+    pub mtime: i64,
+    pub format: HistoryFormat,
+    pub content: String,
+}
+
+/// Split the output of `fetch_script` into files (same path listed twice is kept once)
+pub fn split_fetched(output: &str) -> Vec<FetchedFile> {
+    let mut files: Vec<FetchedFile> = Vec::new();
+    let mut current: Option<FetchedFile> = None;
+
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix(FILE_MARKER) {
+            files.extend(current.take());
+            let (mtime, path) = header.split_once(' ').unwrap_or(("0", header));
+            current = HistoryFormat::from_path(path).map(|format| FetchedFile {
+                path: path.to_string(),
+                mtime: mtime.parse().unwrap_or(0),
+                format,
+                content: String::new(),
+            });
+        } else if let Some(file) = current.as_mut() {
+            file.content.push_str(line);
+            file.content.push('\n');
+        }
+    }
+    files.extend(current);
+
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|f| seen.insert(f.path.clone()));
+    files
+}
+
+/// Give undated commands increasing timestamps ending at `fallback_end`
+fn assign_timestamps(commands: &mut [ParsedCommand], fallback_end: i64) {
+    let undated = commands.iter().filter(|c| c.ts.is_none()).count() as i64;
+    let mut next = fallback_end - undated;
+    for c in commands.iter_mut() {
+        if c.ts.is_none() {
+            next += 1;
+            c.ts = Some(next);
+        }
+    }
+}
+
+/// Merge parsed commands into a profile's history, skipping ones already recorded
+/// Dated commands are duplicates if the same command exists at the same time;
+/// undated ones if the profile already has the command at all
+pub fn import_commands(
+    conn: &Connection,
+    profile_id: &str,
+    default_shell: Option<&str>,
+    mut commands: Vec<ParsedCommand>,
+    fallback_end: i64,
+    summary: &mut ImportSummary,
+) -> Result<()> {
+    summary.parsed += commands.len();
+
+    let undated: std::collections::HashSet<String> = commands
+        .iter()
+        .filter(|c| c.ts.is_none())
+        .map(|c| c.cmd.clone())
+        .collect();
+    assign_timestamps(&mut commands, fallback_end);
+    commands.sort_by_key(|c| c.ts);
+
+    let redactor = Redactor::load(conn)?;
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut exact = tx.prepare("SELECT 1 FROM history WHERE profile_id = ?1 AND cmd = ?2 AND ts = ?3")?;
+        let mut any = tx.prepare("SELECT 1 FROM history WHERE profile_id = ?1 AND cmd = ?2")?;
+
+        for c in commands {
+            let Some(cmd) = redactor.filter(&c.cmd) else {
+                continue;
+            };
+            let cmd = cmd.trim().to_string();
+            let ts = c.ts.unwrap_or(fallback_end);
+            let duplicate = if undated.contains(&c.cmd) {
+                any.exists(params![profile_id, cmd])?
+            } else {
+                exact.exists(params![profile_id, cmd, ts])?
+            };
+            if cmd.trim().is_empty() || duplicate {
+                summary.skipped_duplicates += 1;
+                continue;
+            }
+
+            history::insert_entry(&tx, &HistoryEntry {
+                id: Uuid::new_v4().to_string(),
+                profile_id: profile_id.to_string(),
+                cmd,
+                ts,
+                exit_code: c.exit_code,
+                duration_ms: c.duration_ms,
+                cwd: c.cwd,
+                hostname: c.hostname,
+                session_id: None,
+                shell: c.shell.or_else(|| default_shell.map(str::to_string)),
+            })?;
+            summary.imported += 1;
+        }
+    }
+    tx.commit()
+}
+
+/// Import the output of `fetch_script` into a profile's history
+pub fn import_fetched(conn: &Connection, profile_id: &str, output: &str) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+
+    for file in split_fetched(output) {
+        let commands = parse(file.format, &file.content);
+        import_commands(conn, profile_id, Some(file.format.name()), commands, file.mtime, &mut summary)?;
+        summary.files += 1;
+    }
+
+    println!(
+        "[HistoryImport] profile={} files={} imported={} duplicates={}",
+        profile_id, summary.files, summary.imported, summary.skipped_duplicates
+    );
+    Ok(summary)
+}
+
+/// One exported command (JSON lines and CSV)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub ts: i64,
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    pub cmd: String,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub duration_ms: Option<i32>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub shell: Option<String>,
+}
+
+const CSV_COLUMNS: [&str; 8] = ["ts", "profile_id", "cmd", "exit_code", "duration_ms", "cwd", "hostname", "shell"];
+
+/// Stored history of one profile (or all profiles), oldest first
+/// Compacted history (see `retention`) has no per-use rows and is not exported
+pub fn load_records(conn: &Connection, profile_id: Option<&str>) -> Result<Vec<ExportRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT ts, profile_id, cmd, exit_code, duration_ms, cwd, hostname, shell
+         FROM history
+         WHERE ?1 IS NULL OR profile_id = ?1
+         ORDER BY ts ASC, rowid ASC",
+    )?;
+    let records = stmt
+        .query_map(params![profile_id], |row| {
+            Ok(ExportRecord {
+                ts: row.get(0)?,
+                profile_id: row.get(1)?,
+                cmd: row.get(2)?,
+                exit_code: row.get(3)?,
+                duration_ms: row.get(4)?,
+                cwd: row.get(5)?,
+                hostname: row.get(6)?,
+                shell: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(records)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render records in the given format
+pub fn export_records(format: ExportFormat, records: &[ExportRecord]) -> String {
+    let mut out = String::new();
+    match format {
+        ExportFormat::Bash => {
+            for r in records {
+                out.push_str(&format!("#{}\n{}\n", r.ts, r.cmd));
+            }
+        }
+        ExportFormat::Zsh => {
+            for r in records {
+                let duration = r.duration_ms.unwrap_or(0).max(0) / 1000;
+                // zsh continues multi-line commands with a trailing backslash
+                out.push_str(&format!(": {}:{};{}\n", r.ts, duration, r.cmd.replace('\n', "\\\n")));
+            }
+        }
+        ExportFormat::Jsonl => {
+            for r in records {
+                out.push_str(&serde_json::to_string(r).unwrap_or_default());
+                out.push('\n');
+            }
+        }
+        ExportFormat::Csv => {
+            out.push_str(&CSV_COLUMNS.join(","));
+            out.push_str("\r\n");
+            for r in records {
+                let opt = |v: &Option<String>| csv_field(v.as_deref().unwrap_or(""));
+                let num = |v: Option<i32>| v.map(|n| n.to_string()).unwrap_or_default();
+                let fields = [
+                    r.ts.to_string(),
+                    opt(&r.profile_id),
+                    csv_field(&r.cmd),
+                    num(r.exit_code),
+                    num(r.duration_ms),
+                    opt(&r.cwd),
+                    opt(&r.hostname),
+                    opt(&r.shell),
+                ];
+                out.push_str(&fields.join(","));
+                out.push_str("\r\n");
+            }
+        }
+    }
+    out
+}
+
+/// Parse RFC 4180 CSV (quoted fields may contain commas, quotes and newlines)
+fn parse_csv_rows(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+    rows
+}
+
+fn parse_csv(content: &str) -> std::result::Result<Vec<ParsedCommand>, String> {
+    let mut rows = parse_csv_rows(content).into_iter();
+    let header = rows.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|h| h.trim() == name);
+    let cmd_col = column("cmd").ok_or("CSV has no 'cmd' column")?;
+    let (ts_col, exit_col, duration_col) = (column("ts"), column("exit_code"), column("duration_ms"));
+    let (cwd_col, host_col, shell_col) = (column("cwd"), column("hostname"), column("shell"));
+
+    let mut commands = Vec::new();
+    for row in rows {
+        let get = |col: Option<usize>| {
+            col.and_then(|i| row.get(i))
+                .map(|v| v.to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let Some(cmd) = get(Some(cmd_col)) else {
+            continue;
+        };
+        commands.push(ParsedCommand {
+            cmd,
+            ts: get(ts_col).and_then(|v| v.trim().parse().ok()),
+            exit_code: get(exit_col).and_then(|v| v.trim().parse().ok()),
+            duration_ms: get(duration_col).and_then(|v| v.trim().parse().ok()),
+            cwd: get(cwd_col),
+            hostname: get(host_col),
+            shell: get(shell_col),
+        });
+    }
+    Ok(commands)
+}
+
+fn parse_jsonl(content: &str) -> std::result::Result<Vec<ParsedCommand>, String> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let r: ExportRecord =
+                serde_json::from_str(line).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+            Ok(ParsedCommand {
+                cmd: r.cmd,
+                ts: Some(r.ts),
+                exit_code: r.exit_code,
+                duration_ms: r.duration_ms,
+                cwd: r.cwd,
+                hostname: r.hostname,
+                shell: r.shell,
+            })
+        })
+        .collect()
+}
+
+/// Parse an exported (or shell-written) history file
+pub fn parse_export(format: ExportFormat, content: &str) -> std::result::Result<Vec<ParsedCommand>, String> {
+    match format {
+        ExportFormat::Bash => Ok(parse_bash(content)),
+        ExportFormat::Zsh => Ok(parse_zsh(content)),
+        ExportFormat::Jsonl => parse_jsonl(content),
+        ExportFormat::Csv => parse_csv(content),
+    }
+}
+
+/// Import a history file into a profile (undated commands end at `fallback_end`)
+pub fn import_file_content(
+    conn: &Connection,
+    profile_id: &str,
+    format: ExportFormat,
+    content: &str,
+    fallback_end: i64,
+) -> std::result::Result<ImportSummary, String> {
+    let commands = parse_export(format, content)?;
+    let default_shell = match format {
+        ExportFormat::Bash => Some("bash"),
+        ExportFormat::Zsh => Some("zsh"),
+        ExportFormat::Jsonl | ExportFormat::Csv => None,
+    };
+
+    let mut summary = ImportSummary {
+        files: 1,
+        ..Default::default()
+    };
+    import_commands(conn, profile_id, default_shell, commands, fallback_end, &mut summary)
+        .map_err(|e| e.to_string())?;
+
+    println!(
+        "[HistoryImport] profile={} format={:?} imported={} duplicates={}",
+        profile_id, format, summary.imported, summary.skipped_duplicates
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db;
+
+    #[test]
+    fn test_parse_history_formats() {
+        let bash = parse_bash("ls -la\n\ncd /srv\n#1700000000\ngit status\n#1700000005\nfor f in *; do\n  echo $f\ndone\n\n");
+        assert_eq!(bash.len(), 4);
+        assert_eq!(bash[1].ts, None);
+        assert_eq!(bash[2], ParsedCommand { cmd: "git status".into(), ts: Some(1_700_000_000), ..Default::default() });
+        assert_eq!(bash[3].cmd, "for f in *; do\n  echo $f\ndone");
+        assert_eq!(bash[3].ts, Some(1_700_000_005));
+
+        let zsh = parse_zsh(": 1700000000:0;ls\n: 1700000005:2;for f in *; do\\\n  echo $f\\\ndone\nuptime\n");
+        assert_eq!(zsh.len(), 3);
+        assert_eq!(zsh[1].cmd, "for f in *; do\n  echo $f\ndone");
+        assert_eq!(zsh[1].ts, Some(1_700_000_005));
+        assert_eq!(zsh[1].duration_ms, Some(2000));
+        assert_eq!(zsh[2], ParsedCommand { cmd: "uptime".into(), ts: None, ..Default::default() });
+
+        let fish = parse_fish("- cmd: echo a\\nb\n  when: 1700000000\n- cmd: ls\n  when: 1700000010\n  paths:\n    - /tmp\n");
+        assert_eq!(fish[0], ParsedCommand { cmd: "echo a\nb".into(), ts: Some(1_700_000_000), ..Default::default() });
+        assert_eq!(fish[1].ts, Some(1_700_000_010));
+    }
+
+    #[test]
+    fn test_import_fetched_deduplicates() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+             VALUES ('p1', 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+            [],
+        ).unwrap();
+
+        let output = format!(
+            "{m}1700001000 /home/u/.bash_history\nls\ncd /srv\nls\n\n\
+             {m}1700002000 /home/u/.zsh_history\n: 1700000000:0;git pull\n: 1700000100:0;make\n",
+            m = FILE_MARKER
+        );
+
+        let first = import_fetched(&conn, "p1", &output).unwrap();
+        assert_eq!(first.files, 2);
+        assert_eq!(first.parsed, 5);
+        assert_eq!(first.imported, 4); // the second undated "ls" is a duplicate
+
+        let second = import_fetched(&conn, "p1", &output).unwrap();
+        assert_eq!(second.imported, 0);
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM history", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 4);
+        let shell: String = conn
+            .query_row("SELECT shell FROM history WHERE cmd = 'make'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(shell, "zsh");
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        for id in ["p1", "p2"] {
+            conn.execute(
+                "INSERT INTO profiles (id, name, host, port, user, auth_type, created_at, updated_at)
+                 VALUES (?1, 'Test Server', 'localhost', 22, 'testuser', 'password', 0, 0)",
+                [id],
+            ).unwrap();
+        }
+
+        let commands = [
+            ParsedCommand { cmd: "ls -la".into(), ts: Some(1_700_000_000), exit_code: Some(0), duration_ms: Some(12), ..Default::default() },
+            ParsedCommand {
+                cmd: "echo \"a, b\" > out.csv".into(),
+                ts: Some(1_700_000_010),
+                exit_code: Some(1),
+                duration_ms: Some(3000),
+                cwd: Some("/srv/app".into()),
+                shell: Some("bash".into()),
+                ..Default::default()
+            },
+            ParsedCommand { cmd: "for f in *; do\n  echo $f\ndone".into(), ts: Some(1_700_000_020), ..Default::default() },
+        ];
+        import_commands(&conn, "p1", None, commands.to_vec(), 0, &mut ImportSummary::default()).unwrap();
+        let records = load_records(&conn, Some("p1")).unwrap();
+        assert_eq!(records.len(), 3);
+
+        for format in [ExportFormat::Jsonl, ExportFormat::Csv, ExportFormat::Zsh, ExportFormat::Bash] {
+            conn.execute("DELETE FROM history WHERE profile_id = 'p2'", []).unwrap();
+            let exported = export_records(format, &records);
+            let summary = import_file_content(&conn, "p2", format, &exported, 0).unwrap();
+            assert_eq!(summary.imported, 3, "{:?}", format);
+
+            let imported = load_records(&conn, Some("p2")).unwrap();
+            let cmds: Vec<(&str, i64)> = imported.iter().map(|r| (r.cmd.as_str(), r.ts)).collect();
+            let expected: Vec<(&str, i64)> = records.iter().map(|r| (r.cmd.as_str(), r.ts)).collect();
+            assert_eq!(cmds, expected, "{:?}", format);
+
+            match format {
+                ExportFormat::Jsonl | ExportFormat::Csv => {
+                    assert_eq!(imported[1].exit_code, Some(1));
+                    assert_eq!(imported[1].duration_ms, Some(3000));
+                    assert_eq!(imported[1].cwd.as_deref(), Some("/srv/app"));
+                }
+                ExportFormat::Zsh => assert_eq!(imported[1].duration_ms, Some(3000)),
+                ExportFormat::Bash => assert_eq!(imported[1].duration_ms, None),
+            }
+
+            // Importing the same file again adds nothing
+            let again = import_file_content(&conn, "p2", format, &exported, 0).unwrap();
+            assert_eq!(again.imported, 0);
+        }
+
+        let bash = export_records(ExportFormat::Bash, &records[..2]);
+        assert_eq!(bash, "#1700000000\nls -la\n#1700000010\necho \"a, b\" > out.csv\n");
+        assert_eq!(load_records(&conn, None).unwrap().len(), 6);
+        assert_eq!(ExportFormat::from_path("history.ndjson"), Some(ExportFormat::Jsonl));
+        assert_eq!(ExportFormat::from_path("/home/u/.zsh_history"), Some(ExportFormat::Zsh));
+    }
+}
@@ -0,0 +1,568 @@
+//! Shell integration: OSC 133 prompt marks and OSC 7 working directory
+//!
+//! Shells that emit semantic prompt marks let us observe commands directly:
+//!   OSC 133;A  prompt start        OSC 133;B  prompt end (input starts)
+//!   OSC 133;C  command executed    OSC 133;D;<exit>  command finished
+//!   OSC 633;E;<cmd>  exact command line (VS Code convention, `;` escaped as \x3b)
+//!   OSC 633;P;<key>=<value>  shell property (we use `Shell=bash|zsh`)
+//!   OSC 7;file://<host><path>  current working directory and hostname
+
+use base64::Engine;
+use std::time::Instant;
+
+/// Maximum OSC payload we buffer before treating the sequence as garbage
+const MAX_OSC_LEN: usize = 8192;
+/// Maximum echoed input we keep while waiting for a command to start
+const MAX_ECHO_LEN: usize = 4096;
+/// Output kept per command (the end, where errors usually are)
+const MAX_OUTPUT_LEN: usize = 16384;
+
+/// Snippet that makes bash/zsh emit the marks above (idempotent)
+const INTEGRATION_SCRIPT: &str = r#"if [ -z "$__AIT_SHELL_INTEGRATION" ]; then
+__AIT_SHELL_INTEGRATION=1
+__ait_esc() { local c="$1"; c="${c//\\/\\\\}"; c="${c//;/\\x3b}"; printf '%s' "$c"; }
+if [ -n "$ZSH_VERSION" ]; then
+  __ait_precmd() { local s=$?; printf '\033]133;D;%s\007\033]7;file://%s%s\007\033]133;A\007' "$s" "$HOST" "$PWD"; }
+  __ait_preexec() { printf '\033]633;E;%s\007\033]133;C\007' "$(__ait_esc "$1")"; }
+  autoload -Uz add-zsh-hook
+  add-zsh-hook precmd __ait_precmd
+  add-zsh-hook preexec __ait_preexec
+  printf '\033]633;P;Shell=zsh\007'
+  PS1="$PS1%{$(printf '\033]133;B\007')%}"
+elif [ -n "$BASH_VERSION" ]; then
+  __ait_histno() { HISTTIMEFORMAT= history 1 | sed -n '1s/^ *\([0-9]*\).*/\1/p'; }
+  __ait_precmd() { local s=$?; __ait_running=; __ait_last=$(__ait_histno); printf '\033]133;D;%s\007\033]7;file://%s%s\007\033]133;A\007' "$s" "$HOSTNAME" "$PWD"; }
+  __ait_preexec() {
+    [ -n "$__ait_running" ] && return
+    [ "$BASH_COMMAND" = "__ait_precmd" ] && return
+    __ait_running=1
+    # Not added to history (ignorespace, ignoredups, HISTIGNORE): history 1 is the previous
+    # command, so send no command line and let the echoed input be used
+    if [ "$(__ait_histno)" = "$__ait_last" ]; then printf '\033]133;C\007'; return; fi
+    # Strip only "  123  " so a leading space typed by the user is kept
+    printf '\033]633;E;%s\007\033]133;C\007' "$(__ait_esc "$(HISTTIMEFORMAT= history 1 | sed '1s/^ *[0-9]*[* ] //')")"
+  }
+  PROMPT_COMMAND="__ait_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+  PS1="$PS1\[\033]133;B\007\]"
+  trap '__ait_preexec' DEBUG
+  printf '\033]633;P;Shell=bash\007'
+fi
+fi
+"#;
+
+/// Build the line written to the shell to install the integration snippet
+/// (leading space keeps it out of the remote history)
+pub fn injection_command() -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(INTEGRATION_SCRIPT);
+    format!(" eval \"$(echo {} | base64 -d)\"\n", encoded)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellEvent {
+    PromptStart,
+    CommandStart,
+    CommandExecuted,
+    CommandFinished { exit_code: Option<i32> },
+    CommandLine(String),
+    Property { key: String, value: String },
+    Cwd { host: Option<String>, path: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Event(ShellEvent),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParseState {
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+/// Streaming OSC parser (sequences may be split across reads)
+/// Everything that is not an OSC sequence is passed through as text
+#[derive(Debug)]
+pub struct OscParser {
+    state: ParseState,
+    payload: String,
+}
+
+impl Default for OscParser {
+    fn default() -> Self {
+        Self {
+            state: ParseState::Ground,
+            payload: String::new(),
+        }
+    }
+}
+
+impl OscParser {
+    pub fn feed(&mut self, data: &str) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+
+        for c in data.chars() {
+            match self.state {
+                ParseState::Ground => {
+                    if c == '\x1b' {
+                        self.state = ParseState::Escape;
+                    } else {
+                        text.push(c);
+                    }
+                }
+                ParseState::Escape => {
+                    if c == ']' {
+                        self.state = ParseState::Osc;
+                        self.payload.clear();
+                    } else {
+                        text.push('\x1b');
+                        text.push(c);
+                        self.state = ParseState::Ground;
+                    }
+                }
+                ParseState::Osc => match c {
+                    '\x07' => {
+                        self.finish_osc(&mut text, &mut segments);
+                    }
+                    '\x1b' => self.state = ParseState::OscEscape,
+                    _ => {
+                        self.payload.push(c);
+                        if self.payload.len() > MAX_OSC_LEN {
+                            self.payload.clear();
+                            self.state = ParseState::Ground;
+                        }
+                    }
+                },
+                ParseState::OscEscape => {
+                    if c == '\\' {
+                        self.finish_osc(&mut text, &mut segments);
+                    } else {
+                        self.payload.push('\x1b');
+                        self.payload.push(c);
+                        self.state = ParseState::Osc;
+                    }
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        segments
+    }
+
+    fn finish_osc(&mut self, text: &mut String, segments: &mut Vec<Segment>) {
+        self.state = ParseState::Ground;
+        if let Some(event) = parse_osc(&self.payload) {
+            if !text.is_empty() {
+                segments.push(Segment::Text(std::mem::take(text)));
+            }
+            segments.push(Segment::Event(event));
+        }
+        self.payload.clear();
+    }
+}
+
+/// Interpret an OSC payload (without ESC ] and terminator)
+fn parse_osc(payload: &str) -> Option<ShellEvent> {
+    let (code, rest) = payload.split_once(';')?;
+
+    match code {
+        "133" | "633" => {
+            let mut fields = rest.split(';');
+            match fields.next()? {
+                "A" => Some(ShellEvent::PromptStart),
+                "B" => Some(ShellEvent::CommandStart),
+                "C" => Some(ShellEvent::CommandExecuted),
+                "D" => Some(ShellEvent::CommandFinished {
+                    exit_code: fields.next().and_then(|s| s.trim().parse().ok()),
+                }),
+                "E" => Some(ShellEvent::CommandLine(unescape_command(
+                    fields.next().unwrap_or(""),
+                ))),
+                "P" => {
+                    let (key, value) = fields.next()?.split_once('=')?;
+                    Some(ShellEvent::Property {
+                        key: key.to_string(),
+                        value: unescape_command(value),
+                    })
+                }
+                _ => None,
+            }
+        }
+        "7" => {
+            let uri = rest.strip_prefix("file://")?;
+            // The hostname runs up to the first '/', where the path starts
+            let (host, path) = uri.split_at(uri.find('/')?);
+            Some(ShellEvent::Cwd {
+                host: (!host.is_empty()).then(|| host.to_string()),
+                path: percent_decode(path),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Undo `\\` and `\xHH` escaping used by OSC 633;E
+fn unescape_command(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('\\') => {
+                chars.next();
+                out.push('\\');
+            }
+            Some('x') => {
+                chars.next();
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) => out.push(b as char),
+                    Err(_) => {
+                        out.push_str("\\x");
+                        out.push_str(&hex);
+                    }
+                }
+            }
+            _ => out.push('\\'),
+        }
+    }
+
+    out
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Remove ANSI escape sequences (CSI, OSC and two-byte escapes)
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // CSI: parameters until a final byte in 0x40..=0x7e
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                // OSC: until BEL or ESC \
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/// Reconstruct a typed command from echoed terminal output
+/// (used when the shell doesn't report the command line via OSC 633;E)
+fn clean_echo(echoed: &str) -> String {
+    let mut out = String::new();
+    for c in strip_ansi(echoed).chars() {
+        match c {
+            '\x08' | '\x7f' => {
+                out.pop();
+            }
+            '\r' | '\n' => {}
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    // A leading space is kept: it marks a command that must not be recorded
+    out.trim_end().to_string()
+}
+
+/// Drop the start of `s` so that at most `max` bytes remain
+fn keep_tail(s: &mut String, max: usize) {
+    if s.len() > max {
+        let mut cut = s.len() - max;
+        while !s.is_char_boundary(cut) {
+            cut += 1;
+        }
+        s.drain(..cut);
+    }
+}
+
+/// A command observed from start to finish
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedCommand {
+    pub cmd: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: i64,
+    pub cwd: Option<String>,
+    pub hostname: Option<String>,
+    pub shell: Option<String>,
+    /// Raw output between the C and D marks, at most MAX_OUTPUT_LEN bytes from the end
+    pub output: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackerEvent {
+    CwdChanged(String),
+    CommandCompleted(CompletedCommand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    Prompt,
+    Input,
+    Running,
+}
+
+/// Per-session state machine turning prompt marks into completed commands
+#[derive(Debug)]
+pub struct CommandTracker {
+    parser: OscParser,
+    phase: Phase,
+    echoed: String,
+    output: String,
+    command_line: Option<String>,
+    started_at: Option<Instant>,
+    cwd: Option<String>,
+    command_cwd: Option<String>,
+    hostname: Option<String>,
+    shell: Option<String>,
+}
+
+impl Default for CommandTracker {
+    fn default() -> Self {
+        Self {
+            parser: OscParser::default(),
+            phase: Phase::Idle,
+            echoed: String::new(),
+            output: String::new(),
+            command_line: None,
+            started_at: None,
+            cwd: None,
+            command_cwd: None,
+            hostname: None,
+            shell: None,
+        }
+    }
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(&mut self, data: &str) -> Vec<TrackerEvent> {
+        let mut events = Vec::new();
+
+        for segment in self.parser.feed(data) {
+            match segment {
+                Segment::Text(text) => {
+                    if self.phase == Phase::Input && self.echoed.len() < MAX_ECHO_LEN {
+                        self.echoed.push_str(&text);
+                    }
+                    if self.phase == Phase::Running {
+                        self.output.push_str(&text);
+                        if self.output.len() > 2 * MAX_OUTPUT_LEN {
+                            keep_tail(&mut self.output, MAX_OUTPUT_LEN);
+                        }
+                    }
+                }
+                Segment::Event(event) => self.handle_event(event, &mut events),
+            }
+        }
+
+        events
+    }
+
+    fn handle_event(&mut self, event: ShellEvent, events: &mut Vec<TrackerEvent>) {
+        match event {
+            ShellEvent::PromptStart => self.phase = Phase::Prompt,
+            ShellEvent::CommandStart => {
+                self.phase = Phase::Input;
+                self.echoed.clear();
+                self.command_line = None;
+            }
+            ShellEvent::CommandLine(cmd) => self.command_line = Some(cmd),
+            ShellEvent::CommandExecuted => {
+                self.phase = Phase::Running;
+                self.output.clear();
+                self.started_at = Some(Instant::now());
+                self.command_cwd = self.cwd.clone();
+            }
+            ShellEvent::CommandFinished { exit_code } => {
+                if self.phase == Phase::Running {
+                    // A leading space is kept on both paths (it marks a command not to record)
+                    let cmd = match self.command_line.take().filter(|cmd| !cmd.trim().is_empty()) {
+                        Some(cmd) => cmd.trim_end().to_string(),
+                        None => clean_echo(&self.echoed),
+                    };
+                    let duration_ms = self
+                        .started_at
+                        .take()
+                        .map(|t| t.elapsed().as_millis() as i64)
+                        .unwrap_or(0);
+
+                    let mut output = std::mem::take(&mut self.output);
+                    keep_tail(&mut output, MAX_OUTPUT_LEN);
+
+                    if !cmd.is_empty() {
+                        events.push(TrackerEvent::CommandCompleted(CompletedCommand {
+                            cmd,
+                            exit_code,
+                            duration_ms,
+                            cwd: self.command_cwd.take(),
+                            hostname: self.hostname.clone(),
+                            shell: self.shell.clone(),
+                            output,
+                        }));
+                    }
+                }
+                self.phase = Phase::Idle;
+                self.echoed.clear();
+            }
+            ShellEvent::Property { key, value } => {
+                if key == "Shell" {
+                    self.shell = Some(value);
+                }
+            }
+            ShellEvent::Cwd { host, path } => {
+                if host.is_some() {
+                    self.hostname = host;
+                }
+                if self.cwd.as_deref() != Some(path.as_str()) {
+                    self.cwd = Some(path.clone());
+                    events.push(TrackerEvent::CwdChanged(path));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::Redactor;
+
+    #[test]
+    fn test_parser_handles_split_sequences() {
+        let mut parser = OscParser::default();
+        let mut segments = parser.feed("hello\x1b]13");
+        segments.extend(parser.feed("3;D;127\x07world\x1b]7;file://web1/tmp/my%20dir\x1b\\"));
+
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("hello".to_string()),
+                Segment::Event(ShellEvent::CommandFinished { exit_code: Some(127) }),
+                Segment::Text("world".to_string()),
+                Segment::Event(ShellEvent::Cwd {
+                    host: Some("web1".to_string()),
+                    path: "/tmp/my dir".to_string(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tracker_with_command_line_mark() {
+        let mut tracker = CommandTracker::new();
+        tracker.process("\x1b]633;P;Shell=bash\x07");
+        tracker.process("\x1b]133;D;0\x07\x1b]7;file://web1/srv/app\x07\x1b]133;A\x07$ \x1b]133;B\x07");
+        tracker.process("make test\r\n");
+        let events = tracker.process(
+            "\x1b]633;E;make test && echo a\\x3bb\x07\x1b]133;C\x07output\r\n\x1b]133;D;2\x07",
+        );
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            TrackerEvent::CommandCompleted(cmd) => {
+                assert_eq!(cmd.cmd, "make test && echo a;b");
+                assert_eq!(cmd.exit_code, Some(2));
+                assert_eq!(cmd.cwd.as_deref(), Some("/srv/app"));
+                assert_eq!(cmd.hostname.as_deref(), Some("web1"));
+                assert_eq!(cmd.shell.as_deref(), Some("bash"));
+                assert_eq!(cmd.output, "output\r\n");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tracker_falls_back_to_echo() {
+        let mut tracker = CommandTracker::new();
+        tracker.process("\x1b]133;A\x07$ \x1b]133;B\x07");
+        tracker.process("ls -lx\x08 \x08a\x1b[K\r\n");
+        let events = tracker.process("\x1b]133;C\x07total 0\r\n\x1b]133;D;0\x07");
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            TrackerEvent::CommandCompleted(cmd) => assert_eq!(cmd.cmd, "ls -la"),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tracker_keeps_space_prefix_private() {
+        let redactor = Redactor::new(&[], true).unwrap();
+        let mut tracker = CommandTracker::new();
+        tracker.process("\x1b]133;A\x07$ \x1b]133;B\x07 export TOKEN=x\r\n");
+        let events = tracker.process("\x1b]633;E; export TOKEN=x\x07\x1b]133;C\x07\x1b]133;D;0\x07");
+
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            TrackerEvent::CommandCompleted(cmd) => {
+                assert_eq!(cmd.cmd, " export TOKEN=x");
+                assert_eq!(redactor.filter(&cmd.cmd), None);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1;32mok\x1b[0m \x1b]0;title\x07done"),
+            "ok done"
+        );
+    }
+}
@@ -1,19 +1,46 @@
 use ssh2::Session;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::{Read, Write},
     net::TcpStream,
     sync::{Arc, Mutex},
     thread,
     time::Duration,
 };
-use tauri::{command, Emitter, WebviewWindow, Runtime};
+use serde::Serialize;
+use tauri::{command, Emitter, Manager, WebviewWindow, Runtime};
 use uuid::Uuid;
 
+use crate::history;
+use crate::profile;
+use crate::remote_complete;
+use crate::risk::{self, InputLine};
+use crate::shell_history;
+use crate::shell_integration::{self, CommandTracker, CompletedCommand, TrackerEvent};
+use crate::terminal_context::{self, Scrollback, TerminalSnapshot};
+
 struct ShellSession {
     #[allow(dead_code)]
     sess: Session,
     channel: ssh2::Channel,
+    profile_id: Option<String>,
+    cwd: Option<String>, // Reported by the shell via OSC 7
+    scrollback: Scrollback,
+    os_info: Option<String>, // Kernel and distribution, read at connect time
+    last_command: Option<(String, Option<i32>)>,
+    recent_outputs: VecDeque<(String, String)>, // (history id, plain output) of recorded commands
+    input: InputLine, // Line being typed, checked by the risk classifier on Enter
+}
+
+/// Command outputs kept per session for error explanations
+const RECENT_OUTPUTS: usize = 20;
+
+/// Per-session outcome of a broadcast write
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastResult {
+    pub id: String,
+    pub ok: bool,
+    pub error: Option<String>,
 }
 
 lazy_static::lazy_static! {
@@ -21,19 +48,16 @@ lazy_static::lazy_static! {
         Mutex::new(HashMap::new());
 }
 
-// 여기서 AppHandle 말고 WebviewWindow 받는다!
-#[command]
-pub fn ssh_open_shell(
-    window: WebviewWindow,
-    host: String,
-    port: u16,
-    user: String,
-    password: String,
-    cols: Option<u32>,
-    rows: Option<u32>,
-) -> Result<String, String> {
-    println!("[ssh_open_shell] start");
+/// Output of a non-interactive command run on its own exec channel
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
 
+/// Open a TCP connection and authenticate a new SSH session (blocking mode)
+pub fn connect(host: &str, port: u16, user: &str, password: &str) -> Result<Session, String> {
     let addr = format!("{}:{}", host, port);
     let tcp =
         TcpStream::connect(&addr).map_err(|e| format!("TCP connect error to {}: {}", addr, e))?;
@@ -45,12 +69,120 @@ pub fn ssh_open_shell(
     sess.set_tcp_stream(tcp);
     sess.handshake()
         .map_err(|e| format!("SSH handshake error: {}", e))?;
-    sess.userauth_password(&user, &password)
+    sess.userauth_password(user, password)
         .map_err(|e| format!("SSH auth error: {}", e))?;
     if !sess.authenticated() {
         return Err("SSH authentication failed".into());
     }
 
+    Ok(sess)
+}
+
+/// Run a command on a blocking session and collect stdout, stderr and exit code
+/// The session is switched to non-blocking for the exec (libssh2 blocking reads
+/// ignore the socket timeout), so a command that never ends fails after `timeout`
+pub fn exec_capture(sess: &Session, command: &str, timeout: Duration) -> Result<ExecOutput, String> {
+    let deadline = std::time::Instant::now() + timeout;
+    sess.set_blocking(false);
+    let result = retry("failed to open exec channel", deadline, || sess.channel_session())
+        .and_then(|mut channel| run_exec(&mut channel, command, deadline, timeout));
+    sess.set_blocking(true);
+    result
+}
+
+fn would_block(e: &ssh2::Error) -> bool {
+    let msg = e.to_string();
+    msg.contains("Would block") || msg.contains("EAGAIN") || e.code() == ssh2::ErrorCode::Session(-37)
+}
+
+/// Retry a non-blocking ssh2 call until it stops returning EAGAIN or the deadline passes
+fn retry<T>(
+    what: &str,
+    deadline: std::time::Instant,
+    mut f: impl FnMut() -> Result<T, ssh2::Error>,
+) -> Result<T, String> {
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(e) if would_block(&e) && std::time::Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(20));
+            }
+            Err(e) => return Err(format!("{}: {}", what, e)),
+        }
+    }
+}
+
+/// Exec `command` on a non-blocking channel, reading stdout and stderr in turn
+/// (the end of each is kept, up to `MAX_EXEC_OUTPUT`) until EOF or the deadline
+fn run_exec(
+    channel: &mut ssh2::Channel,
+    command: &str,
+    deadline: std::time::Instant,
+    timeout: Duration,
+) -> Result<ExecOutput, String> {
+    retry("failed to exec command", deadline, || channel.exec(command))?;
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let mut progressed = false;
+        for (stream, out) in [(0, &mut stdout), (1, &mut stderr)] {
+            match channel.stream(stream).read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    out.extend_from_slice(&buf[..n]);
+                    if out.len() > MAX_EXEC_OUTPUT {
+                        out.drain(..out.len() - MAX_EXEC_OUTPUT);
+                    }
+                    progressed = true;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("failed to read output: {}", e)),
+            }
+        }
+        if channel.eof() && !progressed {
+            break;
+        }
+        if std::time::Instant::now() >= deadline {
+            let _ = channel.close();
+            return Err(format!("command timed out after {}s", timeout.as_secs()));
+        }
+        if !progressed {
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    retry("failed to close channel", deadline, || channel.wait_close())?;
+    let exit_code = channel
+        .exit_status()
+        .map_err(|e| format!("failed to get exit status: {}", e))?;
+
+    Ok(ExecOutput {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_code,
+    })
+}
+
+// 여기서 AppHandle 말고 WebviewWindow 받는다!
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn ssh_open_shell(
+    window: WebviewWindow,
+    host: String,
+    port: u16,
+    user: String,
+    password: String,
+    cols: Option<u32>,
+    rows: Option<u32>,
+    profile_id: Option<String>,
+    shell_integration: Option<bool>,
+) -> Result<String, String> {
+    println!("[ssh_open_shell] start");
+
+    let sess = connect(&host, port, &user, &password)?;
+
     let mut channel = sess
         .channel_session()
         .map_err(|e| format!("failed to open channel: {}", e))?;
@@ -71,11 +203,30 @@ pub fn ssh_open_shell(
         .shell()
         .map_err(|e| format!("failed to start shell: {}", e))?;
 
+    // Install OSC 133/7 prompt marks so commands can be recorded accurately
+    if shell_integration.unwrap_or(false) {
+        channel
+            .write_all(shell_integration::injection_command().as_bytes())
+            .map_err(|e| format!("failed to inject shell integration: {}", e))?;
+    }
+
     // 세션 전체 non-blocking
     sess.set_blocking(false);
 
     let id = Uuid::new_v4().to_string();
-    let shell = Arc::new(Mutex::new(ShellSession { sess, channel }));
+    let profile_for_thread = profile_id.clone();
+    let profile_for_setup = profile_id.clone();
+    let shell = Arc::new(Mutex::new(ShellSession {
+        sess,
+        channel,
+        profile_id,
+        cwd: None,
+        scrollback: Scrollback::new(),
+        os_info: None,
+        last_command: None,
+        recent_outputs: VecDeque::new(),
+        input: InputLine::default(),
+    }));
     {
         let mut map = SHELLS.lock().unwrap();
         map.insert(id.clone(), shell.clone());
@@ -91,6 +242,7 @@ pub fn ssh_open_shell(
         let mut buf = [0u8; 4096]; // 1KB → 4KB로 증가
         let mut output_buffer = String::new();
         let mut last_emit = std::time::Instant::now();
+        let mut tracker = CommandTracker::new();
 
         // Helper function to check if we should flush the buffer immediately
         let should_flush_immediately = |buffer: &str| -> bool {
@@ -110,6 +262,8 @@ pub fn ssh_open_shell(
                 Ok(g) => g,
                 Err(_) => break,
             };
+            // Recorded after the guard is released so writes and resizes don't wait on the DB
+            let mut completed = Vec::new();
 
             match guard.channel.read(&mut buf) {
                 Ok(0) => {
@@ -137,6 +291,18 @@ pub fn ssh_open_shell(
                 }
                 Ok(n) => {
                     let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
+
+                    for event in tracker.process(&chunk) {
+                        match event {
+                            TrackerEvent::CwdChanged(cwd) => guard.cwd = Some(cwd),
+                            TrackerEvent::CommandCompleted(command) => {
+                                guard.last_command = Some((command.cmd.clone(), command.exit_code));
+                                completed.push(command);
+                            }
+                        }
+                    }
+
+                    guard.scrollback.push(&chunk);
                     output_buffer.push_str(&chunk);
 
                     // Smart buffering: flush if needed
@@ -189,21 +355,260 @@ pub fn ssh_open_shell(
             }
 
             drop(guard);
+
+            for command in completed {
+                let history_id = record_command(
+                    &win_for_thread,
+                    &id_for_thread,
+                    profile_for_thread.as_deref(),
+                    &command,
+                );
+                if let Some(history_id) = history_id {
+                    let output = terminal_context::plain_text(&command.output);
+                    let Ok(mut guard) = shell_for_thread.lock() else {
+                        break;
+                    };
+                    guard.recent_outputs.push_back((history_id, output));
+                    if guard.recent_outputs.len() > RECENT_OUTPUTS {
+                        guard.recent_outputs.pop_front();
+                    }
+                }
+            }
+
             thread::sleep(Duration::from_millis(10));
         }
     });
 
+    // History import and OS info run over the open session so the shell isn't held up by them
+    let win_for_setup = window.clone();
+    let id_for_setup = id.clone();
+    thread::spawn(move || {
+        let timeout = Duration::from_secs(EXEC_TIMEOUT_SECS);
+
+        // Opt-in: merge the server's existing shell history
+        if let Some(pid) = profile_for_setup.filter(|pid| remote_history_enabled(&win_for_setup, pid)) {
+            match exec_in_session(&id_for_setup, &shell_history::fetch_script(), timeout) {
+                Ok(output) => import_remote_history(win_for_setup.clone(), pid, output.stdout),
+                Err(e) => println!("[ssh_open_shell] history fetch failed: {}", e),
+            }
+        }
+
+        // OS info for the AI context (best effort)
+        let os_info = exec_in_session(&id_for_setup, terminal_context::OS_INFO_COMMAND, timeout)
+            .ok()
+            .and_then(|output| terminal_context::parse_os_info(&output.stdout));
+        let shell = SHELLS.lock().unwrap().get(&id_for_setup).cloned();
+        if let Some(shell) = shell {
+            if let Ok(mut shell) = shell.lock() {
+                shell.os_info = os_info;
+            }
+        }
+    });
+
     println!("[ssh_open_shell] return id={}", id);
     Ok(id)
 }
 
+fn remote_history_enabled(window: &WebviewWindow, profile_id: &str) -> bool {
+    let state = window.state::<crate::AppState>();
+    let db_guard = state.db.lock().unwrap();
+    db_guard
+        .as_ref()
+        .and_then(|conn| profile::get_profile(conn, profile_id).ok().flatten())
+        .is_some_and(|p| p.import_remote_history)
+}
+
+/// Import fetched history files in the background and notify the frontend
+fn import_remote_history(window: WebviewWindow, profile_id: String, output: String) {
+    thread::spawn(move || {
+        let state = window.state::<crate::AppState>();
+        let db_guard = state.db.lock().unwrap();
+        let Some(conn) = db_guard.as_ref() else {
+            return;
+        };
+
+        match shell_history::import_fetched(conn, &profile_id, &output) {
+            Ok(summary) => {
+                let _ = window.emit_to(
+                    window.label(),
+                    "history:imported",
+                    serde_json::json!({ "profile_id": profile_id, "summary": summary }),
+                );
+            }
+            Err(e) => println!("[HistoryImport] failed for profile {}: {}", profile_id, e),
+        }
+    });
+}
+
+/// Save a command observed through shell integration and notify the frontend
+/// Returns the id of the history entry, if one was stored
+fn record_command(
+    window: &WebviewWindow,
+    session_id: &str,
+    profile_id: Option<&str>,
+    command: &CompletedCommand,
+) -> Option<String> {
+    // Files, branches and containers may have changed
+    remote_complete::forget_session(session_id);
+
+    let history_id = profile_id.and_then(|profile_id| save_command(window, session_id, profile_id, command));
+
+    let _ = window.emit_to(
+        window.label(),
+        "ssh:command",
+        serde_json::json!({
+            "id": session_id,
+            "history_id": history_id,
+            "cmd": command.cmd,
+            "exit_code": command.exit_code,
+            "duration_ms": command.duration_ms,
+            "cwd": command.cwd,
+            "hostname": command.hostname,
+            "shell": command.shell,
+        }),
+    );
+
+    history_id
+}
+
+fn save_command(window: &WebviewWindow, session_id: &str, profile_id: &str, command: &CompletedCommand) -> Option<String> {
+    let state = window.state::<crate::AppState>();
+    let db_guard = state.db.lock().unwrap();
+    let conn = db_guard.as_ref()?;
+    let input = history::SaveHistoryInput {
+        profile_id: profile_id.to_string(),
+        cmd: command.cmd.clone(),
+        exit_code: command.exit_code,
+        duration_ms: Some(command.duration_ms.min(i32::MAX as i64) as i32),
+        cwd: command.cwd.clone(),
+        hostname: command.hostname.clone(),
+        session_id: Some(session_id.to_string()),
+        shell: command.shell.clone(),
+    };
+    match history::save_history(conn, input) {
+        Ok(entry) => entry.map(|e| e.id),
+        Err(e) => {
+            println!("[ssh_reader:{}] failed to save history: {}", session_id, e);
+            None
+        }
+    }
+}
+
+/// Write user input to a session
+/// A submitted line classified high-risk or worse is refused with a `risk::CONFIRMATION_REQUIRED` error
+/// unless `confirmed` is set
 #[command]
-pub fn ssh_write(id: String, data: String) -> Result<(), String> {
+pub fn ssh_write(id: String, data: String, confirmed: Option<bool>) -> Result<(), String> {
+    write_to_session(&id, &data, confirmed.unwrap_or(false))
+}
+
+/// Write the same input to several sessions (like tmux synchronize-panes)
+/// Failures are reported per session instead of aborting the whole broadcast
+#[command]
+pub fn ssh_broadcast(ids: Vec<String>, data: String, confirmed: Option<bool>) -> Result<Vec<BroadcastResult>, String> {
+    Ok(broadcast(&ids, &data, confirmed.unwrap_or(false)))
+}
+
+pub fn broadcast(ids: &[String], data: &str, confirmed: bool) -> Vec<BroadcastResult> {
+    ids.iter()
+        .map(|id| match write_to_session(id, data, confirmed) {
+            Ok(()) => BroadcastResult {
+                id: id.clone(),
+                ok: true,
+                error: None,
+            },
+            Err(e) => BroadcastResult {
+                id: id.clone(),
+                ok: false,
+                error: Some(e),
+            },
+        })
+        .collect()
+}
+
+/// Get the last working directory reported by a session's shell
+pub fn session_cwd(id: &str) -> Option<String> {
+    let shell = SHELLS.lock().unwrap().get(id)?.clone();
+    let shell = shell.lock().ok()?;
+    shell.cwd.clone()
+}
+
+/// Capture what the AI context is built from (scrollback, cwd, OS, last command)
+pub fn session_snapshot(id: &str, scrollback_lines: usize) -> Option<TerminalSnapshot> {
+    let shell = SHELLS.lock().unwrap().get(id)?.clone();
+    let shell = shell.lock().ok()?;
+    let (last_command, last_exit_code) = match &shell.last_command {
+        Some((cmd, exit_code)) => (Some(cmd.clone()), *exit_code),
+        None => (None, None),
+    };
+    Some(TerminalSnapshot {
+        profile_id: shell.profile_id.clone(),
+        scrollback: shell.scrollback.tail(scrollback_lines),
+        cwd: shell.cwd.clone(),
+        os_info: shell.os_info.clone(),
+        last_command,
+        last_exit_code,
+    })
+}
+
+/// Captured output of a recorded command, while its session is open and it is recent enough
+pub fn command_output(session_id: &str, history_id: &str) -> Option<String> {
+    let shell = SHELLS.lock().unwrap().get(session_id)?.clone();
+    let shell = shell.lock().ok()?;
+    shell
+        .recent_outputs
+        .iter()
+        .find(|(id, _)| id == history_id)
+        .map(|(_, output)| output.clone())
+}
+
+/// Get the profile an open session was started from
+pub fn session_profile(id: &str) -> Option<String> {
+    let shell = SHELLS.lock().unwrap().get(id)?.clone();
+    let shell = shell.lock().ok()?;
+    shell.profile_id.clone()
+}
+
+/// Get the ids of all open sessions that belong to one of the given profiles
+pub fn session_ids_for_profiles(profile_ids: &[String]) -> Vec<String> {
     let map = SHELLS.lock().unwrap();
-    let shell = map
-        .get(&id)
-        .ok_or_else(|| format!("session {} not found", id))?;
-    let mut shell = shell.lock().unwrap();
+    let mut ids: Vec<String> = map
+        .iter()
+        .filter(|(_, shell)| {
+            shell
+                .lock()
+                .map(|s| s.profile_id.as_ref().is_some_and(|p| profile_ids.contains(p)))
+                .unwrap_or(false)
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+    ids.sort();
+    ids
+}
+
+fn write_to_session(id: &str, data: &str, confirmed: bool) -> Result<(), String> {
+    let shell = {
+        let map = SHELLS.lock().unwrap();
+        map.get(id)
+            .ok_or_else(|| format!("session {} not found", id))?
+            .clone()
+    };
+    let mut shell = shell.lock().map_err(|_| format!("session {} is poisoned", id))?;
+
+    // Check what this write submits before it reaches the shell; nothing is written if refused
+    let mut input = shell.input.clone();
+    let submitted = input.feed(data);
+    if !confirmed {
+        let risky: Vec<risk::RiskReport> = submitted
+            .iter()
+            .map(|line| risk::assess(line))
+            .filter(|report| report.requires_confirmation)
+            .collect();
+        if !risky.is_empty() {
+            println!("[Risk] session {}: confirmation required for {:?}", id, risky[0].command);
+            return Err(risk::confirmation_error(&risky));
+        }
+    }
 
     shell
         .channel
@@ -213,6 +618,7 @@ pub fn ssh_write(id: String, data: String) -> Result<(), String> {
         .channel
         .flush()
         .map_err(|e| format!("flush error: {}", e))?;
+    shell.input = input;
 
     Ok(())
 }
@@ -240,6 +646,7 @@ pub fn ssh_close(id: String) -> Result<(), String> {
         if let Ok(mut s) = shell.lock() {
             let _ = s.channel.close();
         }
+        remote_complete::forget_session(&id);
         Ok(())
     } else {
         Err(format!("session {} not found", id))
@@ -250,56 +657,29 @@ pub fn ssh_close(id: String) -> Result<(), String> {
 /// Returns the command output as a string
 #[command]
 pub fn ssh_exec(id: String, command: String) -> Result<String, String> {
-    let map = SHELLS.lock().unwrap();
-    let shell = map
-        .get(&id)
-        .ok_or_else(|| format!("session {} not found", id))?
-        .clone();
-    drop(map); // Release the global lock early
-
-    // Retry opening a new exec channel if WouldBlock
-    let mut channel = None;
-    let max_retries = 5;
-
-    for attempt in 0..max_retries {
-        let shell_guard = shell.lock().unwrap();
-
-        match shell_guard.sess.channel_session() {
-            Ok(ch) => {
-                channel = Some(ch);
-                break;
-            }
-            Err(e) => {
-                // Check if error message contains "Would block" (ssh2::Error doesn't have kind())
-                let err_msg = format!("{}", e);
-                let is_would_block = err_msg.contains("Would block") || err_msg.contains("EAGAIN");
-
-                if is_would_block && attempt < max_retries - 1 {
-                    // Session is busy, wait a bit and retry
-                    drop(shell_guard);
-                    thread::sleep(Duration::from_millis(100));
-                    continue;
-                } else {
-                    return Err(format!("failed to open exec channel after {} attempts: {}", attempt + 1, e));
-                }
-            }
-        }
-    }
-
-    let mut channel = channel.ok_or_else(|| "failed to open exec channel".to_string())?;
+    exec_in_session(&id, &command, Duration::from_secs(EXEC_TIMEOUT_SECS)).map(|output| output.stdout)
+}
 
-    channel
-        .exec(&command)
-        .map_err(|e| format!("failed to exec command: {}", e))?;
+/// Default time limit of `ssh_exec` and the exec calls made while opening a shell
+const EXEC_TIMEOUT_SECS: u64 = 30;
 
-    let mut output = String::new();
-    channel
-        .read_to_string(&mut output)
-        .map_err(|e| format!("failed to read output: {}", e))?;
+/// Output kept per stream of a silent exec (the end is kept)
+const MAX_EXEC_OUTPUT: usize = 256 * 1024;
 
-    channel
-        .wait_close()
-        .map_err(|e| format!("failed to close channel: {}", e))?;
+/// Run a command on its own exec channel of an open session, next to the interactive shell
+/// The session is in non-blocking mode (the reader thread shares it), so every call retries on EAGAIN
+pub fn exec_in_session(id: &str, command: &str, timeout: Duration) -> Result<ExecOutput, String> {
+    let shell = SHELLS
+        .lock()
+        .unwrap()
+        .get(id)
+        .ok_or_else(|| format!("session {} not found", id))?
+        .clone();
+    let deadline = std::time::Instant::now() + timeout;
 
-    Ok(output)
+    // The reader thread shares the session: hold the shell lock only while opening the channel
+    let mut channel = retry("failed to open exec channel", deadline, || {
+        shell.lock().unwrap().sess.channel_session()
+    })?;
+    run_exec(&mut channel, command, deadline, timeout)
 }
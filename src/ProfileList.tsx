@@ -11,6 +11,7 @@ export interface ServerProfile {
   user: string;
   password?: string; // Database fallback when keyring unavailable
   profile_group?: string;
+  import_remote_history?: boolean;
   auth_type?: string;
   created_at?: number;
   updated_at?: number;
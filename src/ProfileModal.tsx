@@ -20,6 +20,7 @@ export const ProfileModal: React.FC<ProfileModalProps> = ({
     user: "",
     password: "",
     profile_group: "Default",
+    import_remote_history: false,
   });
   const [isSubmitting, setIsSubmitting] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -39,6 +40,7 @@ export const ProfileModal: React.FC<ProfileModalProps> = ({
           auth_type: "password",
           password: formData.password || null,
           profile_group: formData.profile_group || null,
+          import_remote_history: formData.import_remote_history,
         },
       });
 
@@ -50,6 +52,7 @@ export const ProfileModal: React.FC<ProfileModalProps> = ({
         user: "",
         password: "",
         profile_group: "Default",
+        import_remote_history: false,
       });
 
       onSuccess();
@@ -173,6 +176,18 @@ export const ProfileModal: React.FC<ProfileModalProps> = ({
             />
           </div>
 
+          <label className="flex items-center gap-2 text-sm text-gray-300">
+            <input
+              type="checkbox"
+              checked={formData.import_remote_history}
+              onChange={(e) =>
+                setFormData({ ...formData, import_remote_history: e.target.checked })
+              }
+              className="h-4 w-4 rounded border-gray-700 bg-gray-800/50"
+            />
+            Import shell history from this server on connect
+          </label>
+
           {error && (
             <div className="rounded-lg border border-red-500/20 bg-red-900/20 px-4 py-3 text-sm text-red-300">
               <div className="font-medium">Error</div>
@@ -6,12 +6,18 @@ import "xterm/css/xterm.css";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { ServerProfile } from "./ProfileList";
-import { Server, Trash2, Settings } from "lucide-react";
+import { Server, Trash2, Settings, Bot, Activity } from "lucide-react";
 import { useCommandInput } from "./hooks/useCommandInput";
 import { AutocompleteDropdown, CommandSuggestion } from "./components/AutocompleteDropdown";
 import { InlineOverlay } from "./components/InlineOverlay";
 import { AIPanel } from "./components/AIPanel";
+import { ExplainErrorPanel, FailedCommand } from "./components/ExplainErrorPanel";
 import { MacroSettings } from "./components/MacroSettings";
+import { AgentPanel } from "./components/AgentPanel";
+import { writeToSession } from "./risk";
+
+// settings 테이블 키: 새 세션에 셸 통합 스니펫을 주입할지 여부
+const SHELL_INTEGRATION_SETTING = "shell_integration";
 
 interface SshTerminalProps {
   profile: ServerProfile;
@@ -24,7 +30,8 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
   const sessionIdRef = useRef<string | null>(null); // ← 새로 추가: effect 안에서 쓸용
   const [sessionId, setSessionId] = useState<string | null>(null); // 화면에 보여줄 용도만
   const [fontSize, setFontSize] = useState<number>(14); // Default font size (16 → 14)
-  const [osInfo, setOsInfo] = useState<string>(''); // OS 정보
+  // 셸 통합(OSC 133/7 프롬프트 마크) 사용 여부 - 전역 설정, 다음 연결부터 적용
+  const [shellIntegration, setShellIntegration] = useState(false);
 
   // Autocomplete dropdown state
   const [showDropdown, setShowDropdown] = useState(false);
@@ -41,6 +48,13 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
   // AI Panel state
   const [showAIPanel, setShowAIPanel] = useState(false);
 
+  // Last failed command (offered for "explain this error") and the open explanation
+  const [lastFailure, setLastFailure] = useState<FailedCommand | null>(null);
+  const [explaining, setExplaining] = useState<FailedCommand | null>(null);
+
+  // Agent mode (multi-step tasks with approval)
+  const [showAgentPanel, setShowAgentPanel] = useState(false);
+
   // Macros state
   const [macros, setMacros] = useState<Record<string, string>>({});
   const [showMacroSettings, setShowMacroSettings] = useState(false);
@@ -53,6 +67,8 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
   const selectedIndexRef = useRef<number>(0);
   const showAIPanelRef = useRef<boolean>(false);
   const macrosRef = useRef<Record<string, string>>({});
+  // Set once the backend reports commands via shell integration (OSC 133)
+  const shellIntegrationRef = useRef<boolean>(false);
 
   // Sync refs with state
   useEffect(() => {
@@ -123,6 +139,26 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
     })();
   }, []);
 
+  // Load shell integration toggle on mount
+  useEffect(() => {
+    invoke<string | null>("settings_get", { key: SHELL_INTEGRATION_SETTING })
+      .then((value) => setShellIntegration(value === "true"))
+      .catch((error) => {
+        console.error("[Terminal] Failed to load shell integration setting:", error);
+      });
+  }, []);
+
+  const toggleShellIntegration = () => {
+    const next = !shellIntegration;
+    setShellIntegration(next);
+    invoke("settings_set", {
+      key: SHELL_INTEGRATION_SETTING,
+      value: next.toString(),
+    }).catch((error) => {
+      console.error("[Terminal] Failed to save shell integration setting:", error);
+    });
+  };
+
   // Save font size when it changes
   useEffect(() => {
     if (fontSize !== 14) { // Only save if different from default
@@ -140,6 +176,9 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
     terminal: termRef.current,
     enabled: true,
     onCommandExecuted: (command) => {
+      // Backend already records commands with real exit codes
+      if (shellIntegrationRef.current) return;
+
       // 히스토리에 저장
       invoke("history_save", {
         input: {
@@ -169,6 +208,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
             profileId: profile.id,
             prefix: currentInput,
             limit: 1, // Only get the top suggestion for inline
+            sessionId: sessionIdRef.current,
           });
 
           if (results.length > 0) {
@@ -222,6 +262,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
         profileId: profile.id,
         prefix: prefix || "",
         limit: 10,
+        sessionId: sessionIdRef.current,
       });
 
       console.log("[Terminal] Found suggestions:", results);
@@ -343,6 +384,27 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
       }
     );
 
+    const unlistenCommandPromise = listen<{
+      id: string;
+      history_id: string | null;
+      cmd: string;
+      exit_code: number | null;
+    }>(
+      "ssh:command",
+      (event) => {
+        const { id, history_id, cmd, exit_code } = event.payload;
+        if (id === sessionIdRef.current) {
+          shellIntegrationRef.current = true;
+          // 실패한 명령만 오류 설명 대상 (기록된 경우)
+          setLastFailure(
+            history_id && exit_code !== null && exit_code !== 0
+              ? { historyId: history_id, cmd, exitCode: exit_code }
+              : null
+          );
+        }
+      }
+    );
+
     // 4) 실제 SSH 셸 열기
     (async () => {
       try {
@@ -380,6 +442,13 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
         const finalRows = term.rows || 24;
         console.log(`[Terminal] Connecting with PTY size: ${finalCols}x${finalRows}`);
 
+        // 마운트 시 로드가 끝나기 전일 수 있으므로 연결 직전에 설정을 다시 읽는다
+        const shellIntegrationEnabled = await invoke<string | null>("settings_get", {
+          key: SHELL_INTEGRATION_SETTING,
+        })
+          .then((value) => value === "true")
+          .catch(() => false);
+
         const id = await invoke<string>("ssh_open_shell", {
           host: profile.host,
           port: profile.port,
@@ -387,6 +456,8 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
           password: password,
           cols: finalCols,
           rows: finalRows,
+          profileId: profile.id,
+          shellIntegration: shellIntegrationEnabled,
         });
 
         // ref에도 저장, state에도 저장
@@ -466,9 +537,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
           const id = sessionIdRef.current;
           if (id && text) {
             console.log('[Terminal] Pasting from clipboard:', text);
-            invoke("ssh_write", { id, data: text }).catch((err) => {
-              console.error("[ssh_write error]", err);
-            });
+            writeToSession(id, text);
           }
         }).catch((err) => {
           console.error('[Terminal] Failed to paste:', err);
@@ -488,9 +557,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
           // 완성 부분을 터미널에 전송
           const id = sessionIdRef.current;
           if (id) {
-            invoke("ssh_write", { id, data: completionPart }).catch((err) => {
-              console.error("[ssh_write error]", err);
-            });
+            writeToSession(id, completionPart);
           }
 
           setInlineSuggestion('');
@@ -522,9 +589,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
           const id = sessionIdRef.current;
           if (id) {
             // 현재 입력 지우기 (Ctrl+U) + 명령어 입력 (엔터 안 누름)
-            invoke("ssh_write", { id, data: '\x15' + command }).catch((err) => {
-              console.error("[ssh_write error]", err);
-            });
+            writeToSession(id, '\x15' + command);
           }
         } else {
           console.log(`[Terminal] No macro defined for Ctrl+${event.key}`);
@@ -589,21 +654,15 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
             if (id && currentCmd) {
               // 현재 입력 길이만큼 백스페이스 전송
               const backspaces = '\x7F'.repeat(currentCmd.length);
-              invoke("ssh_write", { id, data: backspaces }).catch((err) => {
-                console.error("[ssh_write error]", err);
-              });
+              writeToSession(id, backspaces);
 
               // 선택한 명령어 전송
               setTimeout(() => {
-                invoke("ssh_write", { id, data: selected.cmd }).catch((err) => {
-                  console.error("[ssh_write error]", err);
-                });
+                writeToSession(id, selected.cmd);
               }, 50); // 약간의 딜레이로 백스페이스가 먼저 처리되도록
             } else if (id) {
               // 현재 입력이 없으면 그냥 명령어 전송
-              invoke("ssh_write", { id, data: selected.cmd }).catch((err) => {
-                console.error("[ssh_write error]", err);
-              });
+              writeToSession(id, selected.cmd);
             }
 
             setShowDropdown(false);
@@ -644,9 +703,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
       if (!id) return;
 
       // SSH PTY에서는 \r만 보내면 됨 (\r\n 보내면 프롬프트 중복)
-      invoke("ssh_write", { id, data }).catch((err) => {
-        console.error("[ssh_write error]", err);
-      });
+      writeToSession(id, data);
     });
 
     // 7) cleanup
@@ -658,6 +715,7 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
       }
 
       unlistenPromise.then((un) => un()).catch(() => {});
+      unlistenCommandPromise.then((un) => un()).catch(() => {});
 
       try {
         if (term) {
@@ -714,6 +772,34 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
             </button>
           </div> */}
 
+          {/* Agent mode button */}
+          {sessionId && (
+            <button
+              onClick={() => setShowAgentPanel(true)}
+              className="flex h-8 items-center gap-2 rounded-lg bg-blue-500/10 px-3 hover:bg-blue-500/20 transition-colors ring-1 ring-blue-500/20"
+              title="AI agent: multi-step tasks with approval"
+            >
+              <Bot size={14} className="text-blue-400" />
+              <span className="text-sm text-blue-400">Agent</span>
+            </button>
+          )}
+
+          {/* Shell integration toggle (applies to the next connection) */}
+          <button
+            onClick={toggleShellIntegration}
+            className={`flex h-8 items-center gap-2 rounded-lg px-3 transition-colors ring-1 ${
+              shellIntegration
+                ? "bg-green-500/10 hover:bg-green-500/20 ring-green-500/20"
+                : "bg-gray-800/50 hover:bg-gray-700/50 ring-gray-700/50"
+            }`}
+            title="Shell integration: mark prompts so commands, exit codes and cwd are recorded exactly (next connection)"
+          >
+            <Activity size={14} className={shellIntegration ? "text-green-400" : "text-gray-400"} />
+            <span className={`text-sm ${shellIntegration ? "text-green-400" : "text-gray-400"}`}>
+              Integration {shellIntegration ? "On" : "Off"}
+            </span>
+          </button>
+
           {/* Macro settings button */}
           <button
             onClick={() => setShowMacroSettings(true)}
@@ -787,21 +873,15 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
               if (id && currentCmd) {
                 // 현재 입력 길이만큼 백스페이스 전송
                 const backspaces = '\x7F'.repeat(currentCmd.length);
-                invoke("ssh_write", { id, data: backspaces }).catch((err) => {
-                  console.error("[ssh_write error]", err);
-                });
+                writeToSession(id, backspaces);
 
                 // 선택한 명령어 전송
                 setTimeout(() => {
-                  invoke("ssh_write", { id, data: suggestion.cmd }).catch((err) => {
-                    console.error("[ssh_write error]", err);
-                  });
+                  writeToSession(id, suggestion.cmd);
                 }, 50);
               } else if (id) {
                 // 현재 입력이 없으면 그냥 명령어 전송
-                invoke("ssh_write", { id, data: suggestion.cmd }).catch((err) => {
-                  console.error("[ssh_write error]", err);
-                });
+                writeToSession(id, suggestion.cmd);
               }
 
               setShowDropdown(false);
@@ -811,6 +891,34 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
           />
         )}
 
+        {/* Explain Error Button */}
+        {lastFailure && !explaining && (
+          <button
+            onClick={() => setExplaining(lastFailure)}
+            className="absolute bottom-3 right-3 z-40 bg-red-900 bg-opacity-80 hover:bg-red-800 border border-red-700 text-red-100 text-xs px-3 py-1.5 rounded shadow"
+            title={lastFailure.cmd}
+          >
+            오류 설명 (exit {lastFailure.exitCode})
+          </button>
+        )}
+
+        {/* Explain Error Panel */}
+        {explaining && (
+          <ExplainErrorPanel
+            failed={explaining}
+            onClose={() => {
+              setExplaining(null);
+              setLastFailure(null);
+            }}
+            onInsertCommand={(command) => {
+              const id = sessionIdRef.current;
+              if (id) {
+                writeToSession(id, command);
+              }
+            }}
+          />
+        )}
+
         {/* AI Panel */}
         <AIPanel
           isOpen={showAIPanel}
@@ -819,23 +927,23 @@ export const SshTerminal: React.FC<SshTerminalProps> = ({ profile }) => {
             const id = sessionIdRef.current;
             if (id) {
               // 명령어를 터미널에 입력 (자동 실행 안 함)
-              invoke("ssh_write", { id, data: command }).catch((err) => {
-                console.error("[ssh_write error]", err);
-              });
+              writeToSession(id, command);
             }
           }}
           sessionId={sessionId}
-          osInfo={osInfo}
-          onOsInfoUpdate={setOsInfo}
           context={`
 SSH Connection: ${profile.user}@${profile.host}:${profile.port}
 Profile: ${profile.name}
-${osInfo ? `Operating System: ${osInfo}` : ''}
 
 Note: User is working in an SSH terminal session. Provide commands appropriate for this specific OS distribution.
 `.trim()}
         />
 
+        {/* Agent Panel */}
+        {showAgentPanel && sessionId && (
+          <AgentPanel sessionId={sessionId} onClose={() => setShowAgentPanel(false)} />
+        )}
+
         {/* Macro Settings Modal */}
         {showMacroSettings && (
           <MacroSettings
@@ -1,10 +1,111 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { X, Send, Copy, Terminal as TerminalIcon, Settings } from 'lucide-react';
+import { listen } from '@tauri-apps/api/event';
+import { X, Send, Copy, Terminal as TerminalIcon, Settings, Square, Plus, Pencil, Trash2 } from 'lucide-react';
+import { assessCommands, RiskReport, SEVERITY_LABELS } from '../risk';
+
+interface GenerationStats {
+  total_ms: number;
+  load_ms: number;
+  prompt_tokens: number;
+  prompt_eval_ms: number;
+  response_tokens: number;
+  eval_ms: number;
+  tokens_per_second: number;
+}
 
 interface AIResponse {
   response: string;
   model: string;
+  request_id?: string | null;
+  cancelled?: boolean;
+  stats?: GenerationStats | null;
+  sources?: RunbookSource[];
+}
+
+interface RunbookSource {
+  index: number;
+  path: string;
+  heading: string;
+  start_line: number;
+  score: number;
+}
+
+interface RunbookStatus {
+  dir: string | null;
+  documents: number;
+  chunks: number;
+  model: string | null;
+  indexed_at: number | null;
+}
+
+interface RunbookIndexSummary {
+  indexed: number;
+  unchanged: number;
+  removed: number;
+  chunks: number;
+  failed: string[];
+}
+
+interface AIConfig {
+  provider: 'ollama' | 'openai' | 'mock';
+  server_url: string;
+  model: string;
+  // 저장할 때만 보냄: null이면 기존 키 유지, ''이면 삭제 (저장된 키는 돌려받지 않음)
+  api_key?: string | null;
+  api_key_set: boolean;
+  temperature: number | null;
+  context_size: number | null;
+  timeout_secs: number;
+  language: 'ko' | 'en';
+  embedding_model: string;
+}
+
+interface ConnectionTest {
+  ok: boolean;
+  latency_ms: number;
+  models: string[];
+  model_available: boolean;
+  error: string | null;
+}
+
+const DEFAULT_URLS: Record<AIConfig['provider'], string> = {
+  ollama: 'http://localhost:11434',
+  openai: 'http://localhost:8080/v1',
+  mock: 'mock://',
+};
+
+interface Conversation {
+  id: string;
+  profile_id: string | null;
+  session_id: string | null;
+  title: string;
+  created_at: number;
+  updated_at: number;
+  message_count: number;
+}
+
+interface ConversationMessage {
+  id: number;
+  role: 'user' | 'assistant';
+  content: string;
+  context: string | null;
+  ts: number;
+}
+
+interface ExtractedCommand {
+  command: string;
+  language: string | null;
+  source: 'block' | 'inline';
+  block: number | null;
+  line: number;
+  had_prompt: boolean;
+  multiline: boolean;
+}
+
+interface AITokenEvent {
+  request_id: string;
+  token: string;
 }
 
 interface AIPanelProps {
@@ -13,8 +114,6 @@ interface AIPanelProps {
   onInsertCommand: (command: string) => void;
   context?: string;
   sessionId: string | null;
-  osInfo: string;
-  onOsInfoUpdate: (osInfo: string) => void;
 }
 
 export const AIPanel: React.FC<AIPanelProps> = ({
@@ -23,77 +122,183 @@ export const AIPanel: React.FC<AIPanelProps> = ({
   onInsertCommand,
   context,
   sessionId,
-  osInfo,
-  onOsInfoUpdate,
 }) => {
   const [question, setQuestion] = useState('');
   const [loading, setLoading] = useState(false);
   const [response, setResponse] = useState<AIResponse | null>(null);
-  const [extractedCommands, setExtractedCommands] = useState<string[]>([]);
+  const [extractedCommands, setExtractedCommands] = useState<ExtractedCommand[]>([]);
+  const [commandRisks, setCommandRisks] = useState<RiskReport[]>([]);
   const [error, setError] = useState<string | null>(null);
+  const [requestId, setRequestId] = useState<string | null>(null);
+
+  // 대화 (없으면 단발 질문)
+  const [conversations, setConversations] = useState<Conversation[]>([]);
+  const [conversationId, setConversationId] = useState<string | null>(null);
+  const [messages, setMessages] = useState<ConversationMessage[]>([]);
+
+  // 자동 수집되는 터미널 컨텍스트 미리보기
+  const [contextPreview, setContextPreview] = useState<string | null>(null);
+
+  const toggleContextPreview = async () => {
+    if (contextPreview !== null) {
+      setContextPreview(null);
+      return;
+    }
+    if (!sessionId) return;
+    try {
+      setContextPreview(await invoke<string>('ai_context_preview', { sessionId }));
+    } catch (err) {
+      setContextPreview(`컨텍스트를 가져오지 못했습니다: ${err}`);
+    }
+  };
+
+  const loadConversations = React.useCallback(async () => {
+    try {
+      const list = await invoke<Conversation[]>('ai_conversation_list', { sessionId });
+      setConversations(list);
+    } catch (err) {
+      console.error('대화 목록 로드 실패:', err);
+    }
+  }, [sessionId]);
+
+  const loadMessages = async (id: string) => {
+    const list = await invoke<ConversationMessage[]>('ai_conversation_messages', { conversationId: id });
+    setMessages(list);
+  };
 
   // AI 설정
-  const [serverUrl, setServerUrl] = useState('http://192.168.136.8:11434');
-  const [model, setModel] = useState('gpt-oss:20b');
+  const [config, setConfig] = useState<AIConfig | null>(null);
   const [showSettings, setShowSettings] = useState(false);
+  const [settingsError, setSettingsError] = useState<string | null>(null);
+  const [testResult, setTestResult] = useState<ConnectionTest | null>(null);
+  const [testing, setTesting] = useState(false);
+
+  // 런북 (검색 증강)
+  const [runbookStatus, setRunbookStatus] = useState<RunbookStatus | null>(null);
+  const [runbookDir, setRunbookDir] = useState('');
+  const [runbookMessage, setRunbookMessage] = useState<string | null>(null);
+  const [indexing, setIndexing] = useState(false);
+
+  const updateConfig = (patch: Partial<AIConfig>) => {
+    setConfig((prev) => (prev ? { ...prev, ...patch } : prev));
+  };
+
+  useEffect(() => {
+    if (isOpen) loadConversations();
+  }, [isOpen, loadConversations]);
+
+  const selectConversation = async (id: string | null) => {
+    setConversationId(id);
+    setResponse(null);
+    setExtractedCommands([]);
+    setError(null);
+    if (id) {
+      await loadMessages(id).catch(console.error);
+    } else {
+      setMessages([]);
+    }
+  };
+
+  const newConversation = async () => {
+    try {
+      const created = await invoke<Conversation>('ai_conversation_create', { sessionId });
+      await loadConversations();
+      await selectConversation(created.id);
+    } catch (err) {
+      setError(err as string);
+    }
+  };
+
+  const renameConversation = async () => {
+    const current = conversations.find((c) => c.id === conversationId);
+    if (!current) return;
+    const title = window.prompt('대화 이름', current.title);
+    if (title === null || !title.trim()) return;
+    await invoke('ai_conversation_rename', { conversationId: current.id, title }).catch(console.error);
+    await loadConversations();
+  };
+
+  const deleteConversation = async () => {
+    if (!conversationId || !window.confirm('이 대화를 삭제할까요?')) return;
+    await invoke('ai_conversation_delete', { conversationId }).catch(console.error);
+    await selectConversation(null);
+    await loadConversations();
+  };
 
-  // 설정 로드 및 OS 정보 가져오기
+  // 설정 로드
   useEffect(() => {
     if (isOpen) {
       // AI 설정 로드
-      Promise.all([
-        invoke<string | null>('settings_get', { key: 'ai_server_url' }),
-        invoke<string | null>('settings_get', { key: 'ai_model' }),
-      ]).then(([savedUrl, savedModel]) => {
-        if (savedUrl) setServerUrl(savedUrl);
-        if (savedModel) setModel(savedModel);
-      }).catch(console.error);
-
-      // OS 정보가 없으면 가져오기
-      if (!osInfo && sessionId) {
-        console.log('[AIPanel] Fetching OS info...');
-        invoke<string>('ssh_exec', {
-          id: sessionId,
-          command: 'cat /etc/os-release 2>/dev/null || uname -s'
-        }).then((output) => {
-          // PRETTY_NAME 또는 NAME 찾기
-          const prettyMatch = output.match(/PRETTY_NAME="([^"]+)"/);
-          const nameMatch = output.match(/NAME="([^"]+)"/);
-          const versionMatch = output.match(/VERSION="([^"]+)"/);
-
-          if (prettyMatch || nameMatch) {
-            const osName = prettyMatch ? prettyMatch[1] : nameMatch![1];
-            const osVersion = versionMatch ? ` ${versionMatch[1]}` : '';
-            const detectedOS = `${osName}${osVersion}`;
-            console.log('[AIPanel] Detected OS:', detectedOS);
-            onOsInfoUpdate(detectedOS);
-          } else {
-            // uname 결과 감지 (fallback)
-            const unameMatch = output.match(/(Linux|Darwin|FreeBSD)/);
-            if (unameMatch) {
-              console.log('[AIPanel] Detected OS (uname):', unameMatch[1]);
-              onOsInfoUpdate(unameMatch[1]);
-            }
-          }
-        }).catch((err) => {
-          // OS 감지 실패는 무시 (선택사항이므로)
-          console.log('[AIPanel] OS detection skipped:', err);
-        });
-      }
+      invoke<AIConfig>('ai_config_get')
+        .then((saved) => {
+          setConfig(saved);
+          // 모델이 없으면 설정부터
+          if (!saved.model) setShowSettings(true);
+        })
+        .catch(console.error);
+      invoke<RunbookStatus>('runbooks_status')
+        .then((status) => {
+          setRunbookStatus(status);
+          setRunbookDir(status.dir ?? '');
+        })
+        .catch(console.error);
     }
-  }, [isOpen, osInfo, sessionId, onOsInfoUpdate]);
+  }, [isOpen]);
 
-  // 설정 저장
+  // 설정 저장 (백엔드에서 검증)
   const saveSettings = async () => {
+    if (!config) return;
     try {
-      await invoke('settings_set', { key: 'ai_server_url', value: serverUrl });
-      await invoke('settings_set', { key: 'ai_model', value: model });
+      await invoke('ai_config_set', { config });
+      setSettingsError(null);
       setShowSettings(false);
     } catch (err) {
       console.error('설정 저장 실패:', err);
+      setSettingsError(err as string);
+    }
+  };
+
+  // 런북 디렉터리 저장 후 인덱싱 (바뀐 파일만 다시 임베딩)
+  const indexRunbooks = async () => {
+    setIndexing(true);
+    setRunbookMessage(null);
+    try {
+      if (config) await invoke('ai_config_set', { config });
+      await invoke<RunbookStatus>('runbooks_set_dir', { dir: runbookDir });
+      if (runbookDir.trim()) {
+        const summary = await invoke<RunbookIndexSummary>('runbooks_index');
+        setRunbookMessage(
+          `새로 인덱싱 ${summary.indexed}개 (${summary.chunks}개 구절) · 변경 없음 ${summary.unchanged}개 · 삭제 ${summary.removed}개` +
+            (summary.failed.length > 0 ? `\n실패: ${summary.failed.join('\n')}` : '')
+        );
+      }
+      setRunbookStatus(await invoke<RunbookStatus>('runbooks_status'));
+    } catch (err) {
+      setRunbookMessage(`오류: ${err}`);
+    } finally {
+      setIndexing(false);
     }
   };
 
+  // 연결 테스트 및 모델 목록
+  const testConnection = async () => {
+    if (!config) return;
+    setTesting(true);
+    try {
+      const result = await invoke<ConnectionTest>('ai_test_connection', { config });
+      setTestResult(result);
+      if (result.ok && !config.model && result.models.length > 0) {
+        updateConfig({ model: result.models[0] });
+      }
+    } catch (err) {
+      setTestResult({ ok: false, latency_ms: 0, models: [], model_available: false, error: err as string });
+    } finally {
+      setTesting(false);
+    }
+  };
+
+  const numberOrNull = (value: string) => (value.trim() === '' ? null : Number(value));
+
   // ESC 키로 패널 닫기
   useEffect(() => {
     if (!isOpen) return;
@@ -114,32 +319,72 @@ export const AIPanel: React.FC<AIPanelProps> = ({
     };
   }, [isOpen, onClose]);
 
+  // 스트리밍 토큰 수신
+  useEffect(() => {
+    if (!requestId) return;
+
+    const unlisten = listen<AITokenEvent>('ai:token', (event) => {
+      if (event.payload.request_id !== requestId) return;
+      setResponse((prev) => ({
+        response: (prev?.response ?? '') + event.payload.token,
+        model: prev?.model ?? config?.model ?? '',
+        request_id: requestId,
+      }));
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [requestId, config?.model]);
+
   const handleAsk = async () => {
     if (!question.trim()) return;
 
+    const id = crypto.randomUUID();
     setLoading(true);
     setError(null);
+    setResponse(null);
+    setExtractedCommands([]);
+    setCommandRisks([]);
+    setRequestId(id);
 
     try {
       const result = await invoke<AIResponse>('ai_ask', {
         prompt: question,
         context: context || null,
-        serverUrl: serverUrl,
-        model: model,
+        requestId: id,
+        conversationId,
+        sessionId,
       });
 
       setResponse(result);
+      if (conversationId) {
+        setQuestion('');
+        await loadMessages(conversationId);
+        await loadConversations();
+      }
 
       // 명령어 추출
-      const commands = await invoke<string[]>('ai_extract_commands', {
+      const commands = await invoke<ExtractedCommand[]>('ai_extract_commands', {
         response: result.response,
       });
       setExtractedCommands(commands);
+      setCommandRisks(await assessCommands(commands.map((c) => c.command)).catch(() => []));
     } catch (err) {
       console.error('AI 요청 실패:', err);
       setError(err as string);
     } finally {
       setLoading(false);
+      setRequestId(null);
+    }
+  };
+
+  const handleCancel = async () => {
+    if (!requestId) return;
+    try {
+      await invoke<boolean>('ai_cancel', { requestId });
+    } catch (err) {
+      console.error('AI 요청 취소 실패:', err);
     }
   };
 
@@ -195,35 +440,240 @@ export const AIPanel: React.FC<AIPanelProps> = ({
           </div>
         </div>
 
+        {/* Conversation Bar */}
+        <div className="flex items-center gap-2 px-4 py-2 border-b border-gray-700">
+          <select
+            value={conversationId ?? ''}
+            onChange={(e) => selectConversation(e.target.value || null)}
+            disabled={loading}
+            className="flex-1 bg-gray-800 text-white border border-gray-700 rounded px-2 py-1 text-sm focus:outline-none focus:border-blue-500"
+          >
+            <option value="">단발 질문 (기록 없음)</option>
+            {conversations.map((c) => (
+              <option key={c.id} value={c.id}>
+                {c.title || '새 대화'} ({c.message_count})
+              </option>
+            ))}
+          </select>
+          <button
+            onClick={newConversation}
+            disabled={loading}
+            className="text-gray-400 hover:text-white disabled:opacity-50 transition-colors"
+            title="새 대화"
+          >
+            <Plus size={18} />
+          </button>
+          {conversationId && (
+            <>
+              <button
+                onClick={renameConversation}
+                className="text-gray-400 hover:text-white transition-colors"
+                title="이름 변경"
+              >
+                <Pencil size={16} />
+              </button>
+              <button
+                onClick={deleteConversation}
+                disabled={loading}
+                className="text-gray-400 hover:text-red-400 disabled:opacity-50 transition-colors"
+                title="대화 삭제"
+              >
+                <Trash2 size={16} />
+              </button>
+            </>
+          )}
+        </div>
+
         {/* Content */}
         <div className="flex-1 overflow-y-auto p-4 space-y-4">
           {/* Settings Panel */}
-          {showSettings && (
+          {showSettings && config && (
             <div className="bg-gray-800 border border-gray-700 rounded p-4 space-y-4">
               <h3 className="text-base font-semibold text-white">AI 설정</h3>
 
               <div className="space-y-2">
-                <label className="text-sm text-gray-300">Ollama 서버 URL:</label>
-                <input
-                  type="text"
-                  value={serverUrl}
-                  onChange={(e) => setServerUrl(e.target.value)}
-                  placeholder="http://192.168.136.8:11434"
+                <label className="text-sm text-gray-300">Provider:</label>
+                <select
+                  value={config.provider}
+                  onChange={(e) => {
+                    const provider = e.target.value as AIConfig['provider'];
+                    updateConfig({ provider, server_url: DEFAULT_URLS[provider] });
+                    setTestResult(null);
+                  }}
                   className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
-                />
+                >
+                  <option value="ollama">Ollama</option>
+                  <option value="openai">OpenAI 호환 (llama.cpp, vLLM, LM Studio)</option>
+                  <option value="mock">Mock (테스트)</option>
+                </select>
+              </div>
+
+              <div className="space-y-2">
+                <label className="text-sm text-gray-300">서버 URL:</label>
+                <div className="flex gap-2">
+                  <input
+                    type="text"
+                    value={config.server_url}
+                    onChange={(e) => updateConfig({ server_url: e.target.value })}
+                    placeholder={DEFAULT_URLS[config.provider]}
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  />
+                  <button
+                    onClick={testConnection}
+                    disabled={testing}
+                    className="shrink-0 bg-gray-700 hover:bg-gray-600 disabled:opacity-50 text-white px-3 py-2 rounded text-sm transition-colors"
+                  >
+                    {testing ? '확인 중...' : '연결 테스트'}
+                  </button>
+                </div>
+                {testResult && (
+                  <div className={`text-xs ${testResult.ok ? 'text-green-400' : 'text-red-400'}`}>
+                    {testResult.ok
+                      ? `연결 성공 (${testResult.latency_ms}ms) · 모델 ${testResult.models.length}개`
+                      : `연결 실패: ${testResult.error}`}
+                  </div>
+                )}
               </div>
 
               <div className="space-y-2">
                 <label className="text-sm text-gray-300">모델:</label>
+                {testResult?.ok && testResult.models.length > 0 ? (
+                  <select
+                    value={config.model}
+                    onChange={(e) => updateConfig({ model: e.target.value })}
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  >
+                    {!testResult.models.includes(config.model) && (
+                      <option value={config.model}>{config.model || '(선택)'}</option>
+                    )}
+                    {testResult.models.map((m) => (
+                      <option key={m} value={m}>{m}</option>
+                    ))}
+                  </select>
+                ) : (
+                  <input
+                    type="text"
+                    value={config.model}
+                    onChange={(e) => updateConfig({ model: e.target.value })}
+                    placeholder="연결 테스트로 모델 목록을 불러오세요"
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  />
+                )}
+              </div>
+
+              {config.provider === 'openai' && (
+                <div className="space-y-2">
+                  <label className="text-sm text-gray-300">API Key (선택):</label>
+                  <div className="flex gap-2">
+                    <input
+                      type="password"
+                      value={config.api_key ?? ''}
+                      onChange={(e) => updateConfig({ api_key: e.target.value || null })}
+                      placeholder={config.api_key_set ? '저장됨 (바꾸려면 새 키 입력)' : ''}
+                      className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                    />
+                    {config.api_key_set && (
+                      <button
+                        onClick={() => updateConfig({ api_key: '', api_key_set: false })}
+                        className="shrink-0 bg-gray-700 hover:bg-gray-600 text-white px-3 py-2 rounded text-sm transition-colors"
+                      >
+                        삭제
+                      </button>
+                    )}
+                  </div>
+                </div>
+              )}
+
+              <div className="grid grid-cols-2 gap-3">
+                <div className="space-y-2">
+                  <label className="text-sm text-gray-300">Temperature (0 ~ 2):</label>
+                  <input
+                    type="number"
+                    step="0.1"
+                    min="0"
+                    max="2"
+                    value={config.temperature ?? ''}
+                    onChange={(e) => updateConfig({ temperature: numberOrNull(e.target.value) })}
+                    placeholder="서버 기본값"
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  />
+                </div>
+                <div className="space-y-2">
+                  <label className="text-sm text-gray-300">Context size (토큰):</label>
+                  <input
+                    type="number"
+                    min="256"
+                    value={config.context_size ?? ''}
+                    onChange={(e) => updateConfig({ context_size: numberOrNull(e.target.value) })}
+                    placeholder="서버 기본값"
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  />
+                </div>
+                <div className="space-y-2">
+                  <label className="text-sm text-gray-300">Timeout (초):</label>
+                  <input
+                    type="number"
+                    min="5"
+                    max="3600"
+                    value={config.timeout_secs}
+                    onChange={(e) => updateConfig({ timeout_secs: Number(e.target.value) })}
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  />
+                </div>
+                <div className="space-y-2">
+                  <label className="text-sm text-gray-300">응답 언어:</label>
+                  <select
+                    value={config.language}
+                    onChange={(e) => updateConfig({ language: e.target.value as AIConfig['language'] })}
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  >
+                    <option value="ko">한국어</option>
+                    <option value="en">English</option>
+                  </select>
+                </div>
+              </div>
+
+              <div className="space-y-2 border-t border-gray-700 pt-4">
+                <label className="text-sm text-gray-300">런북 디렉터리 (Markdown):</label>
                 <input
                   type="text"
-                  value={model}
-                  onChange={(e) => setModel(e.target.value)}
-                  placeholder="gpt-oss:20b"
+                  value={runbookDir}
+                  onChange={(e) => setRunbookDir(e.target.value)}
+                  placeholder="/home/me/team-runbooks (비우면 사용 안 함)"
                   className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
                 />
+                <label className="text-sm text-gray-300">임베딩 모델:</label>
+                <div className="flex gap-2">
+                  <input
+                    type="text"
+                    value={config.embedding_model}
+                    onChange={(e) => updateConfig({ embedding_model: e.target.value })}
+                    placeholder="nomic-embed-text"
+                    className="w-full bg-gray-900 text-white border border-gray-600 rounded px-3 py-2 text-sm focus:outline-none focus:border-blue-500"
+                  />
+                  <button
+                    onClick={indexRunbooks}
+                    disabled={indexing}
+                    className="shrink-0 bg-gray-700 hover:bg-gray-600 disabled:opacity-50 text-white px-3 py-2 rounded text-sm transition-colors"
+                  >
+                    {indexing ? '인덱싱 중...' : '인덱싱'}
+                  </button>
+                </div>
+                {runbookStatus && runbookStatus.documents > 0 && (
+                  <div className="text-xs text-gray-400">
+                    문서 {runbookStatus.documents}개 · 구절 {runbookStatus.chunks}개 · {runbookStatus.model}
+                    {runbookStatus.indexed_at && ` · ${new Date(runbookStatus.indexed_at * 1000).toLocaleString()}`}
+                  </div>
+                )}
+                {runbookMessage && (
+                  <div className="text-xs text-gray-400 whitespace-pre-wrap">{runbookMessage}</div>
+                )}
               </div>
 
+              {settingsError && (
+                <div className="text-xs text-red-400">{settingsError}</div>
+              )}
+
               <div className="flex gap-2">
                 <button
                   onClick={saveSettings}
@@ -241,6 +691,24 @@ export const AIPanel: React.FC<AIPanelProps> = ({
             </div>
           )}
 
+          {/* Conversation Messages */}
+          {conversationId && messages.length > 0 && (
+            <div className="space-y-2">
+              {messages.map((m) => (
+                <div
+                  key={m.id}
+                  className={`rounded p-3 text-sm whitespace-pre-wrap ${
+                    m.role === 'user'
+                      ? 'bg-blue-900 bg-opacity-30 border border-blue-800 text-blue-100'
+                      : 'bg-gray-800 border border-gray-700 text-gray-200 font-mono'
+                  }`}
+                >
+                  {m.content}
+                </div>
+              ))}
+            </div>
+          )}
+
           {/* Question Input */}
           <div className="space-y-2">
             <label className="text-sm text-gray-300">질문을 입력하세요:</label>
@@ -256,20 +724,43 @@ export const AIPanel: React.FC<AIPanelProps> = ({
               disabled={loading}
               lang="ko"
             />
-            <div className="text-xs text-gray-500">
-              Enter: 전송 | Shift+Enter: 줄바꿈
+            <div className="flex items-center justify-between text-xs text-gray-500">
+              <span>Enter: 전송 | Shift+Enter: 줄바꿈</span>
+              {sessionId && (
+                <button
+                  onClick={toggleContextPreview}
+                  className="text-gray-400 hover:text-white transition-colors"
+                >
+                  {contextPreview !== null ? '컨텍스트 숨기기' : '전송될 터미널 컨텍스트 보기'}
+                </button>
+              )}
             </div>
+            {contextPreview !== null && (
+              <pre className="bg-gray-800 border border-gray-700 rounded p-3 text-xs text-gray-400 whitespace-pre-wrap max-h-[200px] overflow-y-auto">
+                {contextPreview || '(수집된 컨텍스트 없음)'}
+              </pre>
+            )}
           </div>
 
-          {/* Ask Button */}
-          <button
-            onClick={handleAsk}
-            disabled={loading || !question.trim()}
-            className="w-full bg-blue-600 hover:bg-blue-700 disabled:bg-gray-700 disabled:cursor-not-allowed text-white px-4 py-2 rounded flex items-center justify-center gap-2 transition-colors"
-          >
-            <Send size={16} />
-            {loading ? '처리 중...' : 'AI에게 질문하기'}
-          </button>
+          {/* Ask / Cancel Button */}
+          {loading ? (
+            <button
+              onClick={handleCancel}
+              className="w-full bg-gray-700 hover:bg-gray-600 text-white px-4 py-2 rounded flex items-center justify-center gap-2 transition-colors"
+            >
+              <Square size={16} />
+              생성 중지
+            </button>
+          ) : (
+            <button
+              onClick={handleAsk}
+              disabled={!question.trim()}
+              className="w-full bg-blue-600 hover:bg-blue-700 disabled:bg-gray-700 disabled:cursor-not-allowed text-white px-4 py-2 rounded flex items-center justify-center gap-2 transition-colors"
+            >
+              <Send size={16} />
+              AI에게 질문하기
+            </button>
+          )}
 
           {/* Error Message */}
           {error && (
@@ -278,8 +769,8 @@ export const AIPanel: React.FC<AIPanelProps> = ({
             </div>
           )}
 
-          {/* Response */}
-          {response && (
+          {/* Response (대화 중에는 완료 후 위 목록에 표시됨) */}
+          {response && (!conversationId || loading) && (
             <div className="space-y-3">
               <div className="flex items-center justify-between">
                 <h3 className="text-sm font-semibold text-gray-300">AI 응답:</h3>
@@ -296,10 +787,33 @@ export const AIPanel: React.FC<AIPanelProps> = ({
               </div>
               <div className="text-xs text-gray-500">
                 모델: {response.model}
+                {response.cancelled && ' | 중지됨'}
+                {response.stats && (
+                  <>
+                    {' | '}
+                    {(response.stats.total_ms / 1000).toFixed(1)}초
+                    {' | '}
+                    {response.stats.response_tokens} 토큰 ({response.stats.tokens_per_second.toFixed(1)} tok/s)
+                  </>
+                )}
               </div>
             </div>
           )}
 
+          {/* Runbook Sources */}
+          {response?.sources && response.sources.length > 0 && !loading && (
+            <div className="space-y-1">
+              <h3 className="text-sm font-semibold text-gray-300">참고한 런북:</h3>
+              {response.sources.map((source) => (
+                <div key={source.index} className="text-xs text-gray-400 font-mono">
+                  [{source.index}] {source.path}
+                  {source.heading && ` › ${source.heading}`}
+                  {` (${source.start_line}행)`}
+                </div>
+              ))}
+            </div>
+          )}
+
           {/* Extracted Commands */}
           {extractedCommands.length > 0 && (
             <div className="space-y-2">
@@ -311,12 +825,28 @@ export const AIPanel: React.FC<AIPanelProps> = ({
                   key={index}
                   className="bg-gray-800 border border-gray-700 rounded p-3 flex items-start justify-between gap-3"
                 >
-                  <code className="text-green-400 text-sm flex-1 font-mono whitespace-pre-wrap">
-                    {cmd}
-                  </code>
+                  <div className="flex-1 min-w-0">
+                    <code className="text-green-400 text-sm font-mono whitespace-pre-wrap">
+                      {cmd.command}
+                    </code>
+                    <div className="text-xs text-gray-500 mt-1">
+                      {cmd.source === 'inline' ? '본문' : cmd.language ?? '코드 블록'}
+                      {cmd.multiline && ' | 여러 줄'}
+                    </div>
+                    {commandRisks[index]?.severity && (
+                      <div className="mt-1 flex items-start gap-2 text-xs">
+                        <span className={`px-1.5 rounded ${SEVERITY_LABELS[commandRisks[index].severity!].className}`}>
+                          {SEVERITY_LABELS[commandRisks[index].severity!].label}
+                        </span>
+                        <span className="text-gray-400">
+                          {commandRisks[index].findings.map((f) => f.explanation).join(' / ')}
+                        </span>
+                      </div>
+                    )}
+                  </div>
                   <div className="flex gap-2">
                     <button
-                      onClick={() => copyToClipboard(cmd)}
+                      onClick={() => copyToClipboard(cmd.command)}
                       className="text-gray-400 hover:text-white transition-colors"
                       title="복사"
                     >
@@ -324,7 +854,7 @@ export const AIPanel: React.FC<AIPanelProps> = ({
                     </button>
                     <button
                       onClick={() => {
-                        onInsertCommand(cmd);
+                        onInsertCommand(cmd.command);
                         onClose();
                       }}
                       className="text-blue-400 hover:text-blue-300 transition-colors"
@@ -0,0 +1,428 @@
+import React, { useCallback, useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { X, Play, Check, Ban, Square, Trash2 } from 'lucide-react';
+import { RiskSeverity, SEVERITY_LABELS } from '../risk';
+
+type RunStatus = 'running' | 'completed' | 'cancelled' | 'limit_reached';
+type StepStatus = 'proposed' | 'running' | 'executed' | 'rejected' | 'blocked' | 'failed';
+
+interface AgentRun {
+  id: string;
+  profile_id: string | null;
+  session_id: string;
+  goal: string;
+  cwd: string | null;
+  os_info: string | null;
+  status: RunStatus;
+  summary: string | null;
+  max_steps: number;
+  max_risk: RiskSeverity;
+  created_at: number;
+  updated_at: number;
+  step_count: number;
+}
+
+interface AgentStep {
+  id: number;
+  run_id: string;
+  idx: number;
+  thought: string;
+  command: string;
+  risk: RiskSeverity | null;
+  risk_explanation: string | null;
+  status: StepStatus;
+  note: string | null;
+  stdout: string | null;
+  stderr: string | null;
+  exit_code: number | null;
+  error: string | null;
+  duration_ms: number | null;
+  proposed_at: number;
+  executed_at: number | null;
+}
+
+interface AgentRunReport {
+  run: AgentRun;
+  steps: AgentStep[];
+}
+
+interface AgentTurn {
+  run: AgentRun;
+  step: AgentStep | null;
+  model: string;
+  cancelled: boolean;
+}
+
+interface AITokenEvent {
+  request_id: string;
+  token: string;
+}
+
+interface AgentPanelProps {
+  sessionId: string;
+  onClose: () => void;
+}
+
+const RUN_STATUS_LABELS: Record<RunStatus, string> = {
+  running: '진행 중',
+  completed: '완료',
+  cancelled: '중지됨',
+  limit_reached: '단계 제한 도달',
+};
+
+const STEP_STATUS_LABELS: Record<StepStatus, { label: string; className: string }> = {
+  proposed: { label: '승인 대기', className: 'text-blue-400' },
+  running: { label: '실행 중', className: 'text-yellow-400' },
+  executed: { label: '실행됨', className: 'text-green-400' },
+  rejected: { label: '거부됨', className: 'text-gray-400' },
+  blocked: { label: '차단됨 (위험 제한 초과)', className: 'text-red-400' },
+  failed: { label: '실행 실패', className: 'text-red-400' },
+};
+
+export const AgentPanel: React.FC<AgentPanelProps> = ({ sessionId, onClose }) => {
+  const [goal, setGoal] = useState('');
+  const [maxSteps, setMaxSteps] = useState(10);
+  const [maxRisk, setMaxRisk] = useState<RiskSeverity>('medium');
+  const [report, setReport] = useState<AgentRunReport | null>(null);
+  const [pastRuns, setPastRuns] = useState<AgentRun[]>([]);
+  const [editedCommand, setEditedCommand] = useState('');
+  const [rejectNote, setRejectNote] = useState('');
+  const [requestId, setRequestId] = useState<string | null>(null);
+  const [streamed, setStreamed] = useState('');
+  const [busy, setBusy] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const run = report?.run ?? null;
+  const pending = report?.steps.find((s) => s.status === 'proposed') ?? null;
+
+  const loadPastRuns = useCallback(() => {
+    invoke<AgentRun[]>('ai_agent_runs', { sessionId })
+      .then(setPastRuns)
+      .catch(console.error);
+  }, [sessionId]);
+
+  useEffect(() => {
+    loadPastRuns();
+  }, [loadPastRuns]);
+
+  // 다음 단계 생성 중 스트리밍 표시
+  useEffect(() => {
+    const unlisten = listen<AITokenEvent>('ai:token', (event) => {
+      if (event.payload.request_id === requestId) {
+        setStreamed((prev) => prev + event.payload.token);
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [requestId]);
+
+  useEffect(() => {
+    setEditedCommand(pending?.command ?? '');
+    setRejectNote('');
+  }, [pending?.id, pending?.command]);
+
+  const refresh = async (runId: string) => {
+    const next = await invoke<AgentRunReport>('ai_agent_get', { runId });
+    setReport(next);
+    return next;
+  };
+
+  // 모델에게 다음 단계를 요청 (차단된 제안은 건너뛰고 계속)
+  const nextStep = async (runId: string) => {
+    for (;;) {
+      const id = crypto.randomUUID();
+      setRequestId(id);
+      setStreamed('');
+      const turn = await invoke<AgentTurn>('ai_agent_next', { runId, requestId: id });
+      setRequestId(null);
+      await refresh(runId);
+      if (turn.cancelled || turn.run.status !== 'running' || turn.step?.status !== 'blocked') {
+        return;
+      }
+    }
+  };
+
+  const withBusy = async (action: () => Promise<void>) => {
+    setBusy(true);
+    setError(null);
+    try {
+      await action();
+    } catch (err) {
+      setError(err as string);
+    } finally {
+      setBusy(false);
+      setRequestId(null);
+      loadPastRuns();
+    }
+  };
+
+  const handleStart = () =>
+    withBusy(async () => {
+      const started = await invoke<AgentRun>('ai_agent_start', {
+        sessionId,
+        goal,
+        maxSteps,
+        maxRisk,
+      });
+      setReport({ run: started, steps: [] });
+      await nextStep(started.id);
+    });
+
+  const handleDecide = (approve: boolean) => {
+    if (!pending || !run) return;
+    withBusy(async () => {
+      await invoke<AgentStep>('ai_agent_decide', {
+        stepId: pending.id,
+        approve,
+        command: approve ? editedCommand : null,
+        note: approve ? null : rejectNote || null,
+      });
+      const next = await refresh(run.id);
+      if (next.run.status === 'running' && !next.steps.some((s) => s.status === 'proposed')) {
+        await nextStep(run.id);
+      }
+    });
+  };
+
+  const handleStop = async () => {
+    if (!run) return;
+    if (requestId) {
+      await invoke<boolean>('ai_cancel', { requestId }).catch(console.error);
+    }
+    await invoke<AgentRun>('ai_agent_stop', { runId: run.id }).catch(console.error);
+    await refresh(run.id).catch(console.error);
+    loadPastRuns();
+  };
+
+  const handleOpenRun = (runId: string) => {
+    if (!runId) {
+      setReport(null);
+      return;
+    }
+    withBusy(async () => {
+      const opened = await refresh(runId);
+      // 승인 대기 중이 아니라면 이어서 진행
+      if (opened.run.status === 'running' && !opened.steps.some((s) => s.status === 'proposed')) {
+        await nextStep(runId);
+      }
+    });
+  };
+
+  const handleDelete = async () => {
+    if (!run || run.status === 'running') return;
+    if (!confirm('이 작업 기록을 삭제하시겠습니까?')) return;
+    await invoke<boolean>('ai_agent_delete', { runId: run.id }).catch(console.error);
+    setReport(null);
+    loadPastRuns();
+  };
+
+  return (
+    <div className="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50">
+      <div className="bg-gray-900 rounded-lg shadow-xl w-[800px] max-h-[85vh] flex flex-col">
+        {/* Header */}
+        <div className="flex items-center justify-between p-4 border-b border-gray-700 gap-3">
+          <div className="min-w-0">
+            <h2 className="text-lg font-semibold text-white">에이전트 모드</h2>
+            {run && (
+              <div className="text-xs text-gray-400 truncate">
+                {RUN_STATUS_LABELS[run.status]} | {run.step_count}/{run.max_steps} 단계 | 위험 제한:{' '}
+                {SEVERITY_LABELS[run.max_risk].label}
+              </div>
+            )}
+          </div>
+          <div className="flex items-center gap-2">
+            <select
+              value={run?.id ?? ''}
+              onChange={(e) => handleOpenRun(e.target.value)}
+              disabled={busy}
+              className="bg-gray-800 border border-gray-700 rounded px-2 py-1 text-sm text-white max-w-[240px]"
+            >
+              <option value="">새 작업</option>
+              {pastRuns.map((r) => (
+                <option key={r.id} value={r.id}>
+                  {r.goal.slice(0, 40)} ({RUN_STATUS_LABELS[r.status]})
+                </option>
+              ))}
+            </select>
+            {run && run.status !== 'running' && (
+              <button onClick={handleDelete} className="text-gray-400 hover:text-red-400" title="기록 삭제">
+                <Trash2 size={16} />
+              </button>
+            )}
+            <button onClick={onClose} className="text-gray-400 hover:text-white transition-colors" title="닫기">
+              <X size={20} />
+            </button>
+          </div>
+        </div>
+
+        <div className="flex-1 overflow-y-auto p-4 space-y-4">
+          {/* Goal */}
+          {!run && (
+            <div className="space-y-3">
+              <textarea
+                value={goal}
+                onChange={(e) => setGoal(e.target.value)}
+                placeholder="목표를 입력하세요 (예: 디스크 사용량이 큰 로그를 찾아 정리)"
+                className="w-full bg-gray-800 border border-gray-700 rounded px-3 py-2 text-white h-20 resize-none"
+              />
+              <div className="flex items-center gap-4 text-sm text-gray-300">
+                <label className="flex items-center gap-2">
+                  최대 단계
+                  <input
+                    type="number"
+                    min={1}
+                    max={30}
+                    value={maxSteps}
+                    onChange={(e) => setMaxSteps(Number(e.target.value))}
+                    className="w-16 bg-gray-800 border border-gray-700 rounded px-2 py-1 text-white"
+                  />
+                </label>
+                <label className="flex items-center gap-2">
+                  허용 위험도
+                  <select
+                    value={maxRisk}
+                    onChange={(e) => setMaxRisk(e.target.value as RiskSeverity)}
+                    className="bg-gray-800 border border-gray-700 rounded px-2 py-1 text-white"
+                  >
+                    <option value="low">{SEVERITY_LABELS.low.label}</option>
+                    <option value="medium">{SEVERITY_LABELS.medium.label}</option>
+                    <option value="high">{SEVERITY_LABELS.high.label}</option>
+                  </select>
+                </label>
+                <button
+                  onClick={handleStart}
+                  disabled={busy || !goal.trim()}
+                  className="ml-auto bg-blue-600 hover:bg-blue-700 disabled:bg-gray-700 text-white px-4 py-2 rounded flex items-center gap-2"
+                >
+                  <Play size={16} />
+                  시작
+                </button>
+              </div>
+            </div>
+          )}
+
+          {run && (
+            <div className="text-sm text-gray-300">
+              <span className="text-gray-500">목표:</span> {run.goal}
+              {run.cwd && <span className="text-gray-500"> ({run.cwd})</span>}
+            </div>
+          )}
+
+          {/* Step log */}
+          {report?.steps.map((step) => (
+            <div key={step.id} className="bg-gray-800 border border-gray-700 rounded p-3 space-y-2">
+              <div className="flex items-center justify-between text-xs">
+                <span className="text-gray-400">단계 {step.idx}</span>
+                <div className="flex items-center gap-2">
+                  {step.risk && (
+                    <span className={`px-1.5 rounded ${SEVERITY_LABELS[step.risk].className}`}>
+                      {SEVERITY_LABELS[step.risk].label}
+                    </span>
+                  )}
+                  <span className={STEP_STATUS_LABELS[step.status].className}>
+                    {STEP_STATUS_LABELS[step.status].label}
+                  </span>
+                </div>
+              </div>
+              {step.thought && <div className="text-sm text-gray-300">{step.thought}</div>}
+
+              {step.status === 'proposed' && !busy ? (
+                <textarea
+                  value={editedCommand}
+                  onChange={(e) => setEditedCommand(e.target.value)}
+                  className="w-full bg-gray-950 border border-gray-700 rounded px-2 py-1 text-green-400 font-mono text-sm"
+                  rows={Math.min(6, editedCommand.split('\n').length)}
+                />
+              ) : (
+                <code className="block text-green-400 text-sm font-mono whitespace-pre-wrap">{step.command}</code>
+              )}
+              {step.risk_explanation && <div className="text-xs text-orange-300">{step.risk_explanation}</div>}
+              {step.note && <div className="text-xs text-gray-400">거부 사유: {step.note}</div>}
+
+              {step.status === 'executed' && (
+                <div className="text-xs space-y-1">
+                  <div className={step.exit_code === 0 ? 'text-green-400' : 'text-red-400'}>
+                    exit {step.exit_code} | {step.duration_ms}ms
+                  </div>
+                  {step.stdout && (
+                    <pre className="bg-gray-950 rounded p-2 text-gray-300 max-h-40 overflow-auto whitespace-pre-wrap">
+                      {step.stdout}
+                    </pre>
+                  )}
+                  {step.stderr && (
+                    <pre className="bg-gray-950 rounded p-2 text-red-300 max-h-40 overflow-auto whitespace-pre-wrap">
+                      {step.stderr}
+                    </pre>
+                  )}
+                </div>
+              )}
+              {step.error && <div className="text-xs text-red-400">{step.error}</div>}
+
+              {step.status === 'proposed' && !busy && run?.status === 'running' && (
+                <div className="flex items-center gap-2">
+                  <button
+                    onClick={() => handleDecide(true)}
+                    disabled={!editedCommand.trim()}
+                    className="bg-green-700 hover:bg-green-600 disabled:bg-gray-700 text-white text-sm px-3 py-1.5 rounded flex items-center gap-1"
+                  >
+                    <Check size={14} />
+                    승인 후 실행
+                  </button>
+                  <input
+                    type="text"
+                    value={rejectNote}
+                    onChange={(e) => setRejectNote(e.target.value)}
+                    placeholder="거부 사유 (선택)"
+                    className="flex-1 bg-gray-950 border border-gray-700 rounded px-2 py-1 text-sm text-white"
+                  />
+                  <button
+                    onClick={() => handleDecide(false)}
+                    className="bg-gray-700 hover:bg-gray-600 text-white text-sm px-3 py-1.5 rounded flex items-center gap-1"
+                  >
+                    <Ban size={14} />
+                    거부
+                  </button>
+                </div>
+              )}
+            </div>
+          ))}
+
+          {busy && requestId && (
+            <div className="bg-gray-800 border border-gray-700 rounded p-3 text-gray-400 whitespace-pre-wrap font-mono text-xs max-h-[150px] overflow-y-auto">
+              {streamed || '다음 단계를 생각하는 중...'}
+            </div>
+          )}
+
+          {run?.summary && (
+            <div className="bg-green-900 bg-opacity-30 border border-green-700 text-green-200 px-4 py-3 rounded whitespace-pre-wrap">
+              {run.summary}
+            </div>
+          )}
+          {run?.status === 'limit_reached' && (
+            <div className="text-sm text-yellow-400">최대 단계 수({run.max_steps})에 도달해 작업을 멈췄습니다.</div>
+          )}
+
+          {error && (
+            <div className="bg-red-900 bg-opacity-30 border border-red-700 text-red-300 px-4 py-3 rounded">
+              <strong>오류:</strong> {error}
+            </div>
+          )}
+        </div>
+
+        {run?.status === 'running' && (
+          <div className="p-4 border-t border-gray-700 flex justify-end">
+            <button
+              onClick={handleStop}
+              className="bg-gray-700 hover:bg-gray-600 text-white px-4 py-2 rounded flex items-center gap-2"
+            >
+              <Square size={16} />
+              작업 중지
+            </button>
+          </div>
+        )}
+      </div>
+    </div>
+  );
+};
@@ -0,0 +1,176 @@
+import React, { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { X, Copy, Terminal as TerminalIcon, Square } from 'lucide-react';
+
+export interface FailedCommand {
+  historyId: string;
+  cmd: string;
+  exitCode: number;
+}
+
+interface FixCommand {
+  command: string;
+  description: string;
+}
+
+interface ErrorExplanation {
+  history_id: string;
+  cmd: string;
+  exit_code: number;
+  output_captured: boolean;
+  cause: string;
+  explanation: string;
+  fix_commands: FixCommand[];
+  confidence: 'low' | 'medium' | 'high';
+  structured: boolean;
+  model: string;
+  request_id: string | null;
+  cancelled: boolean;
+}
+
+interface AITokenEvent {
+  request_id: string;
+  token: string;
+}
+
+interface ExplainErrorPanelProps {
+  failed: FailedCommand;
+  onClose: () => void;
+  onInsertCommand: (command: string) => void;
+}
+
+const CONFIDENCE_LABELS: Record<ErrorExplanation['confidence'], { label: string; className: string }> = {
+  high: { label: '확신도 높음', className: 'text-green-400' },
+  medium: { label: '확신도 보통', className: 'text-yellow-400' },
+  low: { label: '확신도 낮음', className: 'text-gray-400' },
+};
+
+export const ExplainErrorPanel: React.FC<ExplainErrorPanelProps> = ({ failed, onClose, onInsertCommand }) => {
+  const [requestId] = useState(() => crypto.randomUUID());
+  const [streamed, setStreamed] = useState('');
+  const [result, setResult] = useState<ErrorExplanation | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const loading = !result && !error;
+
+  // 진행 상황 표시용 스트리밍 텍스트
+  useEffect(() => {
+    const unlisten = listen<AITokenEvent>('ai:token', (event) => {
+      if (event.payload.request_id === requestId) {
+        setStreamed((prev) => prev + event.payload.token);
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [requestId]);
+
+  useEffect(() => {
+    invoke<ErrorExplanation>('ai_explain_error', { historyId: failed.historyId, requestId })
+      .then(setResult)
+      .catch((err) => setError(err as string));
+  }, [failed.historyId, requestId]);
+
+  const handleCancel = () => {
+    invoke<boolean>('ai_cancel', { requestId }).catch(console.error);
+  };
+
+  return (
+    <div className="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50">
+      <div className="bg-gray-900 rounded-lg shadow-xl w-[700px] max-h-[80vh] flex flex-col">
+        {/* Header */}
+        <div className="flex items-center justify-between p-4 border-b border-gray-700">
+          <div className="min-w-0">
+            <h2 className="text-lg font-semibold text-white">오류 설명</h2>
+            <code className="text-xs text-red-400 font-mono truncate block">
+              {failed.cmd} (exit {failed.exitCode})
+            </code>
+          </div>
+          <button onClick={onClose} className="text-gray-400 hover:text-white transition-colors" title="닫기">
+            <X size={20} />
+          </button>
+        </div>
+
+        <div className="flex-1 overflow-y-auto p-4 space-y-4">
+          {loading && (
+            <div className="space-y-3">
+              <div className="bg-gray-800 border border-gray-700 rounded p-4 text-gray-400 whitespace-pre-wrap font-mono text-xs max-h-[200px] overflow-y-auto">
+                {streamed || '분석 중...'}
+              </div>
+              <button
+                onClick={handleCancel}
+                className="w-full bg-gray-700 hover:bg-gray-600 text-white px-4 py-2 rounded flex items-center justify-center gap-2 transition-colors"
+              >
+                <Square size={16} />
+                생성 중지
+              </button>
+            </div>
+          )}
+
+          {error && (
+            <div className="bg-red-900 bg-opacity-30 border border-red-700 text-red-300 px-4 py-3 rounded">
+              <strong>오류:</strong> {error}
+            </div>
+          )}
+
+          {result && (
+            <>
+              <div className="space-y-2">
+                <h3 className="text-sm font-semibold text-gray-300">원인</h3>
+                <div className="text-white">{result.cause}</div>
+                {result.explanation && result.explanation !== result.cause && (
+                  <div className="text-sm text-gray-300 whitespace-pre-wrap">{result.explanation}</div>
+                )}
+              </div>
+
+              {result.fix_commands.length > 0 && (
+                <div className="space-y-2">
+                  <h3 className="text-sm font-semibold text-gray-300">해결 명령어</h3>
+                  {result.fix_commands.map((fix, index) => (
+                    <div
+                      key={index}
+                      className="bg-gray-800 border border-gray-700 rounded p-3 flex items-start justify-between gap-3"
+                    >
+                      <div className="flex-1 min-w-0">
+                        <code className="text-green-400 text-sm font-mono whitespace-pre-wrap">{fix.command}</code>
+                        {fix.description && <div className="text-xs text-gray-400 mt-1">{fix.description}</div>}
+                      </div>
+                      <div className="flex gap-2">
+                        <button
+                          onClick={() => navigator.clipboard.writeText(fix.command)}
+                          className="text-gray-400 hover:text-white transition-colors"
+                          title="복사"
+                        >
+                          <Copy size={16} />
+                        </button>
+                        <button
+                          onClick={() => {
+                            onInsertCommand(fix.command);
+                            onClose();
+                          }}
+                          className="text-blue-400 hover:text-blue-300 transition-colors"
+                          title="터미널에 삽입"
+                        >
+                          <TerminalIcon size={16} />
+                        </button>
+                      </div>
+                    </div>
+                  ))}
+                </div>
+              )}
+
+              <div className="text-xs text-gray-500">
+                <span className={CONFIDENCE_LABELS[result.confidence].className}>
+                  {CONFIDENCE_LABELS[result.confidence].label}
+                </span>
+                {' | '}모델: {result.model}
+                {!result.output_captured && ' | 출력이 수집되지 않음'}
+                {result.cancelled && ' | 중지됨'}
+              </div>
+            </>
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
@@ -29,11 +29,12 @@ export function useCommandInput({ terminal, enabled = true, onCommandExecuted }:
 
       // Handle Enter key (ASCII 13 or \r)
       if (charCode === 13) {
-        const command = inputBufferRef.current.trim();
+        // Keep a leading space: it marks a command that must not be recorded
+        const command = inputBufferRef.current.trimEnd();
         console.log('[useCommandInput] Enter pressed, command:', command);
 
         // Call callback if provided
-        if (command && onCommandExecuted) {
+        if (command.trim() && onCommandExecuted) {
           onCommandExecuted(command);
         }
 
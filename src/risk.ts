@@ -0,0 +1,57 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export type RiskSeverity = 'low' | 'medium' | 'high' | 'critical';
+
+export interface RiskFinding {
+  rule: string;
+  severity: RiskSeverity;
+  explanation: string;
+  matched: string;
+}
+
+export interface RiskReport {
+  command: string;
+  severity: RiskSeverity | null;
+  findings: RiskFinding[];
+  requires_confirmation: boolean;
+}
+
+// ssh_write가 위험 명령 실행을 막을 때의 오류 접두사 (risk::CONFIRMATION_REQUIRED)
+const CONFIRMATION_REQUIRED = 'CONFIRMATION_REQUIRED:';
+
+export const SEVERITY_LABELS: Record<RiskSeverity, { label: string; className: string }> = {
+  critical: { label: '치명적', className: 'bg-red-700 text-white' },
+  high: { label: '위험', className: 'bg-orange-600 text-white' },
+  medium: { label: '주의', className: 'bg-yellow-600 text-black' },
+  low: { label: '낮음', className: 'bg-gray-600 text-white' },
+};
+
+export const assessCommands = (commands: string[]) =>
+  invoke<RiskReport[]>('risk_assess', { commands });
+
+const confirmRisk = (reports: RiskReport[]) => {
+  const lines = reports.flatMap((report) => [
+    `$ ${report.command}`,
+    ...report.findings.map((f) => `  [${SEVERITY_LABELS[f.severity].label}] ${f.explanation}`),
+  ]);
+  return window.confirm(`위험할 수 있는 명령입니다.\n\n${lines.join('\n')}\n\n그래도 실행하시겠습니까?`);
+};
+
+/// 세션에 입력을 쓴다. 위험 명령은 사용자 확인 후에만 전송된다.
+export const writeToSession = async (id: string, data: string) => {
+  try {
+    await invoke("ssh_write", { id, data });
+  } catch (err) {
+    const message = String(err);
+    if (!message.startsWith(CONFIRMATION_REQUIRED)) {
+      console.error("[ssh_write error]", err);
+      return;
+    }
+    const reports: RiskReport[] = JSON.parse(message.slice(CONFIRMATION_REQUIRED.length));
+    if (confirmRisk(reports)) {
+      await invoke("ssh_write", { id, data, confirmed: true }).catch((err) => {
+        console.error("[ssh_write error]", err);
+      });
+    }
+  }
+};